@@ -1,62 +1,1763 @@
 use std::collections::HashMap;
-use std::sync::Arc;
 
 use crate::core::*;
-use tower_lsp::jsonrpc::Result;
+use hashbrown::HashSet;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
 use tower_lsp::lsp_types::*;
+use ustr::Ustr;
 
-pub fn rename_dash(
-    params: CodeActionParams,
-    diagnostic: Diagnostic,
-    snapshot: std::result::Result<Option<(Draft, Arc<RootGraph>)>, tower_lsp::jsonrpc::Error>,
-) -> Result<Option<CodeActionResponse>> {
-    if let Ok(Some((Draft::UVL { source, .. }, ..))) = snapshot {
-        let start_byte = byte_offset(&diagnostic.range.start, &source);
-        let end_byte = byte_offset(&diagnostic.range.end, &source);
-        let name = source.slice(start_byte..end_byte).as_str().unwrap();
-        let new_name = name.replace("-", "_").to_string();
+//A quick fix receives one diagnostic of the code it was registered for together with a
+//snapshot of the document and the linked graph.
+pub type QuickFix =
+    fn(&CodeActionParams, &Diagnostic, &Draft, &RootGraph) -> Vec<CodeActionOrCommand>;
 
-        let code_action_replace = CodeAction {
-            title: format!("Rename to: {}", new_name),
+//All quick fixes by diagnostic code, new fixes only have to be added here
+fn registry() -> HashMap<ErrorType, Vec<QuickFix>> {
+    let mut fixes: HashMap<ErrorType, Vec<QuickFix>> = HashMap::new();
+    let mut register = |error_type: ErrorType, fix: QuickFix| {
+        fixes.entry(error_type).or_default().push(fix);
+    };
+    register(ErrorType::FeatureNameContainsDashes, rename_dash);
+    register(ErrorType::UnresolvedReference, unresolved_reference);
+    register(ErrorType::MissingInclude, include_lang_lvls);
+    register(ErrorType::MissingStringConstraints, include_lang_lvls);
+    fixes
+}
+lazy_static! {
+    static ref QUICK_FIXES: HashMap<ErrorType, Vec<QuickFix>> = registry();
+}
+pub fn quick_fixes(error_type: ErrorType) -> &'static [QuickFix] {
+    QUICK_FIXES
+        .get(&error_type)
+        .map(|fixes| fixes.as_slice())
+        .unwrap_or(&[])
+}
+//Refactorings are offered for the requested range independent of any diagnostic
+pub type Refactoring = fn(&CodeActionParams, &Draft, &RootGraph) -> Vec<CodeActionOrCommand>;
+const REFACTORINGS: &[Refactoring] = &[extract_submodel, inline_submodel];
+pub fn refactorings(
+    params: &CodeActionParams,
+    draft: &Draft,
+    root: &RootGraph,
+) -> Vec<CodeActionOrCommand> {
+    if let Some(only) = params.context.only.as_ref() {
+        if !only.iter().any(|k| k.as_str().starts_with("refactor")) {
+            return Vec::new();
+        }
+    }
+    REFACTORINGS
+        .iter()
+        .flat_map(|refactoring| refactoring(params, draft, root))
+        .collect()
+}
+
+fn rename_dash(
+    params: &CodeActionParams,
+    diagnostic: &Diagnostic,
+    draft: &Draft,
+    _: &RootGraph,
+) -> Vec<CodeActionOrCommand> {
+    let source = match draft {
+        Draft::UVL { source, .. } => source,
+        _ => return Vec::new(),
+    };
+    let start_byte = byte_offset(&diagnostic.range.start, source);
+    let end_byte = byte_offset(&diagnostic.range.end, source);
+    let name = source.slice(start_byte..end_byte).to_string();
+    let new_name = name.replace("-", "_");
+
+    let code_action_replace = CodeAction {
+        title: format!("Rename to: {}", new_name),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::<Url, Vec<TextEdit>>::from([(
+                params.text_document.uri.clone(),
+                vec![TextEdit {
+                    range: diagnostic.range,
+                    new_text: new_name.clone(),
+                }],
+            )])),
+            document_changes: None,
+            change_annotations: None,
+        }),
+        is_preferred: Some(true),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        ..Default::default()
+    };
+    let code_action_quotes = CodeAction {
+        title: format!("Rename to: \"{}\"", name),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::<Url, Vec<TextEdit>>::from([(
+                params.text_document.uri.clone(),
+                vec![TextEdit {
+                    range: diagnostic.range,
+                    new_text: format!("\"{}\"", name),
+                }],
+            )])),
+            document_changes: None,
+            change_annotations: None,
+        }),
+        is_preferred: Some(true),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        ..Default::default()
+    };
+    vec![
+        CodeActionOrCommand::CodeAction(code_action_replace),
+        CodeActionOrCommand::CodeAction(code_action_quotes),
+    ]
+}
+fn insert_edit(uri: &Url, position: Position, new_text: String) -> WorkspaceEdit {
+    WorkspaceEdit {
+        changes: Some(HashMap::<Url, Vec<TextEdit>>::from([(
+            uri.clone(),
+            vec![TextEdit {
+                range: Range {
+                    start: position,
+                    end: position,
+                },
+                new_text,
+            }],
+        )])),
+        document_changes: None,
+        change_annotations: None,
+    }
+}
+//One indentation level deeper than indent, following the style of indent
+fn indent_unit(indent: &str) -> &'static str {
+    if indent.contains(' ') && !indent.contains('\t') {
+        "    "
+    } else {
+        "\t"
+    }
+}
+//Insert text as new lines directly below the line at byte
+fn insert_below(source: &Rope, byte: usize, text: String) -> Option<(Position, String)> {
+    let row = source.byte_to_line(byte);
+    if row + 1 < source.len_lines() {
+        Some((
+            Position {
+                line: row as u32 + 1,
+                character: 0,
+            },
+            text,
+        ))
+    } else {
+        Some((
+            lsp_position(source.len_bytes(), source)?,
+            format!("\n{}", text.trim_end_matches('\n')),
+        ))
+    }
+}
+//Features and attributes that can be referenced from a file, imported symbols
+//are prefixed by their import
+fn visible_paths(root: &RootGraph, file: &AstDocument) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut collect = |doc: &AstDocument, import: &[Ustr]| {
+        doc.visit_named_children(Symbol::Root, false, |sym, prefix| {
+            if matches!(sym, Symbol::Feature(..) | Symbol::Attribute(..)) {
+                out.push(
+                    import
+                        .iter()
+                        .chain(prefix.iter())
+                        .map(|n| quoted(n.as_str()))
+                        .join("."),
+                );
+            }
+            true
+        });
+    };
+    collect(file, &[]);
+    for (import, target) in root.fs().imports(file.id) {
+        if let Some(doc) = root.files.get(&target) {
+            collect(doc, file.import_prefix(import));
+        }
+    }
+    out
+}
+fn declare_feature(
+    root: &RootGraph,
+    file: &AstDocument,
+    path: &[Ustr],
+    diagnostic: &Diagnostic,
+) -> Vec<CodeActionOrCommand> {
+    //The closest prefix of path that resolves to a feature or a file
+    let parent = (0..path.len()).rev().find_map(|k| {
+        if k == 0 {
+            Some((
+                RootSymbol {
+                    file: file.id,
+                    sym: Symbol::Root,
+                },
+                k,
+            ))
+        } else {
+            root.resolve(file.id, &path[..k])
+                .find(|s| matches!(s.sym, Symbol::Feature(..) | Symbol::Root))
+                .map(|s| (s, k))
+        }
+    });
+    let (parent, name) = match parent {
+        Some((parent, k)) if k + 1 == path.len() => (parent, quoted(path[k].as_str())),
+        _ => return vec![],
+    };
+    let doc = root.file(parent.file);
+    let mut groups = Vec::new();
+    if matches!(parent.sym, Symbol::Root) {
+        doc.visit_children(Symbol::Root, false, |sym| {
+            if matches!(sym, Symbol::Group(..)) {
+                groups.push(sym);
+            }
+            true
+        });
+    } else {
+        groups.extend(
+            doc.direct_children(parent.sym)
+                .filter(|sym| matches!(sym, Symbol::Group(..))),
+        );
+    }
+    let action = |title: String, edit: Option<(Position, String)>| {
+        edit.map(|(position, text)| {
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(insert_edit(&doc.uri, position, text)),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                ..Default::default()
+            })
+        })
+    };
+    let mut actions = Vec::new();
+    for group in groups.into_iter().take(16) {
+        let span = match doc.span(group) {
+            Some(span) => span,
+            None => continue,
+        };
+        let group_row = doc.source.byte_to_line(span.start);
+        let group_indent = line_indent(&doc.source, group_row);
+        let indent = doc
+            .direct_children(group)
+            .find_map(|child| doc.span(child))
+            .map(|child| line_indent(&doc.source, doc.source.byte_to_line(child.start)))
+            .unwrap_or_else(|| format!("{}{}", group_indent, indent_unit(&group_indent)));
+        let owner = doc
+            .parent(group, false)
+            .and_then(|p| doc.name(p))
+            .map(|p| p.to_string())
+            .unwrap_or_default();
+        let mode = doc.source.byte_slice(span.clone()).to_string();
+        actions.extend(action(
+            format!("Declare feature {} in {} group of {}", name, mode, owner),
+            insert_below(&doc.source, span.start, format!("{}{}\n", indent, name)),
+        ));
+    }
+    if actions.is_empty() {
+        if let Some(span) = doc.span(parent.sym) {
+            let indent = line_indent(&doc.source, doc.source.byte_to_line(span.start));
+            let unit = indent_unit(&indent);
+            actions.extend(action(
+                format!(
+                    "Declare feature {} in new optional group of {}",
+                    name,
+                    doc.name(parent.sym)
+                        .map(|n| n.to_string())
+                        .unwrap_or_default()
+                ),
+                insert_below(
+                    &doc.source,
+                    span.start,
+                    format!("{indent}{unit}optional\n{indent}{unit}{unit}{name}\n"),
+                ),
+            ));
+        }
+    }
+    actions
+}
+fn replace_reference(
+    root: &RootGraph,
+    file: &AstDocument,
+    path: &[Ustr],
+    uri: &Url,
+    diagnostic: &Diagnostic,
+) -> Vec<CodeActionOrCommand> {
+    let text = path.iter().map(|n| quoted(n.as_str())).join(".");
+    let mut candidates: Vec<(usize, String)> = visible_paths(root, file)
+        .into_iter()
+        .map(|p| (strsim::levenshtein(&text, &p), p))
+        .filter(|(dist, _)| *dist > 0)
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates
+        .into_iter()
+        .take(3)
+        .enumerate()
+        .map(|(i, (_, name))| {
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Replace with: {}", name),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::<Url, Vec<TextEdit>>::from([(
+                        uri.clone(),
+                        vec![TextEdit {
+                            range: diagnostic.range,
+                            new_text: name,
+                        }],
+                    )])),
+                    document_changes: None,
+                    change_annotations: None,
+                }),
+                is_preferred: Some(i == 0),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+//Insert an imports entry, a new imports section is placed in front of the features
+fn import_entry(file: &AstDocument, entry: &str) -> Option<TextEdit> {
+    let (position, new_text) = if let Some(imports) = top_level_section(&file.tree, "imports") {
+        let indent = imports
+            .named_child(1)
+            .filter(|n| n.kind() == "blk")
+            .map(|n| line_indent(&file.source, n.start_position().row))
+            .unwrap_or_else(|| "\t".into());
+        insert_below(
+            &file.source,
+            imports.start_byte(),
+            format!("{}{}\n", indent, entry),
+        )?
+    } else if let Some(features) = top_level_section(&file.tree, "features") {
+        (
+            lsp_position(features.start_byte(), &file.source)?,
+            format!("imports\n\t{}\n\n", entry),
+        )
+    } else {
+        (
+            lsp_position(file.source.len_bytes(), &file.source)?,
+            format!("\nimports\n\t{}\n", entry),
+        )
+    };
+    Some(TextEdit {
+        range: Range {
+            start: position,
+            end: position,
+        },
+        new_text,
+    })
+}
+//Import prefix that does not clash with any top level name of file
+fn free_alias(file: &AstDocument, import_path: &[Ustr]) -> Option<Ustr> {
+    let taken = |name: Ustr| {
+        file.lookup(Symbol::Root, std::slice::from_ref(&name), |_| true)
+            .next()
+            .is_some()
+    };
+    if !taken(import_path[0]) {
+        return None;
+    }
+    let base = import_path.last().unwrap();
+    (1..)
+        .map(|i| {
+            if i == 1 {
+                *base
+            } else {
+                format!("{}_{}", base, i).as_str().into()
+            }
+        })
+        .find(|alias| !taken(*alias))
+}
+//Import files that define path, the import path is relative to the directory of file
+//and has to resolve through the file system like any other import
+fn add_import(
+    root: &RootGraph,
+    file: &AstDocument,
+    path: &[Ustr],
+    uri: &Url,
+    diagnostic: &Diagnostic,
+) -> Vec<CodeActionOrCommand> {
+    let dir = &file.path[..file.path.len().saturating_sub(1)];
+    let reference = path.iter().map(|n| quoted(n.as_str())).join(".");
+    let mut actions = Vec::new();
+    for (&id, target) in root.files.iter() {
+        if id == file.id
+            || !target.path.starts_with(dir)
+            || target
+                .lookup(Symbol::Root, path, |sym| {
+                    matches!(sym, Symbol::Feature(..) | Symbol::Attribute(..))
+                })
+                .next()
+                .is_none()
+        {
+            continue;
+        }
+        let import_path = &target.path[dir.len()..];
+        if import_path.is_empty() || root.fs().resolve(file.id, import_path) != Some(id) {
+            continue;
+        }
+        if root.fs().recursive_imports(id).contains(&file.id) {
+            continue;
+        }
+        let import_text = import_path.iter().map(|n| quoted(n.as_str())).join(".");
+        let mut edits = Vec::new();
+        let prefix = if let Some(import) = root.fs().imports_connecting(file.id, id).next() {
+            file.import_prefix(import)
+                .iter()
+                .map(|n| quoted(n.as_str()))
+                .join(".")
+        } else {
+            let alias = free_alias(file, import_path);
+            let entry = match alias {
+                Some(alias) => format!("{} as {}", import_text, quoted(alias.as_str())),
+                None => import_text.clone(),
+            };
+            match import_entry(file, &entry) {
+                Some(edit) => edits.push(edit),
+                None => continue,
+            }
+            alias
+                .map(|alias| quoted(alias.as_str()))
+                .unwrap_or_else(|| import_text.clone())
+        };
+        let new_reference = format!("{}.{}", prefix, reference);
+        edits.push(TextEdit {
+            range: diagnostic.range,
+            new_text: new_reference.clone(),
+        });
+        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+            title: if edits.len() > 1 {
+                format!("Import {} and use {}", import_text, new_reference)
+            } else {
+                format!("Use {}", new_reference)
+            },
             kind: Some(CodeActionKind::QUICKFIX),
             edit: Some(WorkspaceEdit {
-                changes: Some(HashMap::<Url, Vec<TextEdit>>::from([(
-                    params.text_document.uri.clone(),
-                    vec![TextEdit {
-                        range: diagnostic.range,
-                        new_text: new_name.clone(),
-                    }],
-                )])),
+                changes: Some(HashMap::<Url, Vec<TextEdit>>::from([(uri.clone(), edits)])),
                 document_changes: None,
                 change_annotations: None,
             }),
-            is_preferred: Some(true),
             diagnostics: Some(vec![diagnostic.clone()]),
             ..Default::default()
-        };
-        let code_action_quotes = CodeAction {
-            title: format!("Rename to: {}", format!("\"{}\"", name)),
+        }));
+    }
+    actions.sort_by(|a, b| match (a, b) {
+        (CodeActionOrCommand::CodeAction(a), CodeActionOrCommand::CodeAction(b)) => {
+            a.title.cmp(&b.title)
+        }
+        _ => std::cmp::Ordering::Equal,
+    });
+    actions
+}
+fn unresolved_reference(
+    params: &CodeActionParams,
+    diagnostic: &Diagnostic,
+    _: &Draft,
+    root: &RootGraph,
+) -> Vec<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    let file = match root.file_by_uri(uri) {
+        Some(file) => file,
+        None => return Vec::new(),
+    };
+    let reference = match file
+        .all_references()
+        .find(|r| file.lsp_range(*r) == Some(diagnostic.range))
+    {
+        Some(reference) => reference,
+        None => return Vec::new(),
+    };
+    let path = file.path(reference);
+    let mut actions = replace_reference(root, file, path, uri, diagnostic);
+    actions.extend(add_import(root, file, path, uri, diagnostic));
+    actions.extend(declare_feature(root, file, path, diagnostic));
+    actions
+}
+//Smallest set of include entries that covers every language level the file needs
+fn include_lang_lvls(
+    params: &CodeActionParams,
+    diagnostic: &Diagnostic,
+    _: &Draft,
+    root: &RootGraph,
+) -> Vec<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    let file = match root.file_by_uri(uri) {
+        Some(file) => file,
+        None => return Vec::new(),
+    };
+    //String comparisons are only known from the type checker diagnostic
+    let needed = match ErrorType::from_diagnostic(diagnostic) {
+        ErrorType::MissingStringConstraints => vec![LanguageLevel::Type(vec![
+            LanguageLevelType::StringConstraints,
+        ])],
+        _ => Vec::new(),
+    };
+    let levels = missing_lang_lvls(file, needed);
+    if levels.is_empty() {
+        return Vec::new();
+    }
+    let entries: Vec<String> = levels.iter().flat_map(|l| l.entries()).collect();
+    let edit = if let Some(include) = top_level_section(&file.tree, "include") {
+        let indent = include
+            .named_child(1)
+            .filter(|n| n.kind() == "blk")
+            .map(|n| line_indent(&file.source, n.start_position().row))
+            .unwrap_or_else(|| "\t".into());
+        insert_below(
+            &file.source,
+            include.start_byte(),
+            entries
+                .iter()
+                .map(|e| format!("{}{}\n", indent, e))
+                .collect(),
+        )
+    } else if let Some(next) = ["imports", "features", "constraints"]
+        .iter()
+        .find_map(|kind| top_level_section(&file.tree, kind))
+    {
+        lsp_position(next.start_byte(), &file.source)
+            .map(|position| (position, format!("include\n\t{}\n\n", entries.join("\n\t"))))
+    } else {
+        lsp_position(file.source.len_bytes(), &file.source)
+            .map(|position| (position, format!("\ninclude\n\t{}\n", entries.join("\n\t"))))
+    };
+    match edit {
+        Some((position, new_text)) => vec![CodeActionOrCommand::CodeAction(CodeAction {
+            title: format!("Include {}", entries.join(", ")),
             kind: Some(CodeActionKind::QUICKFIX),
-            edit: Some(WorkspaceEdit {
-                changes: Some(HashMap::<Url, Vec<TextEdit>>::from([(
-                    params.text_document.uri.clone(),
-                    vec![TextEdit {
-                        range: diagnostic.range,
-                        new_text: format!("\"{}\"", name),
-                    }],
-                )])),
-                document_changes: None,
-                change_annotations: None,
-            }),
+            edit: Some(insert_edit(uri, position, new_text)),
             is_preferred: Some(true),
             diagnostics: Some(vec![diagnostic.clone()]),
             ..Default::default()
+        })],
+        None => Vec::new(),
+    }
+}
+//The blk declaring or holding sym
+fn symbol_blk<'a>(file: &'a AstDocument, sym: Symbol) -> Option<tree_sitter::Node<'a>> {
+    let span = file.span(sym)?;
+    containing_blk(
+        file.tree
+            .root_node()
+            .descendant_for_byte_range(span.start, span.end)?,
+    )
+}
+//End of node without trailing whitespace
+fn trimmed_end(source: &Rope, node: tree_sitter::Node) -> usize {
+    let mut end = node.end_byte();
+    while end > node.start_byte() && source.byte(end - 1).is_ascii_whitespace() {
+        end -= 1;
+    }
+    end
+}
+fn document_edit(uri: &Url, edits: Vec<TextEdit>) -> DocumentChangeOperation {
+    DocumentChangeOperation::Edit(TextDocumentEdit {
+        text_document: OptionalVersionedTextDocumentIdentifier {
+            uri: uri.clone(),
+            version: None,
+        },
+        edits: edits.into_iter().map(OneOf::Left).collect(),
+    })
+}
+//Insert prefix in front of every reference in file that binds to a moved symbol of target,
+//references inside skip are moved themselves and stay untouched
+fn prefix_references(
+    root: &RootGraph,
+    file: &AstDocument,
+    target: FileID,
+    moved: &HashSet<Symbol>,
+    skip: &std::ops::Range<usize>,
+    prefix: Ustr,
+) -> Vec<TextEdit> {
+    file.all_references()
+        .filter_map(|r| {
+            let span = file.span(r)?;
+            if file.id == target && span.start >= skip.start && span.end <= skip.end {
+                return None;
+            }
+            let path = file.path(r);
+            let at = root
+                .resolve_with_binding(file.id, path)
+                .find_map(|binding| {
+                    binding
+                        .iter()
+                        .find(|(s, _)| s.file == target && moved.contains(&s.sym))
+                        .map(|(_, end)| end - 1)
+                })?;
+            let new_text = path[..at]
+                .iter()
+                .chain(std::iter::once(&prefix))
+                .chain(path[at..].iter())
+                .map(|n| quoted(n.as_str()))
+                .join(".");
+            Some(TextEdit {
+                range: file.lsp_range(r)?,
+                new_text,
+            })
+        })
+        .collect()
+}
+//File names are plain identifiers so the import path needs no quotes
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        stem
+    } else {
+        format!("model_{}", stem)
+    }
+}
+//Same as prefix_references for the keys of a configuration, the prefix is inserted into the
+//key holding the first moved segment
+fn prefix_config_keys(
+    root: &RootGraph,
+    config: &ConfigDocument,
+    target: FileID,
+    moved: &HashSet<Symbol>,
+    prefix: Ustr,
+) -> Vec<TextEdit> {
+    let Some(file_config) = config.config.as_ref() else {
+        return Vec::new();
+    };
+    let mut leaves = Vec::new();
+    config_leaves(&file_config.config, &mut vec![], &mut leaves);
+    //segment in front of which the prefix goes of each key, keys are identified by their start
+    let mut inserted: HashMap<usize, (&Path, usize)> = HashMap::new();
+    for chain in leaves.iter() {
+        let path: Vec<Ustr> = chain.iter().flat_map(|k| k.names.iter().cloned()).collect();
+        let Some(at) = root
+            .resolve_with_binding(file_config.file, &path)
+            .find_map(|binding| {
+                binding
+                    .iter()
+                    .find(|(s, _)| s.file == target && moved.contains(&s.sym))
+                    .map(|(_, end)| end - 1)
+            })
+        else {
+            continue;
+        };
+        let mut offset = 0;
+        for key in chain.iter() {
+            if (offset..offset + key.len()).contains(&at) {
+                inserted.insert(key.spans[0].start, (*key, at - offset));
+                break;
+            }
+            offset += key.len();
+        }
+    }
+    inserted
+        .values()
+        .filter_map(|(key, at)| {
+            let new_text = key.names[..*at]
+                .iter()
+                .chain(std::iter::once(&prefix))
+                .chain(key.names[*at..].iter())
+                .map(|n| n.as_str())
+                .join(".");
+            Some(TextEdit {
+                range: lsp_range(
+                    key.spans[0].start..key.spans[key.len() - 1].end,
+                    &config.source,
+                )?,
+                new_text,
+            })
+        })
+        .collect()
+}
+//The selected feature of an extract refactoring with everything that is moved along
+struct Extraction<'a> {
+    file: &'a AstDocument,
+    name: Ustr,
+    blk: tree_sitter::Node<'a>,
+    moved: HashSet<Symbol>,
+    stem: String,
+    target: Url,
+    //imports used inside the subtree, they are copied into the new file
+    imports: Vec<Symbol>,
+}
+//Refactorings only compute their edit when they are resolved, the data is the request
+#[derive(Serialize, Deserialize)]
+struct RefactoringData {
+    uri: Url,
+    position: Position,
+}
+fn extraction<'a>(
+    uri: &Url,
+    position: &Position,
+    root: &'a RootGraph,
+) -> std::result::Result<Extraction<'a>, Option<String>> {
+    let file = root.file_by_uri(uri).ok_or(None)?;
+    let sym = match file.find(byte_offset(position, &file.source)) {
+        Some(sym @ Symbol::Feature(..)) => sym,
+        _ => return Err(None),
+    };
+    //Root features are already a model of their own
+    if matches!(file.parent(sym, false), None | Some(Symbol::Root)) {
+        return Err(None);
+    }
+    let name = file.name(sym).ok_or(None)?;
+    let blk = symbol_blk(file, sym).ok_or(None)?;
+    let mut moved = HashSet::new();
+    moved.insert(sym);
+    file.visit_children(sym, false, |child| {
+        moved.insert(child);
+        true
+    });
+    //A submodel can only see its own features and its imports
+    let span = blk.byte_range();
+    let mut imports = Vec::new();
+    for r in file.all_references() {
+        if !file
+            .span(r)
+            .map(|s| s.start >= span.start && s.end <= span.end)
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        let path = file.path(r);
+        let import = root
+            .resolve_with_binding(file.id, path)
+            .find_map(|binding| {
+                binding
+                    .first()
+                    .filter(|(s, _)| s.file == file.id && matches!(s.sym, Symbol::Import(..)))
+                    .map(|(s, _)| s.sym)
+            });
+        if let Some(import) = import {
+            if !imports.contains(&import) {
+                imports.push(import);
+            }
+        } else if root
+            .resolve(file.id, path)
+            .any(|s| s.file == file.id && !moved.contains(&s.sym))
+        {
+            return Err(Some(format!(
+                "{} refers to {} outside of the subtree",
+                name,
+                path.iter().join(".")
+            )));
+        }
+    }
+    let base = file_stem(name.as_str());
+    let (stem, target) = (1..100)
+        .map(|i| {
+            if i == 1 {
+                base.clone()
+            } else {
+                format!("{}_{}", base, i)
+            }
+        })
+        .find_map(|stem| {
+            let target = create_new_uvl(file.uri.to_string(), format!("{}.uvl", stem))?;
+            let exists = root.file_by_uri(&target).is_some()
+                || target.to_file_path().map(|p| p.exists()).unwrap_or(false);
+            let taken = file
+                .lookup(Symbol::Root, &[stem.as_str().into()], |s| {
+                    !moved.contains(&s)
+                })
+                .next()
+                .is_some();
+            (!exists && !taken).then_some((stem, target))
+        })
+        .ok_or(None)?;
+    Ok(Extraction {
+        file,
+        name,
+        blk,
+        moved,
+        stem,
+        target,
+        imports,
+    })
+}
+//Move the subtree of the selected feature into a new file next to the current one. The
+//subtree is replaced by a reference and all references to moved symbols are routed through
+//the new import, in this file, in every file importing it and in their configurations.
+fn extract_edit(extraction: &Extraction, root: &RootGraph) -> Option<WorkspaceEdit> {
+    let Extraction {
+        file,
+        name,
+        blk,
+        moved,
+        stem,
+        target,
+        imports,
+    } = extraction;
+    let source = &file.source;
+    let start = source.line_to_byte(blk.start_position().row);
+    let end = trimmed_end(source, *blk);
+    let indent = line_indent(source, blk.start_position().row);
+    let unit = indent_unit(&indent);
+    let mut content = String::new();
+    if let Some(include) = top_level_section(&file.tree, "include") {
+        content.push_str(
+            &source
+                .byte_slice(include.start_byte()..trimmed_end(source, include))
+                .to_string(),
+        );
+        content.push_str("\n\n");
+    }
+    if !imports.is_empty() {
+        content.push_str("imports\n");
+        for import in imports.iter() {
+            let entry = symbol_blk(file, *import)?;
+            content.push_str(unit);
+            content.push_str(
+                &source
+                    .byte_slice(entry.start_byte()..trimmed_end(source, entry))
+                    .to_string(),
+            );
+            content.push('\n');
+        }
+        content.push('\n');
+    }
+    content.push_str("features\n");
+    for line in source.byte_slice(start..end).lines() {
+        let line = line.to_string();
+        let line = line.trim_end_matches(['\n', '\r']);
+        if !line.trim().is_empty() {
+            content.push_str(unit);
+            content.push_str(
+                line.strip_prefix(indent.as_str())
+                    .unwrap_or(line.trim_start()),
+            );
+        }
+        content.push('\n');
+    }
+    let header = blk.child_by_field_name("header")?;
+    let prefix: Ustr = stem.as_str().into();
+    let mut edits = prefix_references(root, file, file.id, moved, &(start..end), prefix);
+    edits.extend(import_entry(file, stem));
+    edits.extend(
+        lsp_range(header.start_byte()..end, source).map(|range| TextEdit {
+            range,
+            new_text: format!("{}.{}", stem, quoted(name.as_str())),
+        }),
+    );
+    let mut operations = vec![
+        DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+            uri: target.clone(),
+            options: None,
+            annotation_id: None,
+        })),
+        document_edit(
+            target,
+            vec![TextEdit {
+                range: Range::default(),
+                new_text: content,
+            }],
+        ),
+        document_edit(&file.uri, edits),
+    ];
+    let visible = root.fs().recursive_imported(file.id);
+    for id in visible.iter() {
+        if *id == file.id {
+            continue;
+        }
+        let importer = root.file(*id);
+        let edits = prefix_references(root, importer, file.id, moved, &(0..0), prefix);
+        if !edits.is_empty() {
+            operations.push(document_edit(&importer.uri, edits));
+        }
+    }
+    for config in root.configs.values() {
+        if !config
+            .config
+            .as_ref()
+            .map(|c| c.file == file.id || visible.contains(&c.file))
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        let edits = prefix_config_keys(root, config, file.id, moved, prefix);
+        if !edits.is_empty() {
+            operations.push(document_edit(&config.uri, edits));
+        }
+    }
+    Some(WorkspaceEdit {
+        changes: None,
+        document_changes: Some(DocumentChanges::Operations(operations)),
+        change_annotations: None,
+    })
+}
+//Offered without an edit, it is computed in resolve_refactoring once the action is chosen
+fn extract_submodel(
+    params: &CodeActionParams,
+    _: &Draft,
+    root: &RootGraph,
+) -> Vec<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    let action = match extraction(uri, &params.range.start, root) {
+        Ok(extraction) => CodeAction {
+            title: format!("Extract {} into {}.uvl", extraction.name, extraction.stem),
+            kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+            data: serde_json::to_value(RefactoringData {
+                uri: uri.clone(),
+                position: params.range.start,
+            })
+            .ok(),
+            ..Default::default()
+        },
+        Err(Some(reason)) => CodeAction {
+            title: "Extract into a submodel".into(),
+            kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+            disabled: Some(CodeActionDisabled { reason }),
+            ..Default::default()
+        },
+        Err(None) => return Vec::new(),
+    };
+    vec![CodeActionOrCommand::CodeAction(action)]
+}
+//Uri of the document a refactoring without edit was offered for
+pub fn refactoring_uri(action: &CodeAction) -> Option<Url> {
+    let kind = action.kind.as_ref()?;
+    if (kind != &CodeActionKind::REFACTOR_EXTRACT && kind != &CodeActionKind::REFACTOR_INLINE)
+        || action.edit.is_some()
+    {
+        return None;
+    }
+    serde_json::from_value::<RefactoringData>(action.data.clone()?)
+        .ok()
+        .map(|data| data.uri)
+}
+//Fill in the edit of a refactoring, the action is returned as is when it no longer applies
+pub fn resolve_refactoring(mut action: CodeAction, root: &RootGraph) -> CodeAction {
+    let Some(data) = action
+        .data
+        .clone()
+        .and_then(|data| serde_json::from_value::<RefactoringData>(data).ok())
+    else {
+        return action;
+    };
+    match action.kind.as_ref() {
+        Some(kind) if kind == &CodeActionKind::REFACTOR_EXTRACT => {
+            if let Ok(extraction) = extraction(&data.uri, &data.position, root) {
+                action.edit = extract_edit(&extraction, root);
+            }
+        }
+        Some(kind) if kind == &CodeActionKind::REFACTOR_INLINE => {
+            if let Ok(inlining) = inlining(&data.uri, &data.position, root) {
+                action.edit = inline_edit(&inlining, root);
+            }
+        }
+        _ => {}
+    }
+    action
+}
+//Remove the segments bound to import of origin from every reference of file that passes
+//through it, references inside skip are left alone
+fn strip_import_references(
+    root: &RootGraph,
+    file: &AstDocument,
+    origin: FileID,
+    import: Symbol,
+    skip: &std::ops::Range<usize>,
+) -> Vec<TextEdit> {
+    let prefix_len = root.file(origin).import_prefix(import).len();
+    file.all_references()
+        .filter_map(|r| {
+            let span = file.span(r)?;
+            if span.start >= skip.start && span.end <= skip.end {
+                return None;
+            }
+            let path = file.path(r);
+            let end = import_binding(root, file.id, path, origin, import)?;
+            let new_text = path[..end - prefix_len]
+                .iter()
+                .chain(path[end..].iter())
+                .map(|n| quoted(n.as_str()))
+                .join(".");
+            Some(TextEdit {
+                range: file.lsp_range(r)?,
+                new_text,
+            })
+        })
+        .collect()
+}
+//End offset in path of the segments bound to import of origin
+fn import_binding(
+    root: &RootGraph,
+    file: FileID,
+    path: &[Ustr],
+    origin: FileID,
+    import: Symbol,
+) -> Option<usize> {
+    root.resolve_with_binding(file, path).find_map(|binding| {
+        binding
+            .iter()
+            .find(|(s, _)| s.file == origin && s.sym == import)
+            .map(|(_, end)| *end)
+    })
+}
+fn config_leaves<'a>(
+    entries: &'a [ConfigEntry],
+    keys: &mut Vec<&'a Path>,
+    out: &mut Vec<Vec<&'a Path>>,
+) {
+    for entry in entries {
+        match entry {
+            ConfigEntry::Value(key, _) => {
+                keys.push(key);
+                out.push(keys.clone());
+                keys.pop();
+            }
+            ConfigEntry::Import(key, children) => {
+                keys.push(key);
+                config_leaves(children, keys, out);
+                keys.pop();
+            }
+        }
+    }
+}
+//Key chains of the objects without any entry
+fn config_empty_objects<'a>(
+    entries: &'a [ConfigEntry],
+    keys: &mut Vec<&'a Path>,
+    out: &mut Vec<Vec<&'a Path>>,
+) {
+    for entry in entries {
+        if let ConfigEntry::Import(key, children) = entry {
+            keys.push(key);
+            if children.is_empty() {
+                out.push(keys.clone());
+            }
+            config_empty_objects(children, keys, out);
+            keys.pop();
+        }
+    }
+}
+//Span of the pair starting at quote and ending at close together with the comma separating it
+//from its neighbours and the whitespace up to the next entry, so neither a trailing comma nor
+//an empty line is left behind
+fn pair_with_comma(text: &str, quote: usize, close: usize) -> std::ops::Range<usize> {
+    if let Some(next) = text[close + 1..].trim_start().strip_prefix(',') {
+        return quote..text.len() - next.trim_start().len();
+    }
+    let before = text[..quote].trim_end();
+    if before.ends_with(',') {
+        return before.len() - 1..close + 1;
+    }
+    before.len()..close + 1
+}
+//Width of the indentation of the line holding byte
+fn indent_width(text: &str, byte: usize) -> usize {
+    let line = &text[text[..byte].rfind('\n').map(|i| i + 1).unwrap_or(0)..];
+    line.len() - line.trim_start_matches([' ', '\t']).len()
+}
+//Byte of the bracket closing the object opened at open, strings are skipped
+fn closing_brace(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+//Same as strip_import_references for the keys of a configuration. Keys are relative to their
+//enclosing object so a segment is removed from the key holding it, objects whose key is removed
+//completely are merged into their parent.
+fn strip_config_keys(
+    root: &RootGraph,
+    config: &ConfigDocument,
+    origin: FileID,
+    import: Symbol,
+) -> Vec<TextEdit> {
+    let Some(file_config) = config.config.as_ref() else {
+        return Vec::new();
+    };
+    let prefix_len = root.file(origin).import_prefix(import).len();
+    let mut leaves = Vec::new();
+    config_leaves(&file_config.config, &mut vec![], &mut leaves);
+    //removed segments of each key, keys are identified by their start
+    let mut removed: HashMap<usize, (&Path, bool, Vec<usize>)> = HashMap::new();
+    for chain in leaves.iter() {
+        let path: Vec<Ustr> = chain.iter().flat_map(|k| k.names.iter().cloned()).collect();
+        let Some(end) = import_binding(root, file_config.file, &path, origin, import) else {
+            continue;
         };
-        return Ok(Some(vec![
-            CodeActionOrCommand::CodeAction(code_action_replace),
-            CodeActionOrCommand::CodeAction(code_action_quotes),
-        ]));
+        let mut offset = 0;
+        for (depth, key) in chain.iter().enumerate() {
+            let local: Vec<usize> = (end - prefix_len..end)
+                .filter(|i| (offset..offset + key.len()).contains(i))
+                .map(|i| i - offset)
+                .collect();
+            if !local.is_empty() {
+                removed.insert(key.spans[0].start, (*key, depth + 1 < chain.len(), local));
+            }
+            offset += key.len();
+        }
+    }
+    let text = config.source.to_string();
+    let mut edits = Vec::new();
+    let mut removals = Vec::new();
+    //first and last byte of the entries of each merged object
+    let mut merged = Vec::new();
+    for (key, is_object, local) in removed.values() {
+        let start = key.spans[0].start;
+        let end = key.spans[key.len() - 1].end;
+        if local.len() < key.len() {
+            let new_text = key
+                .names
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !local.contains(i))
+                .map(|(_, n)| n.as_str())
+                .join(".");
+            edits.extend(
+                lsp_range(start..end, &config.source).map(|range| TextEdit { range, new_text }),
+            );
+        } else if *is_object {
+            let Some(quote) = text[..start].rfind('"') else {
+                continue;
+            };
+            let Some(open) = text[end..].find('{').map(|i| i + end) else {
+                continue;
+            };
+            let Some(close) = closing_brace(&text, open) else {
+                continue;
+            };
+            //The first entry takes the place of the key, the brace goes with the whitespace in
+            //front of it so a comma after it follows the last entry
+            let first = text.len() - text[open + 1..].trim_start().len();
+            let last = text[..close].trim_end().len();
+            removals.push(quote..first);
+            removals.push(last..close + 1);
+            merged.push((quote, first, last));
+        }
+    }
+    //Empty objects of the import have nothing to merge, they are dropped
+    let mut empty = Vec::new();
+    config_empty_objects(&file_config.config, &mut vec![], &mut empty);
+    for chain in empty.iter() {
+        let path: Vec<Ustr> = chain.iter().flat_map(|k| k.names.iter().cloned()).collect();
+        if import_binding(root, file_config.file, &path, origin, import) != Some(path.len()) {
+            continue;
+        }
+        let key = chain[chain.len() - 1];
+        let end = key.spans[key.len() - 1].end;
+        let Some(quote) = text[..key.spans[0].start].rfind('"') else {
+            continue;
+        };
+        let Some(close) = text[end..]
+            .find('{')
+            .and_then(|open| closing_brace(&text, open + end))
+        else {
+            continue;
+        };
+        removals.push(pair_with_comma(&text, quote, close));
+    }
+    //Lines inside a merged object lose the indentation of its entries, lines starting in a
+    //removed span are already placed by it
+    let mut line = 0;
+    while let Some(next) = text[line..].find('\n') {
+        line += next + 1;
+        if removals.iter().any(|r| r.contains(&line)) {
+            continue;
+        }
+        let dedent: usize = merged
+            .iter()
+            .filter(|(_, first, last)| (*first..*last).contains(&line))
+            .map(|(quote, first, _)| {
+                indent_width(&text, *first).saturating_sub(indent_width(&text, *quote))
+            })
+            .sum();
+        if dedent > 0 {
+            removals.push(line..line + dedent.min(indent_width(&text, line)));
+        }
+    }
+    edits.extend(removals.into_iter().filter_map(|span| {
+        Some(TextEdit {
+            range: lsp_range(span, &config.source)?,
+            new_text: "".into(),
+        })
+    }));
+    edits
+}
+//Lines of node including the line break after it
+fn whole_lines(source: &Rope, node: tree_sitter::Node) -> std::ops::Range<usize> {
+    let start = source.line_to_byte(node.start_position().row);
+    let last = source.byte_to_line(trimmed_end(source, node));
+    let end = if last + 1 < source.len_lines() {
+        source.line_to_byte(last + 1)
     } else {
-        return Ok(None);
+        source.len_bytes()
+    };
+    start..end
+}
+//Text of a blk with its first line unindented and all other lines moved from the blk's
+//indentation to indent
+fn reindent(source: &Rope, blk: tree_sitter::Node, indent: &str) -> String {
+    let old = line_indent(source, blk.start_position().row);
+    source
+        .byte_slice(blk.start_byte()..trimmed_end(source, blk))
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let line = line.to_string();
+            let line = line.trim_end_matches(['\n', '\r']);
+            if i == 0 {
+                line.to_string()
+            } else if line.trim().is_empty() {
+                "".to_string()
+            } else {
+                format!(
+                    "{}{}",
+                    indent,
+                    line.strip_prefix(old.as_str()).unwrap_or(line.trim_start())
+                )
+            }
+        })
+        .join("\n")
+}
+//The reference of an imported submodel in the feature tree that is replaced by its features
+struct Inlining<'a> {
+    file: &'a AstDocument,
+    import: Symbol,
+    target: &'a AstDocument,
+    prefix: String,
+    tree_ref: Symbol,
+    //root feature of the submodel
+    feature: Symbol,
+}
+//The import selected at position, Err holds the prefix and the reason it can not be inlined
+fn inlining<'a>(
+    uri: &Url,
+    position: &Position,
+    root: &'a RootGraph,
+) -> std::result::Result<Inlining<'a>, Option<(String, String)>> {
+    let file = root.file_by_uri(uri).ok_or(None)?;
+    let import = match file.find(byte_offset(position, &file.source)).ok_or(None)? {
+        sym @ Symbol::Import(..) => sym,
+        r @ Symbol::Reference(..) => root
+            .resolve_with_binding(file.id, file.path(r))
+            .find_map(|binding| {
+                binding
+                    .first()
+                    .filter(|(s, _)| s.file == file.id && matches!(s.sym, Symbol::Import(..)))
+                    .map(|(s, _)| s.sym)
+            })
+            .ok_or(None)?,
+        _ => return Err(None),
+    };
+    let (_, target_id) = root
+        .fs()
+        .imports(file.id)
+        .find(|(s, _)| *s == import)
+        .ok_or(None)?;
+    let target = root.file(target_id);
+    let prefix = file.import_prefix(import).iter().join(".");
+    let refused = |reason: String| Err(Some((prefix.clone(), reason)));
+    let importers = root.fs().imported(target_id).count();
+    if importers > 1 {
+        return refused(format!(
+            "{} is imported {} times, inlining it would merge its instances",
+            prefix, importers
+        ));
+    }
+    //References in the feature tree to a root feature of the submodel
+    let tree_refs: Vec<(Symbol, Symbol)> = file
+        .all_references()
+        .filter_map(|r| {
+            if header_kind(symbol_blk(file, r)?) != "ref" {
+                return None;
+            }
+            root.resolve(file.id, file.path(r))
+                .find(|s| {
+                    s.file == target_id
+                        && matches!(s.sym, Symbol::Feature(..))
+                        && target.parent(s.sym, false) == Some(Symbol::Root)
+                })
+                .map(|s| (r, s.sym))
+        })
+        .collect();
+    let (tree_ref, feature) = match tree_refs.as_slice() {
+        [single] => *single,
+        [] => return refused(format!("{} is not referenced in the feature tree", prefix)),
+        _ => {
+            return refused(format!(
+            "{} is referenced {} times in the feature tree, inlining it would merge its instances",
+            prefix,
+            tree_refs.len()
+        ))
+        }
+    };
+    if target.all_imports().next().is_some() {
+        return refused(format!("{} has imports of its own", prefix));
+    }
+    if let Some(clash) = target
+        .all_features()
+        .filter_map(|f| target.name(f))
+        .find(|name| {
+            file.lookup(Symbol::Root, std::slice::from_ref(name), |s| {
+                matches!(s, Symbol::Feature(..))
+            })
+            .next()
+            .is_some()
+        })
+    {
+        return refused(format!("feature {} already exists", clash));
+    }
+    Ok(Inlining {
+        file,
+        import,
+        target,
+        prefix,
+        tree_ref,
+        feature,
+    })
+}
+//Copy the features of an imported submodel in place of its reference in the feature tree,
+//move its constraints over and drop the import. Paths through the import are shortened in all
+//files and configurations that can see them.
+fn inline_edit(inlining: &Inlining, root: &RootGraph) -> Option<WorkspaceEdit> {
+    let Inlining {
+        file,
+        import,
+        target,
+        tree_ref,
+        feature,
+        ..
+    } = *inlining;
+    let source = &file.source;
+    let ref_blk = symbol_blk(file, tree_ref)?;
+    let indent = line_indent(source, ref_blk.start_position().row);
+    let header = ref_blk.child_by_field_name("header")?;
+    let replaced = header.start_byte()..trimmed_end(source, ref_blk);
+    let mut edits = strip_import_references(root, file, file.id, import, &replaced);
+    edits.push(TextEdit {
+        range: lsp_range(replaced, source)?,
+        new_text: reindent(&target.source, symbol_blk(target, feature)?, &indent),
+    });
+    if let Some(constraints) = top_level_section(&target.tree, "constraints") {
+        let (position, new_text) =
+            if let Some(section) = top_level_section(&file.tree, "constraints") {
+                let child_indent = section
+                    .named_child(1)
+                    .filter(|n| n.kind() == "blk")
+                    .map(|n| line_indent(source, n.start_position().row))
+                    .unwrap_or_else(|| "\t".into());
+                let mut text = String::new();
+                let mut cursor = constraints.walk();
+                for child in constraints.named_children(&mut cursor).skip(1) {
+                    text.push_str(&format!(
+                        "\n{}{}",
+                        child_indent,
+                        reindent(&target.source, child, &child_indent)
+                    ));
+                }
+                (lsp_position(trimmed_end(source, section), source)?, text)
+            } else {
+                (
+                    lsp_position(source.len_bytes(), source)?,
+                    format!("\n{}\n", reindent(&target.source, constraints, "")),
+                )
+            };
+        edits.push(TextEdit {
+            range: Range {
+                start: position,
+                end: position,
+            },
+            new_text,
+        });
+    }
+    //The language levels of the submodel are added to the include section, without one
+    //everything is included already
+    if let Some(include) = top_level_section(&file.tree, "include") {
+        let needed: Vec<LanguageLevel> = target
+            .lang_lvl_uses()
+            .iter()
+            .map(|u| u.lang_lvl.clone())
+            .collect();
+        let mut levels: Vec<LanguageLevel> = Vec::new();
+        for lvl in needed {
+            if !file.includes_lang_lvl(&lvl) && !levels.iter().any(|l| l.covers(&lvl)) {
+                levels.retain(|l| !lvl.covers(l));
+                levels.push(lvl);
+            }
+        }
+        if !levels.is_empty() {
+            let indent = include
+                .named_child(1)
+                .filter(|n| n.kind() == "blk")
+                .map(|n| line_indent(source, n.start_position().row))
+                .unwrap_or_else(|| "\t".into());
+            let (position, new_text) = insert_below(
+                source,
+                include.start_byte(),
+                levels
+                    .iter()
+                    .flat_map(|l| l.entries())
+                    .map(|e| format!("{}{}\n", indent, e))
+                    .collect(),
+            )?;
+            edits.push(TextEdit {
+                range: Range {
+                    start: position,
+                    end: position,
+                },
+                new_text,
+            });
+        }
+    }
+    //Drop the import entry or the whole section when it was the only one
+    let entry = symbol_blk(file, import)?;
+    let section = top_level_section(&file.tree, "imports")?;
+    let entries = {
+        let mut cursor = section.walk();
+        let count = section
+            .named_children(&mut cursor)
+            .filter(|n| n.kind() == "blk")
+            .count();
+        count
+    };
+    edits.push(TextEdit {
+        range: lsp_range(
+            whole_lines(source, if entries > 1 { entry } else { section }),
+            source,
+        )?,
+        new_text: "".into(),
+    });
+    let mut changes = HashMap::from([(file.uri.clone(), edits)]);
+    let mut visible = root.fs().recursive_imported(file.id);
+    for id in visible.iter() {
+        if *id == file.id {
+            continue;
+        }
+        let importer = root.file(*id);
+        let edits = strip_import_references(root, importer, file.id, import, &(0..0));
+        if !edits.is_empty() {
+            changes.insert(importer.uri.clone(), edits);
+        }
+    }
+    visible.push(file.id);
+    for config in root.configs.values() {
+        if !config
+            .config
+            .as_ref()
+            .map(|c| visible.contains(&c.file))
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        let edits = strip_config_keys(root, config, file.id, import);
+        if !edits.is_empty() {
+            changes.insert(config.uri.clone(), edits);
+        }
+    }
+    Some(WorkspaceEdit {
+        changes: Some(changes),
+        document_changes: None,
+        change_annotations: None,
+    })
+}
+//Offered without an edit, it is computed in resolve_refactoring once the action is chosen
+fn inline_submodel(
+    params: &CodeActionParams,
+    _: &Draft,
+    root: &RootGraph,
+) -> Vec<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    let action = match inlining(uri, &params.range.start, root) {
+        Ok(inlining) => CodeAction {
+            title: format!("Inline {}", inlining.prefix),
+            kind: Some(CodeActionKind::REFACTOR_INLINE),
+            data: serde_json::to_value(RefactoringData {
+                uri: uri.clone(),
+                position: params.range.start,
+            })
+            .ok(),
+            ..Default::default()
+        },
+        Err(Some((prefix, reason))) => CodeAction {
+            title: format!("Inline {}", prefix),
+            kind: Some(CodeActionKind::REFACTOR_INLINE),
+            disabled: Some(CodeActionDisabled { reason }),
+            ..Default::default()
+        },
+        Err(None) => return Vec::new(),
+    };
+    vec![CodeActionOrCommand::CodeAction(action)]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::time::Instant;
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///ws/{}", name)).unwrap()
+    }
+    fn graph(files: &[(&str, &str)]) -> RootGraph {
+        let mut ast = hashbrown::HashMap::new();
+        let mut configs = hashbrown::HashMap::new();
+        for (name, text) in files {
+            let source = Rope::from_str(text);
+            if name.ends_with(".json") {
+                let tree = parse::parse_json(&source, None);
+                let doc = config::parse_json(tree, source, uri(name), Instant::now());
+                configs.insert(doc.id, Arc::new(doc));
+            } else {
+                let tree = parse::parse(&source, None);
+                let doc = AstDocument::new(source, tree, uri(name), Instant::now());
+                ast.insert(doc.id, Arc::new(doc));
+            }
+        }
+        let mut err = ErrorsAcc {
+            errors: hashbrown::HashMap::new(),
+            files: &ast,
+            configs: &configs,
+        };
+        RootGraph::new(
+            &ast,
+            &configs,
+            &[],
+            0,
+            &Cache::default(),
+            &mut err,
+            &mut hashbrown::HashMap::new(),
+        )
+    }
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut source = Rope::from_str(text);
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|e| (e.range.start.line, e.range.start.character));
+        for edit in edits.iter().rev() {
+            let start = source.byte_to_char(byte_offset(&edit.range.start, &source));
+            let end = source.byte_to_char(byte_offset(&edit.range.end, &source));
+            source.remove(start..end);
+            source.insert(start, &edit.new_text);
+        }
+        source.to_string()
+    }
+    //Files after applying edit, by name
+    fn applied(files: &[(&str, &str)], edit: &WorkspaceEdit) -> HashMap<String, String> {
+        let mut out: HashMap<String, String> = files
+            .iter()
+            .map(|(name, text)| (name.to_string(), text.to_string()))
+            .collect();
+        let name = |uri: &Url| uri.as_str().trim_start_matches("file:///ws/").to_string();
+        for (uri, edits) in edit.changes.iter().flatten() {
+            let text = apply(&out[&name(uri)], edits);
+            out.insert(name(uri), text);
+        }
+        if let Some(DocumentChanges::Operations(operations)) = edit.document_changes.as_ref() {
+            for op in operations {
+                match op {
+                    DocumentChangeOperation::Op(ResourceOp::Create(create)) => {
+                        assert!(out.insert(name(&create.uri), String::new()).is_none());
+                    }
+                    DocumentChangeOperation::Edit(edit) => {
+                        let edits: Vec<TextEdit> = edit
+                            .edits
+                            .iter()
+                            .map(|e| match e {
+                                OneOf::Left(e) => e.clone(),
+                                OneOf::Right(e) => e.text_edit.clone(),
+                            })
+                            .collect();
+                        let uri = name(&edit.text_document.uri);
+                        let text = apply(&out[&uri], &edits);
+                        out.insert(uri, text);
+                    }
+                    _ => panic!("unexpected operation"),
+                }
+            }
+        }
+        out
+    }
+    fn params(name: &str, line: u32, character: u32) -> CodeActionParams {
+        let position = Position { line, character };
+        CodeActionParams {
+            text_document: TextDocumentIdentifier { uri: uri(name) },
+            range: Range {
+                start: position,
+                end: position,
+            },
+            context: Default::default(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        }
+    }
+    fn draft() -> Draft {
+        let source = Rope::new();
+        let tree = parse::parse(&source, None);
+        Draft::UVL {
+            source,
+            tree,
+            timestamp: Instant::now(),
+        }
+    }
+    fn single(actions: Vec<CodeActionOrCommand>) -> CodeAction {
+        match actions.as_slice() {
+            [CodeActionOrCommand::CodeAction(action)] => action.clone(),
+            _ => panic!("expected a single code action"),
+        }
+    }
+    const EXTRACT_BEFORE: &[(&str, &str)] = &[
+        (
+            "model.uvl",
+            "imports\n\tother as o\nfeatures\n\tCar\n\t\toptional\n\t\t\tEngine\n\t\t\t\talternative\n\t\t\t\t\tPetrol\n\t\t\t\t\to.Battery\nconstraints\n\tPetrol => Car\n",
+        ),
+        ("other.uvl", "features\n\tBattery\n"),
+        (
+            "model.uvl.json",
+            "{\n\t\"file\": \"model.uvl\",\n\t\"config\": {\n\t\t\"Car\": true,\n\t\t\"Petrol\": true\n\t}\n}\n",
+        ),
+    ];
+    const EXTRACT_AFTER: &[(&str, &str)] = &[
+        (
+            "model.uvl",
+            "imports\n\tEngine\n\tother as o\nfeatures\n\tCar\n\t\toptional\n\t\t\tEngine.Engine\nconstraints\n\tEngine.Petrol => Car\n",
+        ),
+        (
+            "Engine.uvl",
+            "imports\n\tother as o\n\nfeatures\n\tEngine\n\t\talternative\n\t\t\tPetrol\n\t\t\to.Battery\n",
+        ),
+        ("other.uvl", "features\n\tBattery\n"),
+        (
+            "model.uvl.json",
+            "{\n\t\"file\": \"model.uvl\",\n\t\"config\": {\n\t\t\"Car\": true,\n\t\t\"Engine.Petrol\": true\n\t}\n}\n",
+        ),
+    ];
+    #[test]
+    fn extract_fixture() {
+        let root = graph(EXTRACT_BEFORE);
+        let action = single(extract_submodel(
+            &params("model.uvl", 5, 4),
+            &draft(),
+            &root,
+        ));
+        assert_eq!(action.title, "Extract Engine into Engine.uvl");
+        //the edit is only computed on resolve
+        assert!(action.edit.is_none());
+        assert_eq!(refactoring_uri(&action), Some(uri("model.uvl")));
+        let action = resolve_refactoring(action, &root);
+        let after = applied(EXTRACT_BEFORE, action.edit.as_ref().unwrap());
+        for (name, text) in EXTRACT_AFTER {
+            assert_eq!(after[*name], *text, "{}", name);
+        }
+        assert_eq!(after.len(), EXTRACT_AFTER.len());
+    }
+    const INLINE_BEFORE: &[(&str, &str)] = &[
+        (
+            "model.uvl",
+            "include\n\tBoolean\nimports\n\tsub\nfeatures\n\tCar\n\t\toptional\n\t\t\tsub.Sub\nconstraints\n\tsub.A => Car\n",
+        ),
+        (
+            "sub.uvl",
+            "include\n\tArithmetic.feature-cardinality\nfeatures\n\tSub\n\t\toptional\n\t\t\tA cardinality [1..2]\nconstraints\n\tA => Sub\n",
+        ),
+        (
+            "model.uvl.json",
+            "{\n\t\"file\": \"model.uvl\",\n\t\"config\": {\n\t\t\"Car\": true,\n\t\t\"sub\": {\n\t\t\t\"Sub\": true\n\t\t}\n\t}\n}\n",
+        ),
+        (
+            "first.uvl.json",
+            "{\n\t\"file\": \"model.uvl\",\n\t\"config\": {\n\t\t\"sub\": {\n\t\t\t\"Sub\": true,\n\t\t\t\"A\": true\n\t\t},\n\t\t\"Car\": true\n\t}\n}\n",
+        ),
+        (
+            "empty.uvl.json",
+            "{\n\t\"file\": \"model.uvl\",\n\t\"config\": {\n\t\t\"Car\": true,\n\t\t\"sub\": {}\n\t}\n}\n",
+        ),
+        (
+            "empty_first.uvl.json",
+            "{\n\t\"file\": \"model.uvl\",\n\t\"config\": {\n\t\t\"sub\": {},\n\t\t\"Car\": true\n\t}\n}\n",
+        ),
+    ];
+    const INLINE_AFTER: &[(&str, &str)] = &[
+        (
+            "model.uvl",
+            "include\n\tArithmetic.feature-cardinality\n\tBoolean\nfeatures\n\tCar\n\t\toptional\n\t\t\tSub\n\t\t\t\toptional\n\t\t\t\t\tA cardinality [1..2]\nconstraints\n\tA => Car\n\tA => Sub\n",
+        ),
+        (
+            "sub.uvl",
+            "include\n\tArithmetic.feature-cardinality\nfeatures\n\tSub\n\t\toptional\n\t\t\tA cardinality [1..2]\nconstraints\n\tA => Sub\n",
+        ),
+        (
+            "model.uvl.json",
+            "{\n\t\"file\": \"model.uvl\",\n\t\"config\": {\n\t\t\"Car\": true,\n\t\t\"Sub\": true\n\t}\n}\n",
+        ),
+        (
+            "first.uvl.json",
+            "{\n\t\"file\": \"model.uvl\",\n\t\"config\": {\n\t\t\"Sub\": true,\n\t\t\"A\": true,\n\t\t\"Car\": true\n\t}\n}\n",
+        ),
+        (
+            "empty.uvl.json",
+            "{\n\t\"file\": \"model.uvl\",\n\t\"config\": {\n\t\t\"Car\": true\n\t}\n}\n",
+        ),
+        (
+            "empty_first.uvl.json",
+            "{\n\t\"file\": \"model.uvl\",\n\t\"config\": {\n\t\t\"Car\": true\n\t}\n}\n",
+        ),
+    ];
+    #[test]
+    fn inline_fixture() {
+        let root = graph(INLINE_BEFORE);
+        let action = single(inline_submodel(&params("model.uvl", 7, 4), &draft(), &root));
+        assert_eq!(action.title, "Inline sub");
+        assert!(action.disabled.is_none());
+        assert!(action.edit.is_none());
+        assert_eq!(refactoring_uri(&action), Some(uri("model.uvl")));
+        let action = resolve_refactoring(action, &root);
+        let after = applied(INLINE_BEFORE, action.edit.as_ref().unwrap());
+        for (name, text) in INLINE_AFTER {
+            assert_eq!(after[*name], *text, "{}", name);
+        }
+    }
+    #[test]
+    fn include_one_minor_per_line() {
+        let files = &[(
+            "model.uvl",
+            "include\n\tBoolean\nfeatures\n\tA cardinality [1..3]\n\t\toptional\n\t\t\tB {price 2}\nconstraints\n\tsum(price) > 1\n",
+        )];
+        let root = graph(files);
+        let diagnostic = Diagnostic {
+            code: Some(NumberOrString::String("missing-include".into())),
+            ..Default::default()
+        };
+        let action = single(include_lang_lvls(
+            &params("model.uvl", 3, 1),
+            &diagnostic,
+            &draft(),
+            &root,
+        ));
+        let after = applied(files, action.edit.as_ref().unwrap());
+        assert!(after["model.uvl"].starts_with(
+            "include\n\tArithmetic.feature-cardinality\n\tArithmetic.aggregate-function\n\tBoolean\n"
+        ));
+        let lvl = LanguageLevel::Arithmetic(vec![
+            LanguageLevelArithmetic::FeatureCardinality,
+            LanguageLevelArithmetic::Aggregate,
+        ]);
+        assert_eq!(
+            lvl.entries(),
+            vec![
+                "Arithmetic.feature-cardinality".to_string(),
+                "Arithmetic.aggregate-function".to_string()
+            ]
+        );
     }
 }
@@ -65,6 +65,8 @@ struct Ast {
     keywords: Vec<Keyword>,
     namespace: Option<Path>,
     includes: Vec<LanguageLevelDecl>,
+    //Constructs that need a language level, checked against includes
+    lang_lvl_uses: Vec<LanguageLevelDecl>,
     import: Vec<Import>,
     features: Vec<Feature>,
     constraints: Vec<ConstraintDecl>,
@@ -326,6 +328,16 @@ impl AstDocument {
             None
         }
     }
+    pub fn lang_lvl_uses(&self) -> &[LanguageLevelDecl] {
+        &self.ast.lang_lvl_uses
+    }
+    //True if the include section allows constructs that need lang_lvl
+    pub fn includes_lang_lvl(&self, lang_lvl: &LanguageLevel) -> bool {
+        self.ast
+            .includes
+            .iter()
+            .any(|i| i.lang_lvl.covers(lang_lvl))
+    }
     pub fn group_mode(&self, sym: Symbol) -> Option<GroupMode> {
         match sym {
             Symbol::Group(id) => Some(self.ast.groups[id].mode.clone()),
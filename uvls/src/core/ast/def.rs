@@ -106,6 +106,89 @@ pub enum LanguageLevel {
     Type(Vec<LanguageLevelType>),
 }
 
+impl LanguageLevel {
+    //Position in the core hierarchy, a major level includes every lower major level
+    fn rank(&self) -> u8 {
+        match self {
+            LanguageLevel::Boolean(..) => 0,
+            LanguageLevel::Arithmetic(..) => 1,
+            LanguageLevel::Type(..) => 2,
+        }
+    }
+    //Major level without minor levels
+    pub fn is_major(&self) -> bool {
+        match self {
+            LanguageLevel::Boolean(m) => m.is_empty(),
+            LanguageLevel::Arithmetic(m) => m.is_empty(),
+            LanguageLevel::Type(m) => m.is_empty(),
+        }
+    }
+    //True if including self allows the constructs that need other. Minor levels are not
+    //inherited from higher major levels, they have to be included explicitly.
+    pub fn covers(&self, other: &LanguageLevel) -> bool {
+        match (self, other) {
+            (LanguageLevel::Boolean(inc), LanguageLevel::Boolean(req)) if !req.is_empty() => req
+                .iter()
+                .all(|r| inc.contains(&LanguageLevelBoolean::Any) || inc.contains(r)),
+            (LanguageLevel::Arithmetic(inc), LanguageLevel::Arithmetic(req)) if !req.is_empty() => {
+                req.iter()
+                    .all(|r| inc.contains(&LanguageLevelArithmetic::Any) || inc.contains(r))
+            }
+            (LanguageLevel::Type(inc), LanguageLevel::Type(req)) if !req.is_empty() => req
+                .iter()
+                .all(|r| inc.contains(&LanguageLevelType::Any) || inc.contains(r)),
+            (_, other) if other.is_major() => self.rank() >= other.rank(),
+            _ => false,
+        }
+    }
+}
+impl LanguageLevel {
+    //One include entry per minor level, an include line only takes a single minor level
+    pub fn entries(&self) -> Vec<String> {
+        let (major, minors): (&str, Vec<&str>) = match self {
+            LanguageLevel::Boolean(m) => (
+                "Boolean",
+                m.iter()
+                    .map(|m| match m {
+                        LanguageLevelBoolean::Any => "*",
+                        LanguageLevelBoolean::GroupCardinality => "group-cardinality",
+                    })
+                    .collect(),
+            ),
+            LanguageLevel::Arithmetic(m) => (
+                "Arithmetic",
+                m.iter()
+                    .map(|m| match m {
+                        LanguageLevelArithmetic::Any => "*",
+                        LanguageLevelArithmetic::FeatureCardinality => "feature-cardinality",
+                        LanguageLevelArithmetic::Aggregate => "aggregate-function",
+                    })
+                    .collect(),
+            ),
+            LanguageLevel::Type(m) => (
+                "Type",
+                m.iter()
+                    .map(|m| match m {
+                        LanguageLevelType::Any => "*",
+                        LanguageLevelType::NumericConstraints => "numeric-constraints",
+                        LanguageLevelType::StringConstraints => "string-constraints",
+                    })
+                    .collect(),
+            ),
+        };
+        if minors.is_empty() {
+            vec![major.into()]
+        } else {
+            minors.iter().map(|m| format!("{major}.{m}")).collect()
+        }
+    }
+}
+impl std::fmt::Display for LanguageLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.entries().join(", "))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct LanguageLevelDecl {
     pub lang_lvl: LanguageLevel,
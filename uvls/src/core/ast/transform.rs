@@ -91,7 +91,7 @@ impl<'a> VisitorState<'a> {
                         "duplicate import already defined in line {}",
                         self.ast.lsp_range(old, self.source).unwrap().start.line
                     ),
-                    error_type: ErrorType::Any,
+                    error_type: ErrorType::NameConflict,
                 });
             }
         }
@@ -113,14 +113,14 @@ impl<'a> VisitorState<'a> {
                                     severity: DiagnosticSeverity::ERROR,
                                     weight: 20,
                                     msg: "duplicate feature".to_string(),
-                                    error_type: ErrorType::Any,
+                                    error_type: ErrorType::DuplicateFeature,
                                 });
                                 self.errors.push(ErrorInfo {
                                     location: self.ast.lsp_range(old, self.source).unwrap(),
                                     severity: DiagnosticSeverity::ERROR,
                                     weight: 20,
                                     msg: "duplicate feature".to_string(),
-                                    error_type: ErrorType::Any,
+                                    error_type: ErrorType::DuplicateFeature,
                                 })
                             }
                         }
@@ -137,14 +137,14 @@ impl<'a> VisitorState<'a> {
                                 severity: DiagnosticSeverity::ERROR,
                                 weight: 20,
                                 msg: "duplicate attribute".to_string(),
-                                error_type: ErrorType::Any,
+                                error_type: ErrorType::DuplicateAttribute,
                             });
                             self.errors.push(ErrorInfo {
                                 location: self.ast.lsp_range(old, self.source).unwrap(),
                                 severity: DiagnosticSeverity::ERROR,
                                 weight: 20,
                                 msg: "duplicate attribute".to_string(),
-                                error_type: ErrorType::Any,
+                                error_type: ErrorType::DuplicateAttribute,
                             });
                         }
                         self.ast.attributes[i].depth = depth + 1;
@@ -178,7 +178,7 @@ impl<'a> VisitorState<'a> {
                         severity: DiagnosticSeverity::ERROR,
                         weight: 20,
                         msg: "name already defined as import directory".to_string(),
-                        error_type: ErrorType::Any,
+                        error_type: ErrorType::NameConflict,
                     });
                 }
                 if self
@@ -192,7 +192,7 @@ impl<'a> VisitorState<'a> {
                         severity: DiagnosticSeverity::ERROR,
                         weight: 20,
                         msg: "name already defined as import".to_string(),
-                        error_type: ErrorType::Any,
+                        error_type: ErrorType::NameConflict,
                     });
                 }
             }
@@ -207,13 +207,13 @@ impl<'a> VisitorState<'a> {
         self.node().child_by_field_name("header")
     }
     //Push an error with location of the current block header
-    fn push_error_blk<T: Into<String>>(&mut self, w: u32, error: T) {
+    fn push_error_blk<T: Into<String>>(&mut self, w: u32, error_type: ErrorType, error: T) {
         self.errors.push(ErrorInfo {
             location: node_range(self.header().unwrap(), self.source),
             severity: DiagnosticSeverity::ERROR,
             weight: w,
             msg: error.into(),
-            error_type: ErrorType::Any,
+            error_type,
         });
     }
 }
@@ -247,7 +247,11 @@ fn opt_path(state: &mut VisitorState) -> Option<Path> {
         })
     } else if state.kind() == "path" {
         if state.child_by_name("tail").is_some() {
-            state.push_error(10, "tailing dot not supported");
+            state.push_error(
+                10,
+                ErrorType::TrailingSeparator,
+                "tailing dot not supported",
+            );
         }
         visit_children(state, |state| {
             let mut p = Path::default();
@@ -269,9 +273,21 @@ fn opt_path(state: &mut VisitorState) -> Option<Path> {
 //Report error if a node has children,cardinality or attributes
 fn check_simple_blk(state: &mut VisitorState, kind: &str) {
     match state.cursor.field_name() {
-        Some("cardinality") => state.push_error(30, format!("{} may not have a cardinality", kind)),
-        Some("attribs") => state.push_error(30, format!("{} may not have a any attributes", kind)),
-        Some("child") => state.push_error(30, format!("{} may not have a any children", kind)),
+        Some("cardinality") => state.push_error(
+            30,
+            ErrorType::InvalidStructure,
+            format!("{} may not have a cardinality", kind),
+        ),
+        Some("attribs") => state.push_error(
+            30,
+            ErrorType::InvalidStructure,
+            format!("{} may not have a any attributes", kind),
+        ),
+        Some("child") => state.push_error(
+            30,
+            ErrorType::InvalidStructure,
+            format!("{} may not have a any children", kind),
+        ),
         _ => {}
     }
 }
@@ -279,8 +295,16 @@ fn check_simple_blk(state: &mut VisitorState, kind: &str) {
 //report error if a node has cardinality or attributes
 fn check_no_extra_blk(state: &mut VisitorState, kind: &str) {
     match state.cursor.field_name() {
-        Some("cardinality") => state.push_error(30, format!("{} may not have a cardinality", kind)),
-        Some("attribs") => state.push_error(30, format!("{} may not have a any attributes", kind)),
+        Some("cardinality") => state.push_error(
+            30,
+            ErrorType::InvalidStructure,
+            format!("{} may not have a cardinality", kind),
+        ),
+        Some("attribs") => state.push_error(
+            30,
+            ErrorType::InvalidStructure,
+            format!("{} may not have a any attributes", kind),
+        ),
         _ => {}
     }
 }
@@ -308,11 +332,11 @@ fn opt_arithmetic_minor(state: &mut VisitorState) -> Option<LanguageLevelArithme
         "feature-cardinality" => Some(LanguageLevelArithmetic::FeatureCardinality),
         "aggregate-function" => Some(LanguageLevelArithmetic::Aggregate),
         "group-cardinality" | "string-constraints" | "numeric-constraints" => {
-            state.push_error(30, "not allowed under Arithmetic");
+            state.push_error(30, ErrorType::LanguageLevel, "not allowed under Arithmetic");
             None
         }
         _ => {
-            state.push_error(30, "unknown Arithmetic level");
+            state.push_error(30, ErrorType::LanguageLevel, "unknown Arithmetic level");
             None
         }
     }
@@ -325,11 +349,11 @@ fn opt_boolean_minor(state: &mut VisitorState) -> Option<LanguageLevelBoolean> {
         | "aggregate-function"
         | "string-constraints"
         | "numeric-constraints" => {
-            state.push_error(30, "not allowed under Boolean");
+            state.push_error(30, ErrorType::LanguageLevel, "not allowed under Boolean");
             None
         }
         _ => {
-            state.push_error(30, "unknown Boolean level");
+            state.push_error(30, ErrorType::LanguageLevel, "unknown Boolean level");
             None
         }
     }
@@ -340,11 +364,11 @@ fn opt_type_minor(state: &mut VisitorState) -> Option<LanguageLevelType> {
         "numeric-constraints" => Some(LanguageLevelType::NumericConstraints),
         "string-constraints" => Some(LanguageLevelType::StringConstraints),
         "feature-cardinality" | "aggregate-function" | "group-cardinality" => {
-            state.push_error(30, "not allowed under Type");
+            state.push_error(30, ErrorType::LanguageLevel, "not allowed under Type");
             None
         }
         _ => {
-            state.push_error(30, "unknown Type level");
+            state.push_error(30, ErrorType::LanguageLevel, "unknown Type level");
             None
         }
     }
@@ -355,7 +379,7 @@ fn opt_major_lang_lvl(state: &mut VisitorState) -> Option<LanguageLevel> {
         "Arithmetic" => Some(LanguageLevel::Arithmetic(vec![])),
         "Type" => Some(LanguageLevel::Type(vec![])),
         _ => {
-            state.push_error(30, "unknown major language level");
+            state.push_error(30, ErrorType::LanguageLevel, "unknown major language level");
             None
         }
     }
@@ -365,7 +389,11 @@ fn opt_lang_lvl(state: &mut VisitorState) -> Option<LanguageLevel> {
     loop {
         if state.kind() == "major_lvl" {
             if out.is_some() {
-                state.push_error(30, "duplicate major level, please pick a minor level");
+                state.push_error(
+                    30,
+                    ErrorType::LanguageLevel,
+                    "duplicate major level, please pick a minor level",
+                );
                 return None;
             } else {
                 out = Some(visit_children(state, opt_major_lang_lvl)?);
@@ -399,13 +427,14 @@ fn opt_lang_lvl(state: &mut VisitorState) -> Option<LanguageLevel> {
             } else {
                 state.push_error(
                     30,
+                    ErrorType::LanguageLevel,
                     "missing major level, please specify Arithmetic, Boolean or Type level",
                 );
                 return None;
             }
         }
         if state.kind() == "name" {
-            state.push_error(30, "unknown language level");
+            state.push_error(30, ErrorType::LanguageLevel, "unknown language level");
             return None;
         }
         if !state.goto_next_sibling() {
@@ -438,10 +467,11 @@ fn visit_include(state: &mut VisitorState) {
                 "lang_lvl" => visit_children(state, visit_lang_lvl),
                 "ref" => state.push_error_blk(
                     30,
+                    ErrorType::LanguageLevel,
                     "unknown language level start with Arithmetic, Boolean or Type",
                 ),
                 _ => {
-                    state.push_error_blk(40, "expected a language level");
+                    state.push_error_blk(40, ErrorType::LanguageLevel, "expected a language level");
                 }
             }
         }
@@ -484,10 +514,18 @@ fn visit_import(state: &mut VisitorState) {
             match state.header().unwrap().kind() {
                 "name" | "ref" => visit_children(state, visit_import_decl),
                 "incomplete_ref" => {
-                    state.push_error_blk(40, "incomplete import, please specify an alias");
+                    state.push_error_blk(
+                        40,
+                        ErrorType::InvalidAlias,
+                        "incomplete import, please specify an alias",
+                    );
                 }
                 _ => {
-                    state.push_error_blk(40, "expected a import declaration");
+                    state.push_error_blk(
+                        40,
+                        ErrorType::InvalidStructure,
+                        "expected a import declaration",
+                    );
                 }
             }
         }
@@ -501,7 +539,7 @@ fn opt_int(node: Node, state: &mut VisitorState) -> Option<usize> {
     if let Ok(i) = state.slice(node).parse() {
         Some(i)
     } else {
-        state.push_error_node(node, 20, "cant parse integer");
+        state.push_error_node(node, 20, ErrorType::InvalidNumber, "cant parse integer");
         None
     }
 }
@@ -525,7 +563,7 @@ fn opt_number(state: &mut VisitorState) -> Option<f64> {
     if let Ok(num) = state.slice(state.node()).parse() {
         Some(num)
     } else {
-        state.push_error(40, "failed to parse number");
+        state.push_error(40, ErrorType::InvalidNumber, "failed to parse number");
         None
     }
 }
@@ -543,7 +581,7 @@ fn opt_aggreate_op(state: &mut VisitorState) -> Option<AggregateOP> {
         "sum" => Some(AggregateOP::Sum),
         "avg" => Some(AggregateOP::Avg),
         _ => {
-            state.push_error(30, "unknown aggregate function");
+            state.push_error(30, ErrorType::UnknownFunction, "unknown aggregate function");
             None
         }
     }
@@ -553,7 +591,7 @@ fn opt_integer_op(state: &mut VisitorState) -> Option<IntegerOP> {
         "floor" => Some(IntegerOP::Floor),
         "ceil" => Some(IntegerOP::Ceil),
         _ => {
-            state.push_error(30, "unknown integer function");
+            state.push_error(30, ErrorType::UnknownFunction, "unknown integer function");
             None
         }
     }
@@ -567,7 +605,7 @@ fn opt_function_args(state: &mut VisitorState) -> Option<Vec<Path>> {
                 "path" => args.push(opt_path(state).unwrap()),
                 _ => {
                     if state.node().is_named() {
-                        state.push_error(30, "expected a reference");
+                        state.push_error(30, ErrorType::InvalidExpression, "expected a reference");
                         return None;
                     }
                 }
@@ -580,113 +618,24 @@ fn opt_function_args(state: &mut VisitorState) -> Option<Vec<Path>> {
     })
 }
 
-fn check_langlvls(state: &mut VisitorState, searched_lang_lvl: LanguageLevel) {
-    if state.ast.includes.is_empty() {
-        // no includes means, that implicitly everything is included
-        return ();
-    }
-
-    let mut includes: Vec<LanguageLevel> = state
+//Record that the current node needs a language level, the include section is checked in
+//check_includes once the whole file is known
+fn require_lang_lvl(state: &mut VisitorState, lang_lvl: LanguageLevel) {
+    let span = state.node().byte_range();
+    state
         .ast
-        .includes
-        .clone()
-        .into_iter()
-        .map(|x| x.lang_lvl)
-        .collect();
-
-    // Add lower Core Language Levels
-    if includes
-        .iter()
-        .any(|x: &LanguageLevel| matches!(x, LanguageLevel::Arithmetic(_)))
-    {
-        includes.push(LanguageLevel::Boolean(vec![]))
-    }
-    if includes
-        .iter()
-        .any(|x: &LanguageLevel| matches!(x, LanguageLevel::Type(_)))
-    {
-        includes.push(LanguageLevel::Boolean(vec![]));
-        includes.push(LanguageLevel::Arithmetic(vec![]));
-    }
-
-    fn check_sub_lang_lvls<'a, F, G, L: PartialEq>(
-        mut m: F,
-        mut get: G,
-        sub_lang_lvls: Vec<L>,
-        includes: Vec<LanguageLevel>,
-        any: L,
-    ) -> bool
-    where
-        F: FnMut(&LanguageLevel) -> bool,
-        G: FnMut(&LanguageLevel) -> Option<Vec<L>>,
-    {
-        sub_lang_lvls.is_empty() && includes.iter().any(|x| m(x))
-            || sub_lang_lvls
-                .iter()
-                .fold(false, |mut res, val: &L| -> bool {
-                    for i in includes.iter() {
-                        let x = get(i).unwrap_or(vec![]);
-                        if x.contains(&any) || x.contains(&val) {
-                            res = true;
-                            break;
-                        }
-                    }
-                    res
-                })
-    }
-
-    if !match searched_lang_lvl.borrow() {
-        LanguageLevel::Arithmetic(s) => check_sub_lang_lvls(
-            |x| matches!(x, LanguageLevel::Arithmetic(_)),
-            |x| -> Option<Vec<LanguageLevelArithmetic>> {
-                match x {
-                    LanguageLevel::Arithmetic(l) => Some(l.to_vec()),
-                    _ => None,
-                }
-            },
-            s.clone(),
-            includes,
-            LanguageLevelArithmetic::Any,
-        ),
-        LanguageLevel::Boolean(s) => check_sub_lang_lvls(
-            |x| matches!(x, LanguageLevel::Boolean(_)),
-            |x| -> Option<Vec<LanguageLevelBoolean>> {
-                match x {
-                    LanguageLevel::Boolean(l) => Some(l.to_vec()),
-                    _ => None,
-                }
-            },
-            s.clone(),
-            includes,
-            LanguageLevelBoolean::Any,
-        ),
-        LanguageLevel::Type(s) => check_sub_lang_lvls(
-            |x| matches!(x, LanguageLevel::Type(_)),
-            |x| -> Option<Vec<LanguageLevelType>> {
-                match x {
-                    LanguageLevel::Type(l) => Some(l.to_vec()),
-                    _ => None,
-                }
-            },
-            s.clone(),
-            includes,
-            LanguageLevelType::Any,
-        ),
-    } {
-        state.push_error(
-            10,
-            format!(
-                "Operation does not correspond includes. Please include {:?}",
-                searched_lang_lvl
-            ),
-        )
-    }
+        .lang_lvl_uses
+        .push(LanguageLevelDecl { lang_lvl, span });
 }
 
 fn opt_aggregate(state: &mut VisitorState) -> Option<Expr> {
     let op = opt_aggreate_op(state)?;
     if state.child_by_name("tail").is_some() {
-        state.push_error(10, "tailing comma not allowed");
+        state.push_error(
+            10,
+            ErrorType::TrailingSeparator,
+            "tailing comma not allowed",
+        );
     }
     let args = opt_function_args(state)?;
 
@@ -710,12 +659,16 @@ fn opt_aggregate(state: &mut VisitorState) -> Option<Expr> {
             .is_some()
     };
     if !check_arg_type_number() {
-        state.push_error(30, "invalid argument. number attribute expected");
+        state.push_error(
+            30,
+            ErrorType::TypeMismatch,
+            "invalid argument. number attribute expected",
+        );
     }
 
     match args.len() {
         0 => {
-            state.push_error(30, "missing arguments");
+            state.push_error(30, ErrorType::WrongArity, "missing arguments");
             None
         }
         1 => Some(Expr::Aggregate {
@@ -729,14 +682,18 @@ fn opt_aggregate(state: &mut VisitorState) -> Option<Expr> {
             context: Some(state.add_ref_direct(args[0].clone())),
         }),
         _ => {
-            state.push_error(30, "too many arguments");
+            state.push_error(30, ErrorType::WrongArity, "too many arguments");
             None
         }
     }
 }
 fn opt_integer(state: &mut VisitorState) -> Option<Expr> {
     if state.child_by_name("tail").is_some() {
-        state.push_error(10, "tailing comma not allowed");
+        state.push_error(
+            10,
+            ErrorType::TrailingSeparator,
+            "tailing comma not allowed",
+        );
     }
     let op = opt_integer_op(state)?;
     visit_children(state, |state| {
@@ -744,11 +701,11 @@ fn opt_integer(state: &mut VisitorState) -> Option<Expr> {
             let n: Box<ExprDecl> = opt_numeric(state)?.into();
             let out = Some(Expr::Integer { op: op.clone(), n });
             if state.goto_next_sibling() && state.goto_field("arg") {
-                state.push_error(30, "expected exactly one argument");
+                state.push_error(30, ErrorType::WrongArity, "expected exactly one argument");
             }
             out
         } else {
-            state.push_error(30, "missing argument");
+            state.push_error(30, ErrorType::WrongArity, "missing argument");
             None
         }
     })
@@ -771,7 +728,7 @@ fn opt_numeric(state: &mut VisitorState) -> Option<ExprDecl> {
                     state.goto_field("lhs");
                     let lhs = opt_numeric(state)?;
                     state.goto_field("op");
-                    check_langlvls(state, LanguageLevel::Arithmetic(vec![]));
+                    require_lang_lvl(state, LanguageLevel::Arithmetic(vec![]));
                     state.goto_field("rhs");
                     let rhs = opt_numeric(state)?;
                     Some(Expr::Binary {
@@ -783,6 +740,7 @@ fn opt_numeric(state: &mut VisitorState) -> Option<ExprDecl> {
                     state.push_error_node(
                         state.node().parent().unwrap(),
                         40,
+                        ErrorType::InvalidExpression,
                         "found a constraint, expected an expression",
                     );
                     None
@@ -792,49 +750,61 @@ fn opt_numeric(state: &mut VisitorState) -> Option<ExprDecl> {
         "nested_expr" => visit_children(state, opt_numeric).map(|c| c.content),
         "function" => match state.slice(state.child_by_name("op")?).borrow() {
             "sum" | "avg" => {
-                check_langlvls(
+                require_lang_lvl(
                     state,
                     LanguageLevel::Arithmetic(vec![LanguageLevelArithmetic::Aggregate]),
                 );
                 opt_aggregate(state)
             }
             "len" => {
-                check_langlvls(
+                require_lang_lvl(
                     state,
                     LanguageLevel::Type(vec![LanguageLevelType::StringConstraints]),
                 );
                 if state.child_by_name("tail").is_some() {
-                    state.push_error(10, "tailing comma not allowed");
+                    state.push_error(
+                        10,
+                        ErrorType::TrailingSeparator,
+                        "tailing comma not allowed",
+                    );
                 }
                 visit_children(state, |state| {
                     if state.goto_field("arg") {
                         info!("{:?}", state.node());
                         let out = Some(Expr::Len(opt_numeric(state)?.into()));
                         if state.goto_next_sibling() && state.goto_field("arg") {
-                            state.push_error(30, "expected exactly one argument");
+                            state.push_error(
+                                30,
+                                ErrorType::WrongArity,
+                                "expected exactly one argument",
+                            );
                         }
 
                         out
                     } else {
-                        state.push_error(30, "missing argument");
+                        state.push_error(30, ErrorType::WrongArity, "missing argument");
                         None
                     }
                 })
             }
             "floor" | "ceil" => {
-                check_langlvls(
+                require_lang_lvl(
                     state,
                     LanguageLevel::Type(vec![LanguageLevelType::NumericConstraints]),
                 );
                 opt_integer(state)
             }
             _ => {
-                state.push_error(30, "unknown function");
+                state.push_error(30, ErrorType::UnknownFunction, "unknown function");
                 None
             }
         },
         _ => {
-            state.push_error(40, "found a constraint, expected a expression");
+            state.push_error(
+                40,
+                ErrorType::InvalidExpression,
+                "found a constraint, expected a expression",
+            );
             None
         }
     }
@@ -886,7 +856,6 @@ fn opt_constraint(state: &mut VisitorState) -> Option<ConstraintDecl> {
                     state.goto_field("lhs");
                     let lhs = opt_constraint(state)?;
                     state.goto_field("op");
-                    check_langlvls(state, LanguageLevel::Boolean(vec![]));
                     state.goto_field("rhs");
                     let rhs = opt_constraint(state)?;
                     Some(Constraint::Logic {
@@ -898,7 +867,7 @@ fn opt_constraint(state: &mut VisitorState) -> Option<ConstraintDecl> {
                     state.goto_field("lhs");
                     let lhs = opt_numeric(state)?;
                     state.goto_field("op");
-                    check_langlvls(state, LanguageLevel::Arithmetic(vec![]));
+                    require_lang_lvl(state, LanguageLevel::Arithmetic(vec![]));
                     state.goto_field("rhs");
                     let rhs = opt_numeric(state)?;
                     Some(Constraint::Equation {
@@ -910,6 +879,7 @@ fn opt_constraint(state: &mut VisitorState) -> Option<ConstraintDecl> {
                     state.push_error_node(
                         state.node().parent().unwrap(),
                         40,
+                        ErrorType::InvalidExpression,
                         "expected a constraint found a expression",
                     );
                     None
@@ -917,7 +887,11 @@ fn opt_constraint(state: &mut VisitorState) -> Option<ConstraintDecl> {
             })
         }
         _ => {
-            state.push_error(40, "expected a constraint found a expression");
+            state.push_error(
+                40,
+                ErrorType::InvalidExpression,
+                "expected a constraint found a expression",
+            );
             None
         }
     }
@@ -957,11 +931,19 @@ fn opt_attrib_expr(state: &mut VisitorState) -> Option<Value> {
         "bool" => Some(Value::Bool(visit_children(state, opt_bool))),
         "string" => Some(Value::String(opt_string(state)?)),
         "path" => {
-            state.push_error(30, "attribute references are not supported");
+            state.push_error(
+                30,
+                ErrorType::InvalidExpression,
+                "attribute references are not supported",
+            );
             None
         }
         "binary_expr" | "nested_expr" | "aggregate" | "unary_expr" => {
-            state.push_error(30, "composit atttribute values are not supported");
+            state.push_error(
+                30,
+                ErrorType::InvalidExpression,
+                "composit atttribute values are not supported",
+            );
             None
         }
         _ => None,
@@ -1012,7 +994,11 @@ fn visit_attributes(state: &mut VisitorState, parent: Symbol, duplicate: &bool)
         match state.kind() {
             "attribute_constraints" => {
                 if state.child_by_name("tail").is_some() {
-                    state.push_error(10, "tailing comma unsupported");
+                    state.push_error(
+                        10,
+                        ErrorType::TrailingSeparator,
+                        "tailing comma unsupported",
+                    );
                 }
                 visit_children_arg(state, parent, duplicate, visit_constraint_list);
             }
@@ -1042,7 +1028,11 @@ fn visit_feature(
 ) {
     match parent {
         Symbol::Feature(..) => {
-            state.push_error(40, "features have to be separated by groups");
+            state.push_error(
+                40,
+                ErrorType::InvalidStructure,
+                "features have to be separated by groups",
+            );
         }
         _ => {}
     }
@@ -1055,7 +1045,7 @@ fn visit_feature(
             .unwrap()
             .child_by_field_name("cardinality")
             .and_then(|n| {
-                check_langlvls(
+                require_lang_lvl(
                     state,
                     LanguageLevel::Arithmetic(vec![LanguageLevelArithmetic::FeatureCardinality]),
                 );
@@ -1123,7 +1113,11 @@ fn visit_feature(
 fn visit_ref(state: &mut VisitorState, parent: Symbol, path: Path) {
     match parent {
         Symbol::Feature(..) => {
-            state.push_error(40, "features have to be separated by groups");
+            state.push_error(
+                40,
+                ErrorType::InvalidStructure,
+                "features have to be separated by groups",
+            );
         }
         _ => {}
     }
@@ -1138,10 +1132,18 @@ fn visit_ref(state: &mut VisitorState, parent: Symbol, path: Path) {
 fn visit_group(state: &mut VisitorState, parent: Symbol, mode: GroupMode, duplicate: &bool) {
     match parent {
         Symbol::Group(..) => {
-            state.push_error(40, "groups have to be separated by features");
+            state.push_error(
+                40,
+                ErrorType::InvalidStructure,
+                "groups have to be separated by features",
+            );
         }
         Symbol::Root => {
-            state.push_error(40, "groups have to be contained by features");
+            state.push_error(
+                40,
+                ErrorType::InvalidStructure,
+                "groups have to be contained by features",
+            );
         }
         _ => {}
     }
@@ -1169,7 +1171,7 @@ fn visit_blk_decl(state: &mut VisitorState, parent: Symbol, duplicate: &bool) {
             visit_feature(state, parent, name, Type::Bool, *duplicate);
         }
         "typed_feature" => {
-            check_langlvls(state, LanguageLevel::Type(vec![]));
+            require_lang_lvl(state, LanguageLevel::Type(vec![]));
             let (name, ty) = visit_children(state, |state| {
                 state.goto_field("type");
                 let ty = match &*state.slice_raw(state.node().byte_range()) {
@@ -1177,7 +1179,11 @@ fn visit_blk_decl(state: &mut VisitorState, parent: Symbol, duplicate: &bool) {
                     "String" => Type::String,
                     "Boolean" => Type::Bool,
                     _ => {
-                        state.push_error(30, "unknown type, interpreting as boolean");
+                        state.push_error(
+                            30,
+                            ErrorType::TypeMismatch,
+                            "unknown type, interpreting as boolean",
+                        );
                         Type::Bool
                     }
                 };
@@ -1192,7 +1198,11 @@ fn visit_blk_decl(state: &mut VisitorState, parent: Symbol, duplicate: &bool) {
                 state.goto_field("path");
                 let path = opt_path(state);
                 if state.goto_field("alias") {
-                    state.push_error(30, "imported features may not have an alias");
+                    state.push_error(
+                        30,
+                        ErrorType::InvalidAlias,
+                        "imported features may not have an alias",
+                    );
                 }
                 path
             })
@@ -1210,7 +1220,7 @@ fn visit_blk_decl(state: &mut VisitorState, parent: Symbol, duplicate: &bool) {
             visit_group(state, parent, mode, duplicate);
         }
         "cardinality" => {
-            check_langlvls(
+            require_lang_lvl(
                 state,
                 LanguageLevel::Boolean(vec![LanguageLevelBoolean::GroupCardinality]),
             );
@@ -1220,13 +1230,17 @@ fn visit_blk_decl(state: &mut VisitorState, parent: Symbol, duplicate: &bool) {
         _ => {
             if state.kind() == "constraint" && state.name(state.cursor().node()).contains("-") {
                 // todo check if also for groups
-                state.push_error_with_type(
+                state.push_error(
                     40,
-                    "name contains a dash (-)",
                     ErrorType::FeatureNameContainsDashes,
+                    "name contains a dash (-)",
                 )
             } else {
-                state.push_error(40, "expected a feature or group declaration");
+                state.push_error(
+                    40,
+                    ErrorType::InvalidStructure,
+                    "expected a feature or group declaration",
+                );
             }
         }
     }
@@ -1259,7 +1273,7 @@ fn visit_constraint_decl(state: &mut VisitorState) {
         }
         if state.kind() == "ref" {
             if let Some(alias) = state.child_by_name("alias") {
-                state.push_error_node(alias, 30, "alias not allowed here");
+                state.push_error_node(alias, 30, ErrorType::InvalidAlias, "alias not allowed here");
             }
         }
         if !state.goto_next_sibling() {
@@ -1277,7 +1291,7 @@ fn visit_constraints(state: &mut VisitorState) {
                     visit_children(state, visit_constraint_decl);
                 }
                 _ => {
-                    state.push_error(40, "expected a constraint");
+                    state.push_error(40, ErrorType::InvalidExpression, "expected a constraint");
                 }
             }
         }
@@ -1299,11 +1313,11 @@ fn visit_top_lvl(state: &mut VisitorState) {
                 "features" => visit_children(state, visit_features),
                 "constraints" => visit_children(state, visit_constraints),
                 "incomplete_namespace" => {
-                    state.push_error_blk(60, "incomplete namespace");
+                    state.push_error_blk(60, ErrorType::SyntaxError, "incomplete namespace");
                     top_level_order.pop();
                 }
                 _ => {
-                    state.push_error_blk(60,"only namspaces, imports, includes, features and constraints are allowed here");
+                    state.push_error_blk(60, ErrorType::InvalidStructure,"only namspaces, imports, includes, features and constraints are allowed here");
                     visit_children(state, visit_features);
                     top_level_order.pop();
                 }
@@ -1331,6 +1345,7 @@ fn visit_top_lvl(state: &mut VisitorState) {
             state.push_error_node(
                 top_level_order[i],
                 50,
+                ErrorType::SectionOrder,
                 format!("duplicate {} section", top_level_order[i].kind()),
             );
         }
@@ -1338,6 +1353,7 @@ fn visit_top_lvl(state: &mut VisitorState) {
             state.push_error_node(
                 top_level_order[i],
                 50,
+                ErrorType::SectionOrder,
                 format!(
                     "{} section comes before the {} section",
                     top_level_order[i - 1].kind(),
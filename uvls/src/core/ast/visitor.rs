@@ -98,16 +98,7 @@ pub trait Visitor<'a> {
             }
         }
     }
-    fn push_error<T: Into<String>>(&mut self, w: u32, error: T) {
-        self.push_err_raw(ErrorInfo {
-            location: node_range(self.node(), self.source()),
-            severity: DiagnosticSeverity::ERROR,
-            weight: w,
-            msg: error.into(),
-            error_type: ErrorType::Any,
-        });
-    }
-    fn push_error_with_type<T: Into<String>>(&mut self, w: u32, error: T, error_type: ErrorType) {
+    fn push_error<T: Into<String>>(&mut self, w: u32, error_type: ErrorType, error: T) {
         self.push_err_raw(ErrorInfo {
             location: node_range(self.node(), self.source()),
             severity: DiagnosticSeverity::ERROR,
@@ -116,13 +107,19 @@ pub trait Visitor<'a> {
             error_type,
         });
     }
-    fn push_error_node<T: Into<String>>(&mut self, node: Node, w: u32, error: T) {
+    fn push_error_node<T: Into<String>>(
+        &mut self,
+        node: Node,
+        w: u32,
+        error_type: ErrorType,
+        error: T,
+    ) {
         self.push_err_raw(ErrorInfo {
             location: node_range(node, self.source()),
             severity: DiagnosticSeverity::ERROR,
             weight: w,
             msg: error.into(),
-            error_type: ErrorType::Any,
+            error_type,
         });
     }
 }
@@ -7,6 +7,7 @@ use module::{ConfigModule, Module};
 use petgraph::prelude::*;
 use resolve::*;
 use std::sync::Arc;
+use tower_lsp::lsp_types::Url;
 use ustr::Ustr;
 #[derive(Debug, Clone, PartialEq)]
 enum FSEdge {
@@ -78,14 +79,25 @@ impl FileSystem {
             })
         })
     }
-    fn new(files: &AstFiles, errors: &mut ErrorsAcc) -> Self {
+    fn new(files: &AstFiles, folders: &[Vec<Ustr>], errors: &mut ErrorsAcc) -> Self {
         let mut graph = DiGraph::new();
         let mut file2node = HashMap::new();
         let root = graph.add_node(FSNode::Dir);
+        //Each workspace folder gets its own root, so imports never resolve into another folder.
+        //Nested folders take precedence over their parents.
+        let mut folder_roots: Vec<_> = folders
+            .iter()
+            .map(|path| (path.as_slice(), graph.add_node(FSNode::Dir)))
+            .collect();
+        folder_roots.sort_by_key(|(path, _)| std::cmp::Reverse(path.len()));
         //create file system
         for (&n, f) in files.iter() {
-            let mut dir = root;
-            for i in f.path[0..f.path.len() - 1].iter() {
+            let (mut dir, path) = folder_roots
+                .iter()
+                .find(|(folder, _)| f.path.len() > folder.len() && f.path.starts_with(folder))
+                .map(|(folder, node)| (*node, &f.path[folder.len()..]))
+                .unwrap_or((root, &f.path[..]));
+            for i in path[0..path.len() - 1].iter() {
                 if let Some(old) = graph.edges(dir).find(|e| match e.weight() {
                     FSEdge::Path(name) => name == i && graph[e.target()].is_dir(),
                     _ => false,
@@ -98,7 +110,7 @@ impl FileSystem {
                 }
             }
             let id = graph.add_node(FSNode::File(n));
-            graph.add_edge(dir, id, FSEdge::Path(*f.path.last().unwrap()));
+            graph.add_edge(dir, id, FSEdge::Path(*path.last().unwrap()));
             file2node.insert(n, id);
         }
         //resolve imports
@@ -106,12 +118,18 @@ impl FileSystem {
             for i in f.all_imports().rev() {
                 if let Some(node) = Self::goto_file(&graph, file2node[&n], f.path(i)) {
                     if graph.contains_edge(node, file2node[&n]) {
-                        errors.sym(i, n, 50, "cyclic import not allowed");
+                        errors.sym(
+                            i,
+                            n,
+                            50,
+                            ErrorType::CyclicImport,
+                            "cyclic import not allowed",
+                        );
                     } else {
                         graph.add_edge(file2node[&n], node, FSEdge::Import(i));
                     }
                 } else {
-                    errors.sym(i, n, 50, "unresolved import");
+                    errors.sym(i, n, 50, ErrorType::UnresolvedImport, "unresolved import");
                     info!("Cant find {:?} ", f.path(i));
                 }
             }
@@ -266,6 +284,17 @@ impl FileSystem {
     }
 }
 
+//Path of a workspace folder in the same form as AstDocument::path
+pub fn folder_path(uri: &Url) -> Option<Vec<Ustr>> {
+    uri.to_file_path()
+        .ok()?
+        .components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(os) => os.to_str().map(|s| Some(s.into())),
+            _ => None,
+        })
+        .collect()
+}
 #[derive(Debug, Clone)]
 pub struct LinkedAstDocument {
     pub content: Arc<AstDocument>,
@@ -299,12 +328,13 @@ impl Cache {
         old: &Cache,
         files: &AstFiles,
         configs: &ConfigFiles,
+        folders: &[Vec<Ustr>],
         dirty: &HashSet<FileID>,
         revision: u64,
         errors: &mut ErrorsAcc,
     ) -> Cache {
         let mut trans_dirty = dirty.clone();
-        let fs = FileSystem::new(files, errors);
+        let fs = FileSystem::new(files, folders, errors);
         for i in dirty.iter() {
             if !i.is_config() {
                 for k in fs.recursive_imported(*i) {
@@ -375,9 +405,9 @@ impl Cache {
                                 }),
                             );
                         } else {
-                            let (values, source_map) = module
-                                .resolve_config(&content.config, |span, err| {
-                                    errors.span(span, *k, 20, err)
+                            let (values, source_map) =
+                                module.resolve_config(&content.config, |span, err| {
+                                    errors.span(span, *k, 20, ErrorType::InvalidConfigEntry, err)
                                 });
                             module.ok &= !errors.has_error(*k);
                             config_modules.insert(
@@ -394,7 +424,13 @@ impl Cache {
                         config_modules.insert(*k, old.config_modules[k].clone());
                     }
                 } else {
-                    errors.span(content.file_span.clone(), *k, 100, "file no found");
+                    errors.span(
+                        content.file_span.clone(),
+                        *k,
+                        100,
+                        ErrorType::InvalidConfigFile,
+                        "file no found",
+                    );
                 }
             }
         }
@@ -2,27 +2,164 @@ use crate::core::*;
 use ast::insert_multi;
 use hashbrown::HashMap;
 use log::info;
+use parking_lot::Mutex;
 use ropey::Rope;
-use tokio::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, Notify};
 use tokio::time::Instant;
 use tower_lsp::lsp_types::*;
 use tower_lsp::Client;
 use tree_sitter::{Node, QueryCursor, Tree};
 
-// This type is used to provide quickactions for a error
-#[derive(Clone, Debug, PartialEq)]
+//Stable diagnostic codes, every error carries one. The numeric value is published as
+//UVLxxxx and may never change, new codes are appended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ErrorType {
     Any = 0,
-    FeatureNameContainsDashes,
+    FeatureNameContainsDashes = 1,
+    UnresolvedReference = 2,
+    SyntaxError = 3,
+    LineBreak = 4,
+    TrailingSeparator = 5,
+    InvalidStructure = 6,
+    DuplicateFeature = 7,
+    DuplicateAttribute = 8,
+    NameConflict = 9,
+    SectionOrder = 10,
+    LanguageLevel = 11,
+    DeadFeature = 12,
+    RedundantConstraint = 13,
+    VoidModel = 14,
+    UnsatConstraint = 15,
+    InvalidConfig = 16,
+    MissingInclude = 17,
+    InvalidNumber = 18,
+    UnknownFunction = 19,
+    WrongArity = 20,
+    TypeMismatch = 21,
+    InvalidExpression = 22,
+    UnresolvedImport = 23,
+    CyclicImport = 24,
+    InvalidAlias = 25,
+    NamespaceConflict = 26,
+    InvalidConfigFile = 27,
+    InvalidConfigEntry = 28,
+    UnusedInclude = 29,
+    AnalysisInconclusive = 30,
+    MissingStringConstraints = 31,
 }
+//Documentation of each code, the anchors are the lowercase codes
+pub const DIAGNOSTICS_DOC: &str =
+    "https://github.com/Universal-Variability-Language/uvl-lsp/blob/master/docs/diagnostics.md";
 
 impl ErrorType {
+    pub const ALL: [ErrorType; 32] = [
+        ErrorType::Any,
+        ErrorType::FeatureNameContainsDashes,
+        ErrorType::UnresolvedReference,
+        ErrorType::SyntaxError,
+        ErrorType::LineBreak,
+        ErrorType::TrailingSeparator,
+        ErrorType::InvalidStructure,
+        ErrorType::DuplicateFeature,
+        ErrorType::DuplicateAttribute,
+        ErrorType::NameConflict,
+        ErrorType::SectionOrder,
+        ErrorType::LanguageLevel,
+        ErrorType::DeadFeature,
+        ErrorType::RedundantConstraint,
+        ErrorType::VoidModel,
+        ErrorType::UnsatConstraint,
+        ErrorType::InvalidConfig,
+        ErrorType::MissingInclude,
+        ErrorType::InvalidNumber,
+        ErrorType::UnknownFunction,
+        ErrorType::WrongArity,
+        ErrorType::TypeMismatch,
+        ErrorType::InvalidExpression,
+        ErrorType::UnresolvedImport,
+        ErrorType::CyclicImport,
+        ErrorType::InvalidAlias,
+        ErrorType::NamespaceConflict,
+        ErrorType::InvalidConfigFile,
+        ErrorType::InvalidConfigEntry,
+        ErrorType::UnusedInclude,
+        ErrorType::AnalysisInconclusive,
+        ErrorType::MissingStringConstraints,
+    ];
     pub fn from_u32(value: u32) -> ErrorType {
-        match value {
-            1 => ErrorType::FeatureNameContainsDashes,
+        Self::ALL
+            .get(value as usize)
+            .copied()
+            .unwrap_or(ErrorType::Any)
+    }
+    //Code as published to the client eg. UVL0012
+    pub fn code(&self) -> String {
+        format!("UVL{:04}", *self as u32)
+    }
+    pub fn from_code(code: &str) -> Option<ErrorType> {
+        code.strip_prefix("UVL")
+            .and_then(|n| n.parse().ok())
+            .map(Self::from_u32)
+    }
+    //Recover the code from a diagnostic send back by the client
+    pub fn from_diagnostic(diagnostic: &Diagnostic) -> ErrorType {
+        match (&diagnostic.code, &diagnostic.data) {
+            (Some(NumberOrString::String(code)), _) => {
+                Self::from_code(code).unwrap_or(ErrorType::Any)
+            }
+            (_, Some(serde_json::value::Value::Number(n))) => {
+                Self::from_u32(n.as_u64().unwrap_or(0) as u32)
+            }
             _ => ErrorType::Any,
         }
     }
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorType::Any => "unclassified",
+            ErrorType::FeatureNameContainsDashes => "name-contains-dash",
+            ErrorType::UnresolvedReference => "unresolved-reference",
+            ErrorType::SyntaxError => "syntax-error",
+            ErrorType::LineBreak => "invalid-line-break",
+            ErrorType::TrailingSeparator => "trailing-separator",
+            ErrorType::InvalidStructure => "invalid-structure",
+            ErrorType::DuplicateFeature => "duplicate-feature",
+            ErrorType::DuplicateAttribute => "duplicate-attribute",
+            ErrorType::NameConflict => "name-conflict",
+            ErrorType::SectionOrder => "section-order",
+            ErrorType::LanguageLevel => "invalid-language-level",
+            ErrorType::DeadFeature => "dead-feature",
+            ErrorType::RedundantConstraint => "redundant-constraint",
+            ErrorType::VoidModel => "void-model",
+            ErrorType::UnsatConstraint => "unsatisfiable-constraint",
+            ErrorType::InvalidConfig => "invalid-configuration",
+            ErrorType::MissingInclude => "missing-include",
+            ErrorType::InvalidNumber => "invalid-number",
+            ErrorType::UnknownFunction => "unknown-function",
+            ErrorType::WrongArity => "wrong-arity",
+            ErrorType::TypeMismatch => "type-mismatch",
+            ErrorType::InvalidExpression => "invalid-expression",
+            ErrorType::UnresolvedImport => "unresolved-import",
+            ErrorType::CyclicImport => "cyclic-import",
+            ErrorType::InvalidAlias => "invalid-alias",
+            ErrorType::NamespaceConflict => "namespace-conflict",
+            ErrorType::InvalidConfigFile => "invalid-config-file",
+            ErrorType::InvalidConfigEntry => "invalid-config-entry",
+            ErrorType::UnusedInclude => "unused-include",
+            ErrorType::AnalysisInconclusive => "analysis-inconclusive",
+            ErrorType::MissingStringConstraints => "missing-string-constraints",
+        }
+    }
+    pub fn code_description(&self) -> Option<CodeDescription> {
+        Url::parse(&format!(
+            "{}#{}",
+            DIAGNOSTICS_DOC,
+            self.code().to_lowercase()
+        ))
+        .ok()
+        .map(|href| CodeDescription { href })
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -46,38 +183,40 @@ impl ErrorInfo {
             range: self.location,
             severity: Some(self.severity),
             message: self.msg,
+            code: Some(NumberOrString::String(self.error_type.code())),
+            code_description: self.error_type.code_description(),
+            source: Some("uvls".into()),
             data: Some(serde_json::value::Value::Number(
-                serde_json::value::Number::from(self.error_type.clone() as i32),
+                serde_json::value::Number::from(self.error_type as i32),
             )),
             ..Default::default()
         }
     }
 }
-pub async fn publish(client: &Client, uri: &Url, err: &[ErrorInfo]) {
-    // reduces cardinality error to one error
+// reduces cardinality error to one error and only keeps the heaviest errors
+fn reduce(err: &[ErrorInfo]) -> Vec<Diagnostic> {
     let mut reduced_err = vec![];
     err.iter().for_each(|ele| {
         if !reduced_err.contains(ele) {
             reduced_err.push(ele.clone())
         }
     });
-    if let Some(max) = reduced_err.clone().into_iter().max_by_key(|e| e.weight) {
-        client
-            .publish_diagnostics(
-                uri.clone(),
-                reduced_err[..]
-                    .iter()
-                    .rev()
-                    .filter(|e| e.weight == max.weight)
-                    .map(|i| i.clone().diagnostic())
-                    .collect(),
-                None,
-            )
-            .await;
+    if let Some(max) = reduced_err.iter().map(|e| e.weight).max() {
+        reduced_err[..]
+            .iter()
+            .rev()
+            .filter(|e| e.weight == max)
+            .map(|i| i.clone().diagnostic())
+            .collect()
     } else {
-        client.publish_diagnostics(uri.clone(), vec![], None).await;
+        vec![]
     }
 }
+pub async fn publish(client: &Client, uri: &Url, err: &[ErrorInfo]) {
+    client
+        .publish_diagnostics(uri.clone(), reduce(err), None)
+        .await;
+}
 //Walk the syntax tree and only go "down" if F is true
 fn ts_filterd_visit<F: FnMut(Node) -> bool>(root: Node, mut f: F) {
     let mut reached_root = false;
@@ -144,7 +283,7 @@ pub fn check_sanity(tree: &Tree, source: &Rope) -> Vec<ErrorInfo> {
                             location: node_range(node, source),
                             severity: DiagnosticSeverity::ERROR,
                             msg: "line breaks are only allowed inside parenthesis".to_string(),
-                            error_type: ErrorType::Any,
+                            error_type: ErrorType::LineBreak,
                         });
                     }
                 }
@@ -157,7 +296,7 @@ pub fn check_sanity(tree: &Tree, source: &Rope) -> Vec<ErrorInfo> {
                     location: node_range(node, source),
                     severity: DiagnosticSeverity::ERROR,
                     msg: "line breaks are only allowed inside parenthesis".to_string(),
-                    error_type: ErrorType::Any,
+                    error_type: ErrorType::LineBreak,
                 });
             }
             if lines.insert(node.start_position().row, node).is_some() {
@@ -166,7 +305,7 @@ pub fn check_sanity(tree: &Tree, source: &Rope) -> Vec<ErrorInfo> {
                     location: node_range(node, source),
                     severity: DiagnosticSeverity::ERROR,
                     msg: "features have to be in different lines".to_string(),
-                    error_type: ErrorType::Any,
+                    error_type: ErrorType::LineBreak,
                 });
             }
         } else {
@@ -177,7 +316,7 @@ pub fn check_sanity(tree: &Tree, source: &Rope) -> Vec<ErrorInfo> {
                     location: node_range(node, source),
                     severity: DiagnosticSeverity::ERROR,
                     msg: "multiline strings are not supported".to_string(),
-                    error_type: ErrorType::Any,
+                    error_type: ErrorType::LineBreak,
                 });
             }
         }
@@ -207,7 +346,7 @@ pub fn classify_error(root: Node, source: &Rope) -> ErrorInfo {
                 severity: DiagnosticSeverity::ERROR,
                 weight: 80,
                 msg: "missing lhs or rhs expression".into(),
-                error_type: ErrorType::Any,
+                error_type: ErrorType::SyntaxError,
             };
         }
     }
@@ -216,7 +355,7 @@ pub fn classify_error(root: Node, source: &Rope) -> ErrorInfo {
         severity: DiagnosticSeverity::ERROR,
         weight: 80,
         msg: "unknown syntax error".into(),
-        error_type: ErrorType::Any,
+        error_type: ErrorType::SyntaxError,
     }
 }
 pub fn check_errors(tree: &Tree, source: &Rope) -> Vec<ErrorInfo> {
@@ -228,7 +367,7 @@ pub fn check_errors(tree: &Tree, source: &Rope) -> Vec<ErrorInfo> {
                 severity: DiagnosticSeverity::ERROR,
                 weight: 80,
                 msg: format!("missing {}", i.kind()),
-                error_type: ErrorType::Any,
+                error_type: ErrorType::SyntaxError,
             });
             false
         } else if i.is_error() {
@@ -248,50 +387,257 @@ pub struct DiagnosticUpdate {
 
 struct DiagnosticState {
     timestamp: u64,
+    //counts changes within the same revision, eg. smt results after linking
+    generation: u64,
     error: Vec<ErrorInfo>,
 }
-async fn maybe_publish(
-    client: &Client,
-    source_map: &mut HashMap<FileID, DiagnosticState>,
-    uri: FileID,
-    mut err: Vec<ErrorInfo>,
-    timestamp: u64,
-) {
-    if let Some(old) = source_map.get_mut(&uri) {
-        if old.timestamp < timestamp {
-            publish(client, &uri.url(), &err).await;
-            old.timestamp = timestamp;
-            old.error = err;
-        } else if old.timestamp == timestamp {
-            old.timestamp = timestamp;
-            old.error.append(&mut err);
-            publish(client, &uri.url(), &old.error).await;
-        }
-    } else {
-        publish(client, &uri.url(), &err).await;
-        source_map.insert(
-            uri,
-            DiagnosticState {
-                timestamp,
-                error: err,
+impl DiagnosticState {
+    fn result_id(&self) -> String {
+        format!("{}.{}", self.timestamp, self.generation)
+    }
+}
+//Latest diagnostics of all files. They are pushed to the client unless it pulls them with
+//textDocument/diagnostic, the result id of a file is its revision and generation.
+#[derive(Clone)]
+pub struct DiagnosticStore {
+    files: Arc<Mutex<HashMap<FileID, DiagnosticState>>>,
+    //latest revision received by the handler
+    revision: watch::Receiver<u64>,
+    pull: Arc<AtomicBool>,
+    refresh: Arc<AtomicBool>,
+}
+impl DiagnosticStore {
+    pub fn new() -> (Self, watch::Sender<u64>) {
+        let (tx, rx) = watch::channel(0);
+        (
+            Self {
+                files: Default::default(),
+                revision: rx,
+                pull: Default::default(),
+                refresh: Default::default(),
             },
-        );
+            tx,
+        )
+    }
+    //Stop pushing, when refresh is supported the client is asked to pull after changes
+    pub fn enable_pull(&self, refresh: bool) {
+        self.pull.store(true, Ordering::SeqCst);
+        self.refresh.store(refresh, Ordering::SeqCst);
+    }
+    pub fn is_pull(&self) -> bool {
+        self.pull.load(Ordering::SeqCst)
+    }
+    //Wait until the diagnostics of revision arrived
+    pub async fn sync(&self, revision: u64) -> Result<()> {
+        let mut rx = self.revision.clone();
+        loop {
+            if *rx.borrow_and_update() >= revision {
+                return Ok(());
+            }
+            rx.changed().await?;
+        }
+    }
+    //Merge err into the state of file, returns all errors of file if they changed
+    fn update(
+        &self,
+        file: FileID,
+        mut err: Vec<ErrorInfo>,
+        timestamp: u64,
+    ) -> Option<Vec<ErrorInfo>> {
+        let mut files = self.files.lock();
+        if let Some(old) = files.get_mut(&file) {
+            if old.timestamp < timestamp {
+                old.timestamp = timestamp;
+                old.generation = 0;
+                old.error = err;
+            } else if old.timestamp == timestamp {
+                old.generation += 1;
+                old.error.append(&mut err);
+            } else {
+                return None;
+            }
+            Some(old.error.clone())
+        } else {
+            files.insert(
+                file,
+                DiagnosticState {
+                    timestamp,
+                    generation: 0,
+                    error: err.clone(),
+                },
+            );
+            Some(err)
+        }
+    }
+    //Full report of file, or unchanged when the client already has previous
+    pub fn report(&self, file: FileID, previous: Option<&str>) -> DocumentDiagnosticReport {
+        let files = self.files.lock();
+        let (result_id, items) = files
+            .get(&file)
+            .map(|state| (state.result_id(), reduce(&state.error)))
+            .unwrap_or_else(|| ("0.0".into(), vec![]));
+        if previous == Some(result_id.as_str()) {
+            DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+                related_documents: None,
+                unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                    result_id,
+                },
+            })
+        } else {
+            DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                related_documents: None,
+                full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                    result_id: Some(result_id),
+                    items,
+                },
+            })
+        }
     }
 }
 
-pub async fn diagnostic_handler(mut rx: mpsc::Receiver<DiagnosticUpdate>, client: Client) {
-    let mut source_map: HashMap<FileID, DiagnosticState> = HashMap::new();
+pub async fn diagnostic_handler(
+    mut rx: mpsc::Receiver<DiagnosticUpdate>,
+    client: Client,
+    store: DiagnosticStore,
+    tx_revision: watch::Sender<u64>,
+) {
+    //Refreshes are sent from their own task so updates never wait on the client, changes
+    //while a refresh is in flight are coalesced into a single one
+    let refresh = Arc::new(Notify::new());
+    let refresh_task = tokio::spawn({
+        let client = client.clone();
+        let refresh = refresh.clone();
+        async move {
+            loop {
+                refresh.notified().await;
+                let _ = client
+                    .send_request::<request::WorkspaceDiagnosticRefresh>(())
+                    .await;
+            }
+        }
+    });
     while let Some(mut update) = rx.recv().await {
+        let mut changed = false;
         for (uri, err) in update.error_state.drain() {
             if uri.is_virtual() {
                 continue;
             }
-            maybe_publish(&client, &mut source_map, uri, err, update.timestamp).await
+            if let Some(err) = store.update(uri, err, update.timestamp) {
+                changed = true;
+                if !store.is_pull() {
+                    publish(&client, &uri.url(), &err).await;
+                }
+            }
+        }
+        tx_revision.send_modify(|revision| *revision = update.timestamp.max(*revision));
+        if changed && store.is_pull() && store.refresh.load(Ordering::SeqCst) {
+            refresh.notify_one();
+        }
+    }
+    refresh_task.abort();
+}
+//Language levels to include for the file, lower levels are dropped when a whole major level
+//is needed
+pub fn missing_lang_lvls(file: &AstDocument, mut needed: Vec<LanguageLevel>) -> Vec<LanguageLevel> {
+    needed.extend(file.lang_lvl_uses().iter().map(|u| u.lang_lvl.clone()));
+    needed.retain(|lvl| !file.includes_lang_lvl(lvl));
+    let mut out: Vec<LanguageLevel> = Vec::new();
+    for lvl in needed.iter().filter(|lvl| !lvl.is_major()) {
+        if !out.iter().any(|o| o.covers(lvl)) {
+            out.push(lvl.clone());
+        }
+    }
+    //The highest major level includes all lower ones
+    for lvl in [
+        LanguageLevel::Type(vec![]),
+        LanguageLevel::Arithmetic(vec![]),
+        LanguageLevel::Boolean(vec![]),
+    ] {
+        if needed
+            .iter()
+            .any(|n| n.is_major() && lvl.covers(n) && n.covers(&lvl))
+        {
+            if !out.iter().any(|o| o.covers(&lvl)) {
+                out.push(lvl);
+            }
+            break;
         }
     }
+    out
 }
-pub fn check_includes(_doc: &AstDocument) -> Vec<ErrorInfo> {
-    Default::default()
+//Compare the language levels needed by the file with its include section. Without an include
+//section everything is implicitly included, the needed levels are only reported once on the
+//features keyword then.
+pub fn check_includes(doc: &AstDocument) -> Vec<ErrorInfo> {
+    let mut errors = Vec::new();
+    if doc.all_lang_lvls().next().is_none() {
+        let levels = missing_lang_lvls(doc, Vec::new());
+        let location = top_level_section(&doc.tree, "features")
+            .and_then(|n| n.child_by_field_name("header"))
+            .and_then(|n| lsp_range(n.byte_range(), &doc.source));
+        if let (false, Some(location)) = (levels.is_empty(), location) {
+            errors.push(ErrorInfo {
+                location,
+                severity: DiagnosticSeverity::INFORMATION,
+                weight: 1,
+                error_type: ErrorType::MissingInclude,
+                msg: format!(
+                    "needs {}, the model has no include section",
+                    levels
+                        .iter()
+                        .map(|l| l.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            });
+        }
+        return errors;
+    }
+    for used in doc.lang_lvl_uses() {
+        if doc.includes_lang_lvl(&used.lang_lvl) {
+            continue;
+        }
+        let Some(location) = lsp_range(used.span.clone(), &doc.source) else {
+            continue;
+        };
+        errors.push(ErrorInfo {
+            location,
+            severity: DiagnosticSeverity::ERROR,
+            weight: 10,
+            error_type: ErrorType::MissingInclude,
+            msg: format!("{} is not included", used.lang_lvl),
+        });
+    }
+    for sym in doc.all_lang_lvls() {
+        let included = doc.lang_lvl(sym).unwrap();
+        let used = match included {
+            LanguageLevel::Boolean(m) if m.is_empty() => true,
+            //String comparisons are only known after type checking
+            LanguageLevel::Type(m)
+                if m.contains(&LanguageLevelType::Any)
+                    || m.contains(&LanguageLevelType::StringConstraints) =>
+            {
+                true
+            }
+            _ => doc
+                .lang_lvl_uses()
+                .iter()
+                .any(|u| included.covers(&u.lang_lvl)),
+        };
+        if used {
+            continue;
+        }
+        if let Some(location) = doc.lsp_range(sym) {
+            errors.push(ErrorInfo {
+                location,
+                severity: DiagnosticSeverity::WARNING,
+                weight: 1,
+                error_type: ErrorType::UnusedInclude,
+                msg: format!("{} is included but never used", included),
+            });
+        }
+    }
+    errors
 }
 //Used to gather errors in compiler stages
 pub struct ErrorsAcc<'a> {
@@ -313,7 +659,14 @@ impl<'a> ErrorsAcc<'a> {
             .map(|u| !u.is_empty())
             .unwrap_or(false)
     }
-    pub fn sym<S: Into<String>>(&mut self, sym: Symbol, file: FileID, weight: u32, s: S) {
+    pub fn sym<S: Into<String>>(
+        &mut self,
+        sym: Symbol,
+        file: FileID,
+        weight: u32,
+        error_type: ErrorType,
+        s: S,
+    ) {
         insert_multi(
             &mut self.errors,
             file,
@@ -322,12 +675,19 @@ impl<'a> ErrorsAcc<'a> {
                 severity: DiagnosticSeverity::ERROR,
                 weight,
                 msg: s.into(),
-                error_type: ErrorType::Any,
+                error_type,
             },
         );
     }
 
-    pub fn sym_info<S: Into<String>>(&mut self, sym: Symbol, file: FileID, weight: u32, s: S) {
+    pub fn sym_info<S: Into<String>>(
+        &mut self,
+        sym: Symbol,
+        file: FileID,
+        weight: u32,
+        error_type: ErrorType,
+        s: S,
+    ) {
         insert_multi(
             &mut self.errors,
             file,
@@ -336,11 +696,18 @@ impl<'a> ErrorsAcc<'a> {
                 severity: DiagnosticSeverity::INFORMATION,
                 weight,
                 msg: s.into(),
-                error_type: ErrorType::Any,
+                error_type,
             },
         );
     }
-    pub fn span<S: Into<String>>(&mut self, span: Span, file: FileID, weight: u32, s: S) {
+    pub fn span<S: Into<String>>(
+        &mut self,
+        span: Span,
+        file: FileID,
+        weight: u32,
+        error_type: ErrorType,
+        s: S,
+    ) {
         let source = self
             .configs
             .get(&file)
@@ -355,12 +722,19 @@ impl<'a> ErrorsAcc<'a> {
                 severity: DiagnosticSeverity::ERROR,
                 weight,
                 msg: s.into(),
-                error_type: ErrorType::Any,
+                error_type,
             },
         );
     }
 
-    pub fn span_info<S: Into<String>>(&mut self, span: Span, file: FileID, weight: u32, s: S) {
+    pub fn span_info<S: Into<String>>(
+        &mut self,
+        span: Span,
+        file: FileID,
+        weight: u32,
+        error_type: ErrorType,
+        s: S,
+    ) {
         let source = self
             .configs
             .get(&file)
@@ -375,8 +749,47 @@ impl<'a> ErrorsAcc<'a> {
                 severity: DiagnosticSeverity::INFORMATION,
                 weight,
                 msg: s.into(),
-                error_type: ErrorType::Any,
+                error_type,
             },
         );
     }
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn document(text: &str) -> AstDocument {
+        let source = Rope::from_str(text);
+        let tree = parse(&source, None);
+        AstDocument::new(
+            source,
+            tree,
+            Url::parse("file:///test.uvl").unwrap(),
+            Instant::now(),
+        )
+    }
+    #[test]
+    fn includes_reported_once_without_section() {
+        let doc = document(
+            "features\n\tA cardinality [1..3]\n\t\toptional\n\t\t\tString B\n\t\t\tInteger C\nconstraints\n\tC > 2\n",
+        );
+        let errors = check_includes(&doc);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].severity, DiagnosticSeverity::INFORMATION);
+        assert_eq!(errors[0].location.start, Position::new(0, 0));
+        assert_eq!(errors[0].location.end, Position::new(0, 8));
+        assert!(errors[0].msg.contains("Type"));
+    }
+    #[test]
+    fn string_values_need_no_string_constraints() {
+        let doc = document("features\n\tA {name 'a'}\n");
+        assert!(missing_lang_lvls(&doc, Vec::new()).is_empty());
+        let doc = document("features\n\tString A\n");
+        let levels: Vec<String> = missing_lang_lvls(&doc, Vec::new())
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        assert_eq!(levels, vec!["Type".to_string()]);
+        let doc = document("include\n\tBoolean\nfeatures\n\tA\n");
+        assert!(missing_lang_lvls(&doc, Vec::new()).is_empty());
+    }
+}
@@ -236,7 +236,12 @@ fn opt_configs(state: &mut State) -> Vec<ConfigEntry> {
                         if let Ok(num) = state.source.slice_raw(val.byte_range()).parse() {
                             acc.push(ConfigEntry::Value(key, ConfigValue::Number(num)));
                         } else {
-                            state.push_error_node(val, 30, "cant parse number");
+                            state.push_error_node(
+                                val,
+                                30,
+                                ErrorType::InvalidNumber,
+                                "cant parse number",
+                            );
                         }
                     }
                     "string" => {
@@ -283,6 +288,7 @@ fn opt_configs(state: &mut State) -> Vec<ConfigEntry> {
                         state.push_error_node(
                             val,
                             30,
+                            ErrorType::InvalidConfigFile,
                             format!("Expect Number or Bool {:?}", val.kind()),
                         );
                     }
@@ -315,7 +321,12 @@ fn visit_file(state: &mut State) -> Option<FileConfig> {
                                 val.byte_range(),
                             ))
                         } else {
-                            state.push_error_node(val, 30, "expected string");
+                            state.push_error_node(
+                                val,
+                                30,
+                                ErrorType::InvalidConfigFile,
+                                "expected string",
+                            );
                         }
                     }
                     Some("config") => visit_children(state, |state| {
@@ -323,11 +334,11 @@ fn visit_file(state: &mut State) -> Option<FileConfig> {
                         if state.kind() == "object" {
                             config = Some(visit_children(state, opt_configs));
                         } else {
-                            state.push_error(30, "expected object");
+                            state.push_error(30, ErrorType::InvalidConfigFile, "expected object");
                         }
                     }),
                     _ => {
-                        state.push_error(40, "unknown key ");
+                        state.push_error(40, ErrorType::InvalidConfigFile, "unknown key ");
                     }
                 }
             }
@@ -342,7 +353,7 @@ fn visit_file(state: &mut State) -> Option<FileConfig> {
                 config,
             })
         } else {
-            state.push_error(40, "missing file key");
+            state.push_error(40, ErrorType::InvalidConfigFile, "missing file key");
             None
         }
     })
@@ -353,7 +364,7 @@ fn visit_root(state: &mut State) -> Option<FileConfig> {
     if state.kind() == "object" {
         visit_file(state)
     } else {
-        state.push_error(40, "expected file object");
+        state.push_error(40, ErrorType::InvalidConfigFile, "expected file object");
         None
     }
 }
@@ -383,7 +394,7 @@ pub fn parse_json(tree: Tree, source: Rope, uri: Url, timestamp: Instant) -> Con
                 weight: 100,
                 severity: DiagnosticSeverity::ERROR,
                 msg: "JSON syntax errors".into(),
-                error_type: ErrorType::Any,
+                error_type: ErrorType::InvalidConfigFile,
             });
             (None, state.err)
         } else {
@@ -1,3 +1,4 @@
+use crate::core::PositionEncoding;
 use ropey::Rope;
 use std::time::SystemTime;
 use tokio::time::Instant;
@@ -5,8 +6,8 @@ use tower_lsp::lsp_types::*;
 use tree_sitter::{InputEdit, Tree};
 
 //update the document text using text deltas form the editor
-//for some insane reason lsp uses utf16 code points as offsets
-//so we have to transform utf16->bytes for tree-sitter
+//lsp offsets are code units of the negotiated encoding
+//so we have to transform them to bytes for tree-sitter
 pub fn update_text(
     source: &mut Rope,
     tree: Option<&mut Tree>,
@@ -21,9 +22,14 @@ pub fn update_text(
             let start_col = range.start.character as usize;
             let end_col = range.end.character as usize;
 
-            let start_col8 = source.line(start_line).utf16_cu_to_char(start_col);
+            let encoding = PositionEncoding::get();
+            let to_char = |line: usize, col: usize| {
+                let line = source.line(line);
+                encoding.to_char(line, col).unwrap_or(line.len_chars())
+            };
+            let start_col8 = to_char(start_line, start_col);
             let end_col8 = if end_line < source.len_lines() {
-                source.line(end_line).utf16_cu_to_char(end_col)
+                to_char(end_line, end_col)
             } else {
                 0
             };
@@ -9,9 +9,12 @@ pub mod parse;
 pub mod pipeline;
 pub mod query;
 pub mod resolve;
+pub mod scheduler;
 pub mod semantic;
+pub mod settings;
 
 pub mod util;
+pub mod workspace;
 pub use actions::*;
 pub use ast::*;
 pub use cache::*;
@@ -22,5 +25,7 @@ pub use log::info;
 pub use module::*;
 pub use parse::*;
 pub use pipeline::*;
+pub use scheduler::*;
 pub use semantic::*;
+pub use settings::*;
 pub use util::*;
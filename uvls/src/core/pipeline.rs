@@ -18,6 +18,7 @@ use tokio::{
     time::Instant,
 };
 use tower_lsp::lsp_types::*;
+use ustr::Ustr;
 use util::Result;
 //The parsing frontend
 //To allow for more nimble and robust parsing, we use 2 stage process to parse 2 different syntax
@@ -41,7 +42,12 @@ enum DraftMsg {
     Shutdown, //Not really needed, TODO remove this
 }
 //Turn a tree-sitter trees into a usable rust structure and send it to the linker
-async fn make_red_tree(draft: Draft, uri: Url, tx_link: mpsc::Sender<LinkMsg>) {
+async fn make_red_tree(
+    draft: Draft,
+    uri: Url,
+    tx_link: mpsc::Sender<LinkMsg>,
+    scheduler: Scheduler,
+) {
     info!("update red tree {uri}");
     match draft {
         Draft::UVL {
@@ -53,6 +59,8 @@ async fn make_red_tree(draft: Draft, uri: Url, tx_link: mpsc::Sender<LinkMsg>) {
                 ast::AstDocument::new(source.clone(), tree.clone(), uri.clone(), timestamp);
             ast.errors.append(&mut check::check_sanity(&tree, &source));
             ast.errors.append(&mut check::check_errors(&tree, &source));
+            ast.errors.append(&mut check::check_includes(&ast));
+            scheduler.promote_imports(&ast);
             let _ = tx_link.send(LinkMsg::UpdateAst(Arc::new(ast))).await;
         }
         Draft::JSON {
@@ -76,6 +84,7 @@ async fn draft_handler(
     initial_text: String,
     tx_link: mpsc::Sender<LinkMsg>,
     initial_timestamp: Instant,
+    scheduler: Scheduler,
 ) {
     let rope = Rope::from_str(&initial_text);
     let mut draft = if util::is_config(&uri) {
@@ -92,7 +101,12 @@ async fn draft_handler(
         }
     };
     info!("started draft handler {uri}");
-    spawn(make_red_tree(draft.clone(), uri.clone(), tx_link.clone()));
+    spawn(make_red_tree(
+        draft.clone(),
+        uri.clone(),
+        tx_link.clone(),
+        scheduler.clone(),
+    ));
     while let Some(msg) = rx.recv().await {
         match msg {
             DraftMsg::Delete(timestamp) => {
@@ -136,7 +150,12 @@ async fn draft_handler(
                         }
                     }
                 };
-                spawn(make_red_tree(draft.clone(), uri.clone(), tx_link.clone()));
+                spawn(make_red_tree(
+                    draft.clone(),
+                    uri.clone(),
+                    tx_link.clone(),
+                    scheduler.clone(),
+                ));
             }
             DraftMsg::Snapshot(out) => {
                 let _ = out.send(draft.clone());
@@ -155,6 +174,7 @@ enum LinkMsg {
     Delete(Url, Instant),
     UpdateAst(Arc<ast::AstDocument>),
     UpdateConfig(Arc<config::ConfigDocument>),
+    Folders(Vec<Url>),
 }
 //This handler links documents together, it also does type checking
 async fn link_handler(
@@ -166,14 +186,35 @@ async fn link_handler(
     let mut latest_configs: HashMap<FileID, Arc<config::ConfigDocument>> = HashMap::new();
     let mut latest_ast: HashMap<FileID, Arc<ast::AstDocument>> = HashMap::new();
     let mut timestamps: HashMap<Url, Instant> = HashMap::new();
-    let (tx_execute, rx_execute) = watch::channel((latest_ast.clone(), latest_configs.clone(), 0));
+    let mut folders: Arc<Vec<Vec<Ustr>>> = Arc::new(Vec::new());
+    let (tx_execute, rx_execute) = watch::channel((
+        latest_ast.clone(),
+        latest_configs.clone(),
+        folders.clone(),
+        0,
+    ));
     let mut dirty = false;
     let mut revision = 0; //Each change is one revision
     info!("started link handler");
     spawn(link_executor(rx_execute, tx_cache, tx_err));
-    let mut timer = tokio::time::interval(tokio::time::Duration::from_millis(100));
+    let mut rx_settings = watch_settings();
+    let debounce = |settings: &Settings| {
+        tokio::time::interval(tokio::time::Duration::from_millis(settings.debounce.max(1)))
+    };
+    let mut timer = debounce(&settings());
+    let mut smt_enabled = settings().smt.enabled;
     loop {
         select! {
+            Ok(())=rx_settings.changed()=>{
+                let settings = rx_settings.borrow_and_update().clone();
+                timer = debounce(&settings);
+                //a new revision replaces the published solver results
+                if settings.smt.enabled != smt_enabled {
+                    smt_enabled = settings.smt.enabled;
+                    revision += 1;
+                    dirty = true;
+                }
+            }
             Some(msg)=rx.recv()=>{
                 match msg{
                     LinkMsg::Delete(uri,timestamp)=>{
@@ -197,6 +238,11 @@ async fn link_handler(
                         dirty=true;
 
                     }
+                    LinkMsg::Folders(uris)=>{
+                        folders = Arc::new(uris.iter().filter_map(folder_path).collect());
+                        revision +=1;
+                        dirty=true;
+                    }
                     LinkMsg::UpdateConfig(conf)=>{
                         if timestamps.get(&conf.uri).map(|old|old < &conf.timestamp).unwrap_or(true){
                             timestamps.insert(conf.uri.clone(),conf.timestamp);
@@ -213,11 +259,11 @@ async fn link_handler(
 
                 }
             }
-            _=timer.tick()=>{//every 100ms relink if there are changes
+            _=timer.tick()=>{//relink after the debounce interval if there are changes
                 if dirty{
                     info!("link prepare");
                     dirty=false;
-                    tx_execute.send_modify(|old|*old=(latest_ast.clone(),latest_configs.clone(),revision))
+                    tx_execute.send_modify(|old|*old=(latest_ast.clone(),latest_configs.clone(),folders.clone(),revision))
                 }
             }
             else =>{
@@ -231,12 +277,15 @@ async fn link_handler(
         mut rx: watch::Receiver<(
             HashMap<FileID, Arc<ast::AstDocument>>,
             HashMap<FileID, Arc<config::ConfigDocument>>,
+            Arc<Vec<Vec<Ustr>>>,
             u64,
         )>,
         tx_cache: watch::Sender<Arc<RootGraph>>,
         tx_err: mpsc::Sender<DiagnosticUpdate>,
     ) {
         let mut timestamps: HashMap<FileID, Instant> = HashMap::new();
+        let mut latest_folders = Arc::new(Vec::new());
+        let mut latest_smt_enabled = settings().smt.enabled;
         info!("started link execute");
         loop {
             if rx.changed().await.is_err() {
@@ -244,7 +293,15 @@ async fn link_handler(
             }
             info!("link execute");
             tx_cache.borrow().cancel();
-            let (ast, configs, revision) = (*rx.borrow_and_update()).clone();
+            let (ast, configs, folders, revision) = (*rx.borrow_and_update()).clone();
+            //imports resolve differently with other folders, so everything is relinked
+            //the same goes for switching the solver, all diagnostics are published again
+            let smt_enabled = settings().smt.enabled;
+            if folders != latest_folders || smt_enabled != latest_smt_enabled {
+                timestamps.clear();
+                latest_folders = folders.clone();
+                latest_smt_enabled = smt_enabled;
+            }
             let mut err = ErrorsAcc {
                 files: &ast,
                 configs: &configs,
@@ -253,7 +310,15 @@ async fn link_handler(
             let old = tx_cache.borrow().cache().clone();
 
             //link files incrementally
-            let root = RootGraph::new(&ast, &configs, revision, &old, &mut err, &mut timestamps);
+            let root = RootGraph::new(
+                &ast,
+                &configs,
+                &folders,
+                revision,
+                &old,
+                &mut err,
+                &mut timestamps,
+            );
 
             let _ = tx_cache.send(Arc::new(root));
             let _ = tx_err
@@ -282,6 +347,12 @@ pub struct AsyncPipeline {
     client: tower_lsp::Client,
     //code inlays are managed globally
     inlay_handler: InlayHandler,
+    //dead and core features from the last smt run
+    fixed_state: smt::FixedState,
+    //latest diagnostics for pull requests
+    diagnostics: check::DiagnosticStore,
+    //background loading
+    scheduler: Scheduler,
 }
 impl AsyncPipeline {
     pub fn new(client: tower_lsp::Client) -> Self {
@@ -291,16 +362,29 @@ impl AsyncPipeline {
         let revision_counter = Arc::new(AtomicU64::new(0));
         let (tx_dirty, _) = broadcast::channel(1024);
         let inlay_handler = InlayHandler::new(client.clone());
+        let fixed_state = smt::FixedState::default();
+        let scheduler = Scheduler::new(rx_root.clone());
         spawn(link_handler(rx_link, tx_root, tx_err.clone()));
-        spawn(check::diagnostic_handler(rx_err, client.clone()));
+        let (diagnostics, tx_revision) = check::DiagnosticStore::new();
+        spawn(check::diagnostic_handler(
+            rx_err,
+            client.clone(),
+            diagnostics.clone(),
+            tx_revision,
+        ));
         spawn(smt::check_handler(
             rx_root.clone(),
             tx_err.clone(),
             client.clone(),
             inlay_handler.clone(),
+            fixed_state.clone(),
+            scheduler.clone(),
         ));
         AsyncPipeline {
+            scheduler,
+            diagnostics,
             inlay_handler,
+            fixed_state,
             client,
             tx_dirty_tree: tx_dirty,
             revision_counter,
@@ -322,6 +406,15 @@ impl AsyncPipeline {
     pub fn inlay_state(&self) -> &InlayHandler {
         &self.inlay_handler
     }
+    pub fn fixed_state(&self) -> &smt::FixedState {
+        &self.fixed_state
+    }
+    pub fn diagnostics(&self) -> &check::DiagnosticStore {
+        &self.diagnostics
+    }
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
 
     pub fn client(&self) -> tower_lsp::Client {
         self.client.clone()
@@ -330,6 +423,12 @@ impl AsyncPipeline {
         self.tx_dirty_tree.subscribe()
     }
     pub fn open(&self, uri: Url, text: String, state: DocumentState) {
+        //editor owned documents are parsed right away and take the background jobs focus
+        let editor = matches!(state, DocumentState::OwnedByEditor);
+        if editor {
+            self.scheduler.cancel_if(|queued| queued == &uri);
+            self.scheduler.add_focus(FileID::from_uri(&uri));
+        }
         match self.drafts.entry(uri.clone()) {
             dashmap::mapref::entry::Entry::Vacant(e) => {
                 let timestamp = Instant::now();
@@ -342,6 +441,7 @@ impl AsyncPipeline {
                     text,
                     self.tx_link.clone(),
                     timestamp,
+                    self.scheduler.clone(),
                 ));
                 e.insert(DraftState {
                     handler: tx,
@@ -362,6 +462,7 @@ impl AsyncPipeline {
                         text,
                         self.tx_link.clone(),
                         timestamp,
+                        self.scheduler.clone(),
                     ));
                     e.insert(DraftState {
                         handler: tx,
@@ -371,6 +472,10 @@ impl AsyncPipeline {
                 }
             }
         }
+        if editor {
+            self.scheduler
+                .editor_change(self.revision_counter.load(Ordering::SeqCst));
+        }
     }
     pub fn should_load(&self, uri: &Url, time: SystemTime) -> bool {
         self.drafts
@@ -386,6 +491,7 @@ impl AsyncPipeline {
             .drafts
             .remove_if(uri, |_, v| v.state.can_update(&state))
         {
+            self.scheduler.remove_focus(FileID::from_uri(uri));
             self.revision_counter.fetch_add(1, Ordering::SeqCst);
             let _ = self.tx_dirty_tree.send(());
             let _ = self
@@ -394,9 +500,39 @@ impl AsyncPipeline {
                 .await;
         }
     }
+    //Workspace folders keep their import resolution separate
+    pub async fn set_workspace_folders(&self, folders: Vec<Url>) {
+        self.revision_counter.fetch_add(1, Ordering::SeqCst);
+        let _ = self.tx_dirty_tree.send(());
+        let _ = self.tx_link.send(LinkMsg::Folders(folders)).await;
+    }
+    //Remove all files under folder that are not opened in the editor
+    pub async fn unload_folder(&self, folder: &Url, keep: impl Fn(&Url) -> bool) {
+        let Ok(folder) = folder.to_file_path() else {
+            return;
+        };
+        let under = |uri: &Url| {
+            uri.to_file_path()
+                .map(|path| path.starts_with(&folder))
+                .unwrap_or(false)
+                && !keep(uri)
+        };
+        self.scheduler.cancel_if(under);
+        let uris: Vec<Url> = self
+            .drafts
+            .iter()
+            .map(|draft| draft.key().clone())
+            .filter(|uri| under(uri))
+            .collect();
+        for uri in uris {
+            self.delete(&uri, DocumentState::OwnedByOs(SystemTime::now()))
+                .await;
+        }
+    }
     pub fn update(&self, params: DidChangeTextDocumentParams) {
         if let Some(state) = self.drafts.get(&params.text_document.uri) {
-            self.revision_counter.fetch_add(1, Ordering::SeqCst);
+            let revision = self.revision_counter.fetch_add(1, Ordering::SeqCst) + 1;
+            self.scheduler.editor_change(revision);
             let _ = self.tx_dirty_tree.send(());
             let _ = state.handler.send(DraftMsg::Update(params, Instant::now()));
         }
@@ -464,18 +600,16 @@ impl AsyncPipeline {
             rx.changed().await?;
         }
     }
-    //wait until ALL parsing is done and root is clean
+    //wait until the focus is loaded and root contains it, the background scan is not waited for
     pub async fn sync_root_global(&self) -> Result<Arc<RootGraph>> {
+        self.scheduler.focus_loaded().await?;
+        let revision = self.revision_counter.load(Ordering::SeqCst);
         let mut rx = self.rx_root.clone();
         loop {
             {
                 let state = rx.borrow_and_update();
-                info!(
-                    "sync {} {}",
-                    state.revision(),
-                    self.revision_counter.load(Ordering::SeqCst)
-                );
-                if self.revision_counter.load(Ordering::SeqCst) <= state.revision() {
+                info!("sync {} {}", state.revision(), revision);
+                if revision <= state.revision() {
                     return Ok(state.clone());
                 }
             }
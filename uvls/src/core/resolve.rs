@@ -214,9 +214,15 @@ pub fn resolve_file(file: FileID, fs: &FileSystem, err: &mut ErrorsAcc) -> RefMa
             }
             if !ok {
                 if found_some {
-                    err.sym(r, file, 30, "expected a feature");
+                    err.sym(r, file, 30, ErrorType::TypeMismatch, "expected a feature");
                 } else {
-                    err.sym(r, file, 30, "unresolved reference");
+                    err.sym(
+                        r,
+                        file,
+                        30,
+                        ErrorType::UnresolvedReference,
+                        "unresolved reference",
+                    );
                 }
             }
         }
@@ -259,13 +265,20 @@ fn resolve_constraint(
             }
             match state {
                 ResolveState::Unresolved => {
-                    err.sym(*sym, file, 30, "unresolved reference");
+                    err.sym(
+                        *sym,
+                        file,
+                        30,
+                        ErrorType::UnresolvedReference,
+                        "unresolved reference",
+                    );
                 }
                 ResolveState::WrongType { expected, found } => {
                     err.sym(
                         *sym,
                         file,
                         30,
+                        ErrorType::TypeMismatch,
                         format!("expected {:?} found {:?}", expected, found),
                     );
                 }
@@ -301,6 +314,7 @@ fn resolve_constraint(
                     constraint.span.clone(),
                     file,
                     30,
+                    ErrorType::TypeMismatch,
                     format!(
                         "type missmatch {:?} and {:?}",
                         select_type(lhs_ty),
@@ -311,23 +325,20 @@ fn resolve_constraint(
             }
             let req = Type::String | Type::Real;
             let ty = req & lhs_ty & rhs_ty;
-            if !((Type::String | Type::String) & ty).is_empty()
-                && !{
-                    // if TYPE-level.string-constraints is not included in any way
-                    let ast_document = ctx.files.get(&file).unwrap();
-                    ast_document.all_lang_lvls()
-                        .map(|x| ast_document.lang_lvl(x).unwrap())
-                             .any(|s|
-                                matches!(s, LanguageLevel::Type(x) if x.contains(&LanguageLevelType::Any))
-                             || matches!(s, LanguageLevel::Type(x) if x.contains(&LanguageLevelType::StringConstraints)))
-                        || {let s: Vec<Symbol> = ast_document.all_lang_lvls().collect(); s.is_empty()}
-                }
+            let string_constraints =
+                LanguageLevel::Type(vec![LanguageLevelType::StringConstraints]);
+            let ast_document = ctx.files.get(&file).unwrap();
+            // without an include section everything is implicitly included
+            if ty.contains(Type::String)
+                && ast_document.all_lang_lvls().next().is_some()
+                && !ast_document.includes_lang_lvl(&string_constraints)
             {
                 err.span(
                     constraint.span.clone(),
                     file,
                     30,
-                    format!("Need to include TYPE-level.string-constraints"),
+                    ErrorType::MissingStringConstraints,
+                    format!("{} is not included", string_constraints),
                 );
             }
             if ty.is_empty() {
@@ -335,6 +346,7 @@ fn resolve_constraint(
                     constraint.span.clone(),
                     file,
                     30,
+                    ErrorType::TypeMismatch,
                     format!(
                         "unsupported operand type {:?}",
                         select_type(lhs_ty & rhs_ty),
@@ -373,6 +385,7 @@ fn gather_expr_options(
                         context,
                         file,
                         10,
+                        ErrorType::UnresolvedReference,
                         "unresolved context expected file root or feature",
                     );
                 }
@@ -388,6 +401,7 @@ fn gather_expr_options(
                     expr.span.clone(),
                     file,
                     30,
+                    ErrorType::TypeMismatch,
                     format!("type missmatch expected Real/Number",),
                 );
                 Default::default()
@@ -411,6 +425,7 @@ fn gather_expr_options(
                     expr.span.clone(),
                     file,
                     30,
+                    ErrorType::TypeMismatch,
                     format!(
                         "type missmatch {:?} and {:?}",
                         select_type(lhs_ty),
@@ -428,6 +443,7 @@ fn gather_expr_options(
                         expr.span.clone(),
                         file,
                         30,
+                        ErrorType::TypeMismatch,
                         format!("unsupported operator type {}", select_type(rhs_ty & lhs_ty),),
                     );
                 }
@@ -443,6 +459,7 @@ fn gather_expr_options(
                     expr.span.clone(),
                     file,
                     30,
+                    ErrorType::TypeMismatch,
                     format!("type missmatch expected String",),
                 );
                 Default::default()
@@ -457,7 +474,13 @@ fn gather_expr_options(
                 .map(|i| ctx.type_of(i).unwrap())
                 .fold(BitFlags::default(), |acc, i: Type| acc | i);
             if ty.is_empty() {
-                err.sym(*sym, file, 30, "unresolved reference");
+                err.sym(
+                    *sym,
+                    file,
+                    30,
+                    ErrorType::UnresolvedReference,
+                    "unresolved reference",
+                );
             }
             ty
         }
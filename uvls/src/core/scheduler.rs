@@ -0,0 +1,410 @@
+use crate::core::*;
+use hashbrown::{HashMap, HashSet};
+use log::info;
+use parking_lot::Mutex;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{oneshot, watch, Notify};
+use tokio::{spawn, task::spawn_blocking};
+use tower_lsp::lsp_types::Url;
+//Background work like the workspace scan is queued per uri and only runs while the root graph
+//contains all editor changes. Editor owned documents and their transitive imports form the
+//focus, queued imports of the focus are promoted and the focus is SMT-checked first.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Background,
+    Import,
+}
+type Job = Box<dyn FnOnce() + Send>;
+struct Pending {
+    priority: Priority,
+    seq: u64,
+    job: Job,
+    //true if the job ran, false if it was cancelled
+    done: oneshot::Sender<bool>,
+}
+#[derive(Default)]
+struct Queue {
+    jobs: HashMap<Url, Pending>,
+    //entries are invalidated lazily when a job is promoted or cancelled
+    order: BinaryHeap<(Priority, Reverse<u64>, Url)>,
+    seq: u64,
+    //priority of the running job
+    running: Option<Priority>,
+}
+impl Queue {
+    fn push_order(&mut self, uri: Url, priority: Priority) -> u64 {
+        self.seq += 1;
+        self.order.push((priority, Reverse(self.seq), uri));
+        self.seq
+    }
+    fn pop(&mut self) -> Option<Pending> {
+        while let Some((priority, Reverse(seq), uri)) = self.order.pop() {
+            if self
+                .jobs
+                .get(&uri)
+                .map(|p| p.priority == priority && p.seq == seq)
+                .unwrap_or(false)
+            {
+                return self.jobs.remove(&uri);
+            }
+        }
+        None
+    }
+}
+#[derive(Default)]
+struct Focus {
+    //editor owned documents
+    owned: HashSet<FileID>,
+    //imported documents of each parsed document
+    imports: HashMap<FileID, Vec<Url>>,
+    //owned documents and their transitive imports
+    files: HashSet<FileID>,
+}
+impl Focus {
+    //Recompute the transitive imports of the owned documents, returns the imported documents
+    fn rebuild(&mut self) -> Vec<Url> {
+        let mut files = self.owned.clone();
+        let mut stack: Vec<FileID> = self.owned.iter().cloned().collect();
+        let mut imported = Vec::new();
+        while let Some(file) = stack.pop() {
+            for uri in self.imports.get(&file).into_iter().flatten() {
+                let id = FileID::from_uri(uri);
+                if files.insert(id) {
+                    stack.push(id);
+                    imported.push(uri.clone());
+                }
+            }
+        }
+        self.files = files;
+        imported
+    }
+}
+#[derive(Clone)]
+pub struct Scheduler {
+    queue: Arc<Mutex<Queue>>,
+    wake: Arc<Notify>,
+    //revision of the latest editor change, background jobs wait until it is linked
+    editor_revision: Arc<AtomicU64>,
+    focus: Arc<Mutex<Focus>>,
+    //number of queued and running jobs
+    tx_pending: Arc<watch::Sender<usize>>,
+    rx_pending: watch::Receiver<usize>,
+}
+impl Scheduler {
+    pub fn new(rx_root: watch::Receiver<Arc<RootGraph>>) -> Self {
+        let (tx_pending, rx_pending) = watch::channel(0);
+        let scheduler = Self {
+            queue: Default::default(),
+            wake: Default::default(),
+            editor_revision: Default::default(),
+            focus: Default::default(),
+            tx_pending: Arc::new(tx_pending),
+            rx_pending,
+        };
+        spawn(scheduler.clone().worker(rx_root));
+        scheduler
+    }
+    async fn worker(self, mut rx_root: watch::Receiver<Arc<RootGraph>>) {
+        loop {
+            if self.queue.lock().jobs.is_empty() {
+                self.wake.notified().await;
+                continue;
+            }
+            //idle: all editor changes are linked
+            while rx_root.borrow_and_update().revision()
+                < self.editor_revision.load(Ordering::SeqCst)
+            {
+                if rx_root.changed().await.is_err() {
+                    return;
+                }
+            }
+            let Some(pending) = ({
+                let mut queue = self.queue.lock();
+                let pending = queue.pop();
+                queue.running = pending.as_ref().map(|p| p.priority);
+                pending
+            }) else {
+                continue;
+            };
+            let ran = spawn_blocking(pending.job).await.is_ok();
+            let _ = pending.done.send(ran);
+            self.queue.lock().running = None;
+            self.tx_pending.send_modify(|n| *n -= 1);
+        }
+    }
+    //Queue a blocking job for uri, it replaces an older job for the same uri
+    pub fn schedule<F: FnOnce() + Send + 'static>(
+        &self,
+        uri: Url,
+        priority: Priority,
+        job: F,
+    ) -> oneshot::Receiver<bool> {
+        let (done, rx) = oneshot::channel();
+        let mut queue = self.queue.lock();
+        let priority = queue
+            .jobs
+            .get(&uri)
+            .map(|old| old.priority.max(priority))
+            .unwrap_or(priority);
+        let seq = queue.push_order(uri.clone(), priority);
+        let old = queue.jobs.insert(
+            uri,
+            Pending {
+                priority,
+                seq,
+                job: Box::new(job),
+                done,
+            },
+        );
+        drop(queue);
+        if let Some(old) = old {
+            let _ = old.done.send(false);
+        } else {
+            self.tx_pending.send_modify(|n| *n += 1);
+        }
+        self.wake.notify_one();
+        rx
+    }
+    //Move a queued job for uri in front of the background jobs
+    pub fn promote(&self, uri: &Url) {
+        let mut queue = self.queue.lock();
+        if queue
+            .jobs
+            .get(uri)
+            .map(|p| p.priority < Priority::Import)
+            .unwrap_or(false)
+        {
+            info!("promote {uri}");
+            let seq = queue.push_order(uri.clone(), Priority::Import);
+            let pending = queue.jobs.get_mut(uri).unwrap();
+            pending.priority = Priority::Import;
+            pending.seq = seq;
+        }
+    }
+    //Drop all queued jobs for uris matching f
+    pub fn cancel_if<F: Fn(&Url) -> bool>(&self, f: F) {
+        let cancelled: Vec<Pending> = {
+            let mut queue = self.queue.lock();
+            let uris: Vec<Url> = queue.jobs.keys().filter(|uri| f(uri)).cloned().collect();
+            uris.iter()
+                .filter_map(|uri| queue.jobs.remove(uri))
+                .collect()
+        };
+        if !cancelled.is_empty() {
+            self.tx_pending.send_modify(|n| *n -= cancelled.len());
+        }
+        for pending in cancelled {
+            let _ = pending.done.send(false);
+        }
+    }
+    pub fn editor_change(&self, revision: u64) {
+        self.editor_revision.fetch_max(revision, Ordering::SeqCst);
+    }
+    fn set_focus(&self, f: impl FnOnce(&mut Focus) -> bool) {
+        let imported = {
+            let mut focus = self.focus.lock();
+            if !f(&mut focus) {
+                return;
+            }
+            focus.rebuild()
+        };
+        for uri in imported.iter() {
+            self.promote(uri);
+        }
+    }
+    //An editor owned document and its imports become the focus
+    pub fn add_focus(&self, file: FileID) {
+        self.set_focus(|focus| focus.owned.insert(file));
+    }
+    //A closed or deleted document, its imports stay in focus if another owned document needs them
+    pub fn remove_focus(&self, file: FileID) {
+        self.set_focus(|focus| {
+            let owned = focus.owned.remove(&file);
+            focus.imports.remove(&file).is_some() || owned
+        });
+    }
+    pub fn is_owned(&self, file: FileID) -> bool {
+        self.focus.lock().owned.contains(&file)
+    }
+    pub fn is_focus(&self, file: FileID) -> bool {
+        self.focus.lock().files.contains(&file)
+    }
+    //Record the imports of a document, the imports of focused documents are promoted
+    pub fn promote_imports(&self, ast: &AstDocument) {
+        let Some(dir) = ast
+            .uri
+            .to_file_path()
+            .ok()
+            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+        else {
+            return;
+        };
+        let imports: Vec<Url> = ast
+            .all_imports()
+            .filter_map(|i| {
+                let (file, dirs) = ast.path(i).split_last()?;
+                let mut path = dir.clone();
+                path.extend(dirs.iter().map(|name| name.as_str()));
+                path.push(format!("{file}.uvl"));
+                Url::from_file_path(&path).ok()
+            })
+            .collect();
+        self.set_focus(|focus| {
+            let changed = focus.imports.get(&ast.id) != Some(&imports);
+            focus.imports.insert(ast.id, imports);
+            changed
+        });
+    }
+    //Wait until the promoted imports of the focus are loaded, background jobs are not waited for
+    pub async fn focus_loaded(&self) -> Result<()> {
+        let mut rx = self.rx_pending.clone();
+        loop {
+            rx.borrow_and_update();
+            {
+                let queue = self.queue.lock();
+                if queue.running != Some(Priority::Import)
+                    && !queue
+                        .jobs
+                        .values()
+                        .any(|p| p.priority == Priority::Import)
+                {
+                    return Ok(());
+                }
+            }
+            rx.changed().await?;
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ropey::Rope;
+    use tokio::time::Instant;
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///ws/{}", name)).unwrap()
+    }
+    fn doc(name: &str, text: &str) -> AstDocument {
+        let source = Rope::from_str(text);
+        let tree = parse::parse(&source, None);
+        AstDocument::new(source, tree, uri(name), Instant::now())
+    }
+    fn root(revision: u64) -> Arc<RootGraph> {
+        let files = HashMap::new();
+        let configs = HashMap::new();
+        let mut err = ErrorsAcc {
+            errors: HashMap::new(),
+            files: &files,
+            configs: &configs,
+        };
+        Arc::new(RootGraph::new(
+            &files,
+            &configs,
+            &[],
+            revision,
+            &Cache::default(),
+            &mut err,
+            &mut HashMap::new(),
+        ))
+    }
+    //A scheduler that holds its jobs back until the returned sender links revision 1
+    fn blocked() -> (Scheduler, watch::Sender<Arc<RootGraph>>) {
+        let (tx_root, rx_root) = watch::channel(root(0));
+        let scheduler = Scheduler::new(rx_root);
+        scheduler.editor_change(1);
+        (scheduler, tx_root)
+    }
+    fn record(log: &Arc<Mutex<Vec<String>>>, name: &str) -> impl FnOnce() + Send + 'static {
+        let log = log.clone();
+        let name = name.to_string();
+        move || log.lock().push(name)
+    }
+    //Wait until all queued jobs are done or cancelled
+    async fn drained(scheduler: &Scheduler) {
+        let mut rx = scheduler.rx_pending.clone();
+        while *rx.borrow_and_update() != 0 {
+            rx.changed().await.unwrap();
+        }
+    }
+    #[tokio::test]
+    async fn promoted_first() {
+        let (scheduler, tx_root) = blocked();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let a = scheduler.schedule(uri("a.uvl"), Priority::Background, record(&log, "a"));
+        let b = scheduler.schedule(uri("b.uvl"), Priority::Background, record(&log, "b"));
+        scheduler.promote(&uri("b.uvl"));
+        assert_eq!(*scheduler.rx_pending.borrow(), 2);
+        tx_root.send(root(1)).unwrap();
+        assert!(a.await.unwrap());
+        assert!(b.await.unwrap());
+        assert_eq!(*log.lock(), vec!["b", "a"]);
+        drained(&scheduler).await;
+        assert_eq!(*scheduler.rx_pending.borrow(), 0);
+    }
+    #[tokio::test]
+    async fn cancelled_and_replaced() {
+        let (scheduler, tx_root) = blocked();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let old = scheduler.schedule(uri("a.uvl"), Priority::Background, record(&log, "old"));
+        let a = scheduler.schedule(uri("a.uvl"), Priority::Background, record(&log, "a"));
+        let b = scheduler.schedule(uri("b.uvl"), Priority::Background, record(&log, "b"));
+        assert!(!old.await.unwrap());
+        assert_eq!(*scheduler.rx_pending.borrow(), 2);
+        scheduler.cancel_if(|queued| queued == &uri("b.uvl"));
+        assert!(!b.await.unwrap());
+        assert_eq!(*scheduler.rx_pending.borrow(), 1);
+        tx_root.send(root(1)).unwrap();
+        drained(&scheduler).await;
+        assert!(a.await.unwrap());
+        assert_eq!(*log.lock(), vec!["a"]);
+    }
+    #[tokio::test]
+    async fn focus_loaded_skips_background() {
+        let (scheduler, tx_root) = blocked();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let a = scheduler.schedule(uri("a.uvl"), Priority::Background, record(&log, "a"));
+        scheduler.focus_loaded().await.unwrap();
+        let b = scheduler.schedule(uri("b.uvl"), Priority::Background, record(&log, "b"));
+        scheduler.promote(&uri("b.uvl"));
+        let loaded = spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.focus_loaded().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!loaded.is_finished());
+        tx_root.send(root(1)).unwrap();
+        loaded.await.unwrap().unwrap();
+        assert_eq!(log.lock()[0], "b");
+        assert!(b.await.unwrap());
+        assert!(a.await.unwrap());
+    }
+    #[tokio::test]
+    async fn focus_follows_owned_documents() {
+        let (scheduler, tx_root) = blocked();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let c = scheduler.schedule(uri("c.uvl"), Priority::Background, record(&log, "c"));
+        let b = scheduler.schedule(uri("b.uvl"), Priority::Background, record(&log, "b"));
+        scheduler.promote_imports(&doc("b.uvl", "imports\n    c\n"));
+        scheduler.promote_imports(&doc("a.uvl", "imports\n    b\n"));
+        assert!(!scheduler.is_focus(FileID::from_uri(&uri("b.uvl"))));
+        //opening a pulls in its transitive imports
+        scheduler.add_focus(FileID::from_uri(&uri("a.uvl")));
+        for name in ["a.uvl", "b.uvl", "c.uvl"] {
+            assert!(scheduler.is_focus(FileID::from_uri(&uri(name))));
+        }
+        tx_root.send(root(1)).unwrap();
+        assert!(c.await.unwrap());
+        assert!(b.await.unwrap());
+        assert_eq!(log.lock().len(), 2);
+        //c stays as long as the owned b imports it
+        scheduler.add_focus(FileID::from_uri(&uri("b.uvl")));
+        scheduler.remove_focus(FileID::from_uri(&uri("a.uvl")));
+        assert!(!scheduler.is_focus(FileID::from_uri(&uri("a.uvl"))));
+        assert!(scheduler.is_focus(FileID::from_uri(&uri("c.uvl"))));
+        scheduler.remove_focus(FileID::from_uri(&uri("b.uvl")));
+        assert!(!scheduler.is_focus(FileID::from_uri(&uri("c.uvl"))));
+    }
+}
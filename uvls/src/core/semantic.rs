@@ -161,6 +161,7 @@ impl RootGraph {
     pub fn new(
         files: &HashMap<FileID, Arc<AstDocument>>,
         configs: &HashMap<FileID, Arc<ConfigDocument>>,
+        folders: &[Vec<Ustr>],
         revision: u64,
         old: &Cache,
         err: &mut ErrorsAcc,
@@ -195,7 +196,13 @@ impl RootGraph {
                 if !file_paths.insert(file.path.as_slice()) {
                     if let Some(ns) = file.namespace() {
                         if err.errors.contains_key(&file.id) {
-                            err.span(ns.range(), file.id, 100, "namespace already defined");
+                            err.span(
+                                ns.range(),
+                                file.id,
+                                100,
+                                ErrorType::NamespaceConflict,
+                                "namespace already defined",
+                            );
                         }
                     }
                 }
@@ -203,7 +210,7 @@ impl RootGraph {
         }
         Self {
             cancel: CancellationToken::new(),
-            cache: Cache::new(old, files, configs, &dirty, revision, err),
+            cache: Cache::new(old, files, configs, folders, &dirty, revision, err),
             revision,
             files: files.clone(),
             configs: configs.clone(),
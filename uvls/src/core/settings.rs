@@ -0,0 +1,127 @@
+use lazy_static::lazy_static;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::watch;
+//Server settings, they are read from initializationOptions and replaced on
+//workspace/didChangeConfiguration. Every component reads the latest value or
+//subscribes to changes, missing keys keep their default.
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackendKind {
+    //the embedded solver for Boolean models, the solver process otherwise
+    Auto,
+    //always the solver process
+    Process,
+    //always the embedded solver, Boolean models only
+    Embedded,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SmtSettings {
+    //run the background analysis (dead features, void model, configs)
+    pub enabled: bool,
+    pub backend: BackendKind,
+    //solver binary and its arguments, it has to read SMT-LIB2 from stdin
+    pub solver_path: String,
+    pub solver_args: Vec<String>,
+    //SMT-LIB logic set before the model, solvers like yices-smt2 need one
+    pub logic: Option<String>,
+    //limit for a single check in milliseconds, 0 means no limit
+    pub timeout: u64,
+    //solver specific resource limit for a single query, 0 means no limit
+    pub rlimit: u64,
+}
+impl Default for SmtSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            backend: BackendKind::Auto,
+            solver_path: "z3".into(),
+            solver_args: vec!["-in".into(), "-smt2".into()],
+            logic: None,
+            timeout: 10000,
+            rlimit: 0,
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WebSettings {
+    pub enabled: bool,
+    //fixed port, otherwise a free port between 3000 and 6000 is used
+    pub port: Option<u16>,
+}
+impl Default for WebSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            port: None,
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    pub smt: SmtSettings,
+    pub web: WebSettings,
+    //delay in milliseconds before changes are linked
+    pub debounce: u64,
+    //globs of files the workspace is limited to, all files if empty
+    pub include: Vec<String>,
+    //globs of paths skipped by the workspace scan and the file watchers
+    pub exclude: Vec<String>,
+    //honor .gitignore and .ignore files
+    pub use_ignore_files: bool,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            smt: SmtSettings::default(),
+            web: WebSettings::default(),
+            debounce: 100,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            use_ignore_files: true,
+        }
+    }
+}
+impl Settings {
+    //Clients either send the settings directly or nested in a uvls section
+    pub fn from_value(value: &serde_json::Value) -> Option<Self> {
+        let value = value.get("uvls").unwrap_or(value);
+        if value.is_null() {
+            return None;
+        }
+        match serde_json::from_value(value.clone()) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                info!("invalid settings {e}");
+                None
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref SETTINGS: (watch::Sender<Arc<Settings>>, watch::Receiver<Arc<Settings>>) =
+        watch::channel(Arc::new(Settings::default()));
+}
+pub fn settings() -> Arc<Settings> {
+    SETTINGS.1.borrow().clone()
+}
+//fires when the settings changed
+pub fn watch_settings() -> watch::Receiver<Arc<Settings>> {
+    SETTINGS.1.clone()
+}
+//Replace the settings, returns the old value if anything changed
+pub fn update_settings(new: Settings) -> Option<Arc<Settings>> {
+    let old = settings();
+    if *old == new {
+        return None;
+    }
+    info!("update settings {new:?}");
+    SETTINGS.0.send_replace(Arc::new(new));
+    Some(old)
+}
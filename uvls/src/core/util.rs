@@ -1,13 +1,14 @@
 use crate::core::query::Queries;
 use futures::Future;
 use lazy_static::lazy_static;
-use ropey::Rope;
+use ropey::{Rope, RopeSlice};
 use std::error;
 use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
 use tokio::select;
 use tokio_util::sync::CancellationToken;
-use tower_lsp::lsp_types::{Position, Range, Url};
-use tree_sitter::{Language, Node};
+use tower_lsp::lsp_types::{Position, PositionEncodingKind, Range, Url};
+use tree_sitter::{Language, Node, Tree};
 
 pub struct ParseConstants {
     pub queries: Queries,
@@ -42,23 +43,88 @@ pub fn node_source(source: &Rope) -> impl tree_sitter::TextProvider<'_> {
 pub fn node_range(node: Node, rope: &Rope) -> Range {
     lsp_range(node.byte_range(), rope).unwrap()
 }
-//Treesitter is using bytes as offsets while lsp uses utf16 codepoints for some insane reason
+//Unit of Position.character, it is negotiated once in initialize and used by every
+//conversion between tree-sitter bytes and lsp positions. Clients default to utf16.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8 = 0,
+    Utf16 = 1,
+    Utf32 = 2,
+}
+static POSITION_ENCODING: AtomicU8 = AtomicU8::new(PositionEncoding::Utf16 as u8);
+impl PositionEncoding {
+    pub fn get() -> Self {
+        match POSITION_ENCODING.load(Ordering::Relaxed) {
+            0 => Self::Utf8,
+            2 => Self::Utf32,
+            _ => Self::Utf16,
+        }
+    }
+    pub fn set(self) {
+        POSITION_ENCODING.store(self as u8, Ordering::Relaxed);
+    }
+    //Take the first encoding offered by the client, utf8 and utf32 avoid the utf16 conversion
+    pub fn negotiate(offered: Option<&[PositionEncodingKind]>) -> Self {
+        offered
+            .unwrap_or_default()
+            .iter()
+            .find_map(|kind| match kind.as_str() {
+                "utf-8" => Some(Self::Utf8),
+                "utf-16" => Some(Self::Utf16),
+                "utf-32" => Some(Self::Utf32),
+                _ => None,
+            })
+            .unwrap_or(Self::Utf16)
+    }
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            Self::Utf8 => PositionEncodingKind::UTF8,
+            Self::Utf16 => PositionEncodingKind::UTF16,
+            Self::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+    //length of a line in code units
+    pub fn len(self, line: RopeSlice) -> usize {
+        match self {
+            Self::Utf8 => line.len_bytes(),
+            Self::Utf16 => line.len_utf16_cu(),
+            Self::Utf32 => line.len_chars(),
+        }
+    }
+    //code unit column to char column inside line
+    pub fn to_char(self, line: RopeSlice, col: usize) -> Option<usize> {
+        match self {
+            Self::Utf8 => line.try_byte_to_char(col).ok(),
+            Self::Utf16 => line.try_utf16_cu_to_char(col).ok(),
+            Self::Utf32 => (col <= line.len_chars()).then_some(col),
+        }
+    }
+    //char column to code unit column inside line
+    pub fn to_unit(self, line: RopeSlice, col: usize) -> usize {
+        match self {
+            Self::Utf8 => line.char_to_byte(col),
+            Self::Utf16 => line.char_to_utf16_cu(col),
+            Self::Utf32 => col,
+        }
+    }
+}
+//Treesitter is using bytes as offsets while lsp uses the negotiated encoding
 //So we have to convert
 pub fn lsp_position(byte: usize, source: &Rope) -> Option<Position> {
+    let encoding = PositionEncoding::get();
     if byte == source.len_bytes() {
         Some(Position {
             line: (source.len_lines() - 1) as u32,
-            character: source.line(source.len_lines() - 1).len_utf16_cu() as u32,
+            character: encoding.len(source.line(source.len_lines() - 1)) as u32,
         })
     } else if byte > source.len_bytes() {
         None
     } else {
         let line = source.byte_to_line(byte);
         let col = source.byte_to_char(byte) - source.line_to_char(line);
-        let col16 = source.line(line).char_to_utf16_cu(col);
         Some(Position {
             line: line as u32,
-            character: col16 as u32,
+            character: encoding.to_unit(source.line(line), col) as u32,
         })
     }
 }
@@ -68,7 +134,7 @@ pub fn lsp_range(span: std::ops::Range<usize>, source: &Rope) -> Option<Range> {
 }
 pub fn char_offset(pos: &Position, source: &Rope) -> usize {
     if let Some(line) = source.get_line(pos.line as usize) {
-        if let Ok(end) = line.try_utf16_cu_to_char(pos.character as usize) {
+        if let Some(end) = PositionEncoding::get().to_char(line, pos.character as usize) {
             source.line_to_char(pos.line as usize) + end
         } else {
             source.line_to_char(pos.line as usize) + line.len_chars()
@@ -91,6 +157,14 @@ pub fn containing_blk(mut node: Node) -> Option<Node> {
 pub fn header_kind(node: Node) -> &str {
     node.child_by_field_name("header").unwrap().kind()
 }
+pub fn top_level_section<'a>(tree: &'a Tree, kind: &str) -> Option<Node<'a>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let section = root
+        .named_children(&mut cursor)
+        .find(|n| n.kind() == "blk" && header_kind(*n) == kind);
+    section
+}
 
 pub type Result<T> = std::result::Result<T, Box<dyn error::Error + Send + Sync>>;
 
@@ -137,3 +211,84 @@ pub fn create_new_uvl(old_uri: String, path: String) -> Option<Url> {
 
     None
 }
+pub const KEYWORDS: &[&str] = &[
+    "namespace",
+    "include",
+    "imports",
+    "features",
+    "constraints",
+    "constraint",
+    "cardinality",
+    "as",
+    "or",
+    "alternative",
+    "mandatory",
+    "optional",
+    "true",
+    "false",
+    "Boolean",
+    "Real",
+    "Integer",
+    "String",
+    "Arithmetic",
+    "Type",
+];
+//Names that are no plain identifiers or collide with a keyword have to be quoted
+pub fn needs_quotes(name: &str) -> bool {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .map(|c| c == '_' || c.is_ascii_alphabetic())
+        .unwrap_or(false)
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric());
+    !valid || KEYWORDS.contains(&name)
+}
+pub fn quoted(name: &str) -> String {
+    if needs_quotes(name) {
+        format!("\"{}\"", name)
+    } else {
+        name.to_string()
+    }
+}
+//Leading whitespace of a line, copied as is so tabs are kept
+pub fn line_indent(source: &Rope, row: usize) -> String {
+    source
+        .get_line(row)
+        .map(|l| l.chars().take_while(|c| *c == ' ' || *c == '\t').collect())
+        .unwrap_or_default()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    //'a' and 'b' take one unit everywhere, 'ä' two bytes and '𝄞' four bytes or a utf16 pair
+    const LINE: &str = "aä𝄞b\n";
+    fn columns(encoding: PositionEncoding, units: &[usize]) {
+        let rope = Rope::from_str(LINE);
+        let line = rope.line(0);
+        for (char_col, unit_col) in units.iter().enumerate() {
+            assert_eq!(encoding.to_char(line, *unit_col), Some(char_col));
+            assert_eq!(encoding.to_unit(line, char_col), *unit_col);
+        }
+        assert_eq!(encoding.len(line), units[units.len() - 1] + 1);
+    }
+    #[test]
+    fn utf8_columns() {
+        columns(PositionEncoding::Utf8, &[0, 1, 3, 7, 8]);
+    }
+    #[test]
+    fn utf16_columns() {
+        columns(PositionEncoding::Utf16, &[0, 1, 2, 4, 5]);
+    }
+    #[test]
+    fn utf32_columns() {
+        columns(PositionEncoding::Utf32, &[0, 1, 2, 3, 4]);
+    }
+    #[test]
+    fn column_past_line() {
+        let rope = Rope::from_str(LINE);
+        let line = rope.line(0);
+        assert_eq!(PositionEncoding::Utf8.to_char(line, 100), None);
+        assert_eq!(PositionEncoding::Utf16.to_char(line, 100), None);
+        assert_eq!(PositionEncoding::Utf32.to_char(line, 100), None);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::core::*;
+use hashbrown::HashMap;
+use ignore::gitignore::Gitignore;
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::{Match, WalkBuilder};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+//Which files of a workspace folder are loaded. The scan and the file watchers honor
+//.gitignore and .ignore files and the include and exclude globs from the settings.
+
+//Include globs whitelist, exclude globs are added as ignore globs
+fn overrides(root: &Path, settings: &Settings) -> Override {
+    let mut builder = OverrideBuilder::new(root);
+    for glob in settings.include.iter() {
+        if let Err(e) = builder.add(glob) {
+            info!("invalid include glob {e}");
+        }
+    }
+    for glob in settings.exclude.iter() {
+        if let Err(e) = builder.add(&format!("!{glob}")) {
+            info!("invalid exclude glob {e}");
+        }
+    }
+    builder.build().unwrap_or_else(|_| Override::empty())
+}
+fn is_uvl(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e == std::ffi::OsStr::new("uvl"))
+        .unwrap_or(false)
+}
+//All uvl files under root
+pub fn scan(root: &Path, settings: &Settings) -> Vec<PathBuf> {
+    WalkBuilder::new(root)
+        .standard_filters(settings.use_ignore_files)
+        .require_git(false)
+        .overrides(overrides(root, settings))
+        .build()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .map(|e| e.into_path())
+        .filter(|path| is_uvl(path))
+        .collect()
+}
+//Ignore files of a folder with their modification time, so watcher events don't parse them again
+type FolderIgnores = Vec<(Option<SystemTime>, Arc<Gitignore>)>;
+lazy_static! {
+    static ref IGNORE_CACHE: Mutex<HashMap<PathBuf, FolderIgnores>> = Mutex::new(HashMap::new());
+}
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|m| m.modified()).ok()
+}
+//.ignore and .gitignore of dir in the order they are checked
+fn folder_ignores(dir: &Path) -> Vec<Arc<Gitignore>> {
+    let files: Vec<PathBuf> = [".ignore", ".gitignore"]
+        .iter()
+        .map(|name| dir.join(name))
+        .filter(|file| file.is_file())
+        .collect();
+    let stamps: Vec<Option<SystemTime>> = files.iter().map(|file| modified(file)).collect();
+    let mut cache = IGNORE_CACHE.lock();
+    match cache.get(dir) {
+        Some(cached)
+            if cached
+                .iter()
+                .map(|(stamp, _)| *stamp)
+                .eq(stamps.iter().cloned()) =>
+        {
+            cached.iter().map(|(_, ignore)| ignore.clone()).collect()
+        }
+        _ => {
+            let parsed: FolderIgnores = files
+                .into_iter()
+                .zip(stamps)
+                .map(|(file, stamp)| (stamp, Arc::new(Gitignore::new(file).0)))
+                .collect();
+            let ignores = parsed.iter().map(|(_, ignore)| ignore.clone()).collect();
+            cache.insert(dir.to_path_buf(), parsed);
+            ignores
+        }
+    }
+}
+//Check a single file from a watcher event against the rules of the folder containing it
+pub fn is_ignored(path: &Path, folders: &[PathBuf], settings: &Settings) -> bool {
+    let Some(root) = folders
+        .iter()
+        .filter(|f| path.starts_with(f))
+        .max_by_key(|f| f.components().count())
+    else {
+        return false;
+    };
+    match overrides(root, settings).matched(path, false) {
+        Match::Ignore(_) => return true,
+        Match::Whitelist(_) => return false,
+        Match::None => {}
+    }
+    if !settings.use_ignore_files {
+        return false;
+    }
+    //hidden files and folders are skipped by the scan as well
+    let hidden = path
+        .strip_prefix(root)
+        .map(|rel| {
+            rel.components()
+                .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        })
+        .unwrap_or(false);
+    if hidden {
+        return true;
+    }
+    //the deepest ignore file decides, like git does
+    for dir in path.ancestors().skip(1) {
+        for ignore in folder_ignores(dir) {
+            match ignore.matched_path_or_any_parents(path, false) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        if dir == root {
+            break;
+        }
+    }
+    false
+}
+//Globs of uvl files the client watches, relative to a workspace folder. Exclude globs and
+//ignore files can't be expressed as watchers and are checked by is_ignored.
+pub fn watch_globs(settings: &Settings) -> Vec<String> {
+    if settings.include.is_empty() {
+        return vec!["**/*.uvl".into()];
+    }
+    settings
+        .include
+        .iter()
+        .map(|glob| {
+            if glob.ends_with(".uvl") {
+                glob.clone()
+            } else {
+                format!(
+                    "{}/**/*.uvl",
+                    glob.trim_end_matches("/**").trim_end_matches('/')
+                )
+            }
+        })
+        .collect()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn hidden_ignored() {
+        let folders = vec![PathBuf::from("/ws/.project")];
+        let settings = Settings::default();
+        assert!(!is_ignored(
+            Path::new("/ws/.project/a.uvl"),
+            &folders,
+            &settings
+        ));
+        assert!(is_ignored(
+            Path::new("/ws/.project/.git/a.uvl"),
+            &folders,
+            &settings
+        ));
+        assert!(is_ignored(
+            Path::new("/ws/.project/.a.uvl"),
+            &folders,
+            &settings
+        ));
+        let settings = Settings {
+            use_ignore_files: false,
+            ..Settings::default()
+        };
+        assert!(!is_ignored(
+            Path::new("/ws/.project/.a.uvl"),
+            &folders,
+            &settings
+        ));
+    }
+    #[test]
+    fn include_watch_globs() {
+        assert_eq!(watch_globs(&Settings::default()), vec!["**/*.uvl"]);
+        let settings = Settings {
+            include: vec!["models/**".into(), "lib/".into(), "**/core.uvl".into()],
+            ..Settings::default()
+        };
+        assert_eq!(
+            watch_globs(&settings),
+            vec!["models/**/*.uvl", "lib/**/*.uvl", "**/core.uvl"]
+        );
+    }
+}
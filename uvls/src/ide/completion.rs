@@ -30,7 +30,7 @@ static W_TYPE: f32 = 2.;
 static W_LEN: f32 = 3.0;
 static AVG_WEIGHT_THRESHOLD: f32 = 0.2; //Unused
 static MIN_WEIGHT: f32 = 0.1;
-struct TopN<V> {
+pub(crate) struct TopN<V> {
     buffer: min_max_heap::MinMaxHeap<V>,
     max: usize,
 }
@@ -38,13 +38,13 @@ impl<V> TopN<V>
 where
     V: Ord,
 {
-    fn new(max: usize) -> Self {
+    pub(crate) fn new(max: usize) -> Self {
         Self {
             buffer: min_max_heap::MinMaxHeap::new(),
             max,
         }
     }
-    fn push(&mut self, value: V) {
+    pub(crate) fn push(&mut self, value: V) {
         if self.max == 0 {
             return;
         }
@@ -58,7 +58,7 @@ where
             self.buffer.push(value);
         }
     }
-    fn into_sorted_vec(self) -> Vec<V> {
+    pub(crate) fn into_sorted_vec(self) -> Vec<V> {
         self.buffer.into_vec_desc()
     }
     fn merge(&mut self, mut other: TopN<V>) {
@@ -209,13 +209,23 @@ pub fn estimate_expr(node: Node, pos: &Position, source: &Rope) -> CompletionEnv
             cursor.goto_first_child();
             let mut arg_offset = -1;
             let mut args = Vec::new();
+            //the cursor is one char back in code units of the negotiated encoding
+            let encoding = PositionEncoding::get();
+            let line = source.line(pos.line as usize);
+            let prev = Position {
+                character: encoding.to_unit(
+                    line,
+                    encoding
+                        .to_char(line, pos.character as usize)
+                        .unwrap_or(line.len_chars())
+                        .saturating_sub(1),
+                ) as u32,
+                line: pos.line,
+            };
             loop {
                 if contains(
                     lsp_range(cursor.node().byte_range(), source).unwrap(),
-                    &Position {
-                        character: pos.character - 1,
-                        line: pos.line,
-                    },
+                    &prev,
                 ) {
                     arg_offset = args.len() as isize;
                 }
@@ -276,7 +286,9 @@ fn position_to_node<'a>(
     pos: &Position,
 ) -> (CompletionOffset, Node<'a>) {
     let line = source.line(pos.line as usize);
-    let rel_char = line.utf16_cu_to_char(pos.character as usize);
+    let rel_char = PositionEncoding::get()
+        .to_char(line, pos.character as usize)
+        .unwrap_or(line.len_chars());
     if rel_char == 0 {
         (CompletionOffset::SameLine, tree.root_node())
     } else {
@@ -814,7 +826,7 @@ fn resolve_name(snapshot: &Snapshot, origin: FileID, _source: &Rope, child: &str
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum CompletionKind {
+pub(crate) enum CompletionKind {
     Feature,
     Namespace,
     Import,
@@ -1009,7 +1021,7 @@ fn make_relativ_path(path: &[CompactString], origin: &[CompactString]) -> Option
     }
 }
 //weight function
-fn completion_weight(
+pub(crate) fn completion_weight(
     query: &str,
     to_match: &str,
     _depth: u32,
@@ -0,0 +1,534 @@
+use crate::core::*;
+use crate::ide::completion::{find_section, Section};
+use itertools::Itertools;
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::{Node, Point, Tree};
+//Pretty printer for UVL driven by the green tree. Every blk is printed as a single line
+//followed by its children, comments are reattached by their position in the source.
+//Files with syntax errors are never formatted because the green tree may not cover the
+//whole source.
+
+//Top level sections are printed in this order
+fn section_rank(blk: Node) -> usize {
+    match header_kind(blk) {
+        "namespace" | "incomplete_namespace" => 0,
+        "include" => 1,
+        "imports" => 2,
+        "features" => 3,
+        "constraints" => 4,
+        _ => 5,
+    }
+}
+fn named_children<'a>(node: Node<'a>) -> impl Iterator<Item = Node<'a>> {
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor)
+        .filter(|n| n.kind() != "comment")
+        .collect_vec()
+        .into_iter()
+}
+fn collect_comments<'a>(node: Node<'a>, out: &mut Vec<Node<'a>>) {
+    if node.kind() == "comment" {
+        out.push(node);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_comments(child, out);
+    }
+}
+fn top_level(tree: &Tree) -> Vec<Node<'_>> {
+    named_children(tree.root_node())
+        .filter(|n| n.kind() == "blk")
+        .collect()
+}
+//A comment belongs to a blk if it starts inside of it or trails its last line,
+//the blk ends before a trailing comment at the end of the file
+fn owns(source: &Rope, blk: Node, comment: Node) -> bool {
+    let end = blk.start_byte() + source.slice_raw(blk.byte_range()).trim_end().len();
+    blk.byte_range().contains(&comment.start_byte())
+        || (comment.start_byte() >= end && comment.start_position().row == source.byte_to_line(end))
+}
+
+struct Printer<'a> {
+    source: &'a Rope,
+    indent: &'a str,
+    comments: Vec<Node<'a>>,
+    next: usize,
+    last_row: Option<usize>,
+    out: String,
+}
+impl<'a> Printer<'a> {
+    fn new(source: &'a Rope, indent: &'a str, comments: Vec<Node<'a>>) -> Self {
+        Self {
+            source,
+            indent,
+            comments,
+            next: 0,
+            last_row: None,
+            out: String::new(),
+        }
+    }
+    fn text(&self, node: Node) -> String {
+        self.source.slice_raw(node.byte_range()).into_owned()
+    }
+    //Emits a line, a single blank line is kept when the source had at least one
+    fn line(&mut self, depth: usize, text: &str, rows: (usize, usize)) {
+        if self.last_row.map(|last| rows.0 > last + 1).unwrap_or(false) {
+            self.out.push('\n');
+        }
+        for _ in 0..depth {
+            self.out.push_str(self.indent);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+        self.last_row = Some(rows.1);
+    }
+    fn comments_before(&mut self, depth: usize, byte: usize) {
+        while let Some(c) = self.comments.get(self.next).cloned() {
+            if c.start_byte() >= byte {
+                break;
+            }
+            let text = self.text(c);
+            self.line(
+                depth,
+                text.trim_end(),
+                (c.start_position().row, c.end_position().row),
+            );
+            self.next += 1;
+        }
+    }
+    //Comments on the same line as the previous content stay there
+    fn trailing_comments(&mut self, row: usize) {
+        while let Some(c) = self.comments.get(self.next).cloned() {
+            if c.start_position().row != row {
+                break;
+            }
+            self.out.pop();
+            self.out.push(' ');
+            let text = self.text(c);
+            self.out.push_str(text.trim_end());
+            self.out.push('\n');
+            self.last_row = Some(c.end_position().row);
+            self.next += 1;
+        }
+    }
+    fn blk(&mut self, node: Node, depth: usize) {
+        self.comments_before(depth, node.start_byte());
+        let header = node.child_by_field_name("header").unwrap();
+        let cardinality = node.child_by_field_name("cardinality");
+        let attribs = node.child_by_field_name("attribs");
+        let end = attribs.or(cardinality).unwrap_or(header).end_byte();
+        let end_row = self.source.byte_to_line(end);
+        let inline_comments = self.comments[self.next..]
+            .iter()
+            .take_while(|c| c.start_byte() < end)
+            .count();
+        let text = if inline_comments > 0 {
+            //Keep lines with comments inside the header or attributes as they are
+            self.next += inline_comments;
+            self.source
+                .slice_raw(node.start_byte()..end)
+                .trim()
+                .to_string()
+        } else {
+            let mut text = self.header(header);
+            if let Some(cardinality) = cardinality {
+                text.push_str(" cardinality ");
+                text.push_str(&self.cardinality(cardinality));
+            }
+            if let Some(attribs) = attribs {
+                text.push(' ');
+                text.push_str(&self.attributes(attribs));
+            }
+            text
+        };
+        self.line(depth, &text, (node.start_position().row, end_row));
+        self.trailing_comments(end_row);
+        for child in named_children(node).filter(|n| n.kind() == "blk") {
+            self.blk(child, depth + 1);
+        }
+        self.comments_before(depth + 1, node.end_byte());
+    }
+    fn header(&self, node: Node) -> String {
+        match node.kind() {
+            "name" => self.name(node),
+            "typed_feature" => format!(
+                "{} {}",
+                self.text(node.child_by_field_name("type").unwrap()),
+                self.name(node.child_by_field_name("name").unwrap())
+            ),
+            "ref" => {
+                let path = self.path(node.child_by_field_name("path").unwrap());
+                match node.child_by_field_name("alias") {
+                    Some(alias) => format!("{path} as {}", self.name(alias)),
+                    None => path,
+                }
+            }
+            "namespace" => format!(
+                "namespace {}",
+                self.path(node.child_by_field_name("name").unwrap())
+            ),
+            "cardinality" => self.cardinality(node),
+            "constraint" => self.expr(node),
+            "lang_lvl" => self
+                .text(node)
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect(),
+            _ => self.text(node).trim().to_string(),
+        }
+    }
+    fn name(&self, node: Node) -> String {
+        let text = self.text(node);
+        if let Some(inner) = text.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            if !needs_quotes(inner) {
+                return inner.to_string();
+            }
+        }
+        text
+    }
+    fn path(&self, node: Node) -> String {
+        named_children(node).map(|n| self.name(n)).join(".")
+    }
+    fn cardinality(&self, node: Node) -> String {
+        let end = node
+            .child_by_field_name("end")
+            .map(|n| self.text(n))
+            .unwrap_or("*".into());
+        match node.child_by_field_name("begin") {
+            Some(begin) => format!("[{}..{end}]", self.text(begin)),
+            None => format!("[{end}]"),
+        }
+    }
+    fn attributes(&self, node: Node) -> String {
+        format!(
+            "{{{}}}",
+            named_children(node).map(|n| self.attribute(n)).join(", ")
+        )
+    }
+    fn attribute(&self, node: Node) -> String {
+        match node.kind() {
+            "attribute_value" => {
+                let name = self.name(node.child_by_field_name("name").unwrap());
+                match node.child_by_field_name("value") {
+                    Some(value) => format!("{name} {}", self.expr(value)),
+                    None => name,
+                }
+            }
+            "attribute_constraint" => format!(
+                "constraint {}",
+                named_children(node)
+                    .next()
+                    .map(|n| self.expr(n))
+                    .unwrap_or_default()
+            ),
+            "attribute_constraints" => format!(
+                "constraints [{}]",
+                named_children(node).map(|n| self.expr(n)).join(", ")
+            ),
+            _ => self.text(node),
+        }
+    }
+    fn expr(&self, node: Node) -> String {
+        match node.kind() {
+            "constraint" | "attrib_expr" => named_children(node)
+                .next()
+                .map(|n| self.expr(n))
+                .unwrap_or_else(|| self.text(node)),
+            "path" => self.path(node),
+            "name" => self.name(node),
+            "nested_expr" => format!(
+                "({})",
+                named_children(node)
+                    .next()
+                    .map(|n| self.expr(n))
+                    .unwrap_or_default()
+            ),
+            "unary_expr" => format!("!{}", self.expr(node.child_by_field_name("lhs").unwrap())),
+            "binary_expr" => format!(
+                "{} {} {}",
+                self.expr(node.child_by_field_name("lhs").unwrap()),
+                self.text(node.child_by_field_name("op").unwrap()),
+                self.expr(node.child_by_field_name("rhs").unwrap())
+            ),
+            "function" => {
+                let mut cursor = node.walk();
+                format!(
+                    "{}({})",
+                    self.name(node.child_by_field_name("op").unwrap()),
+                    node.children_by_field_name("arg", &mut cursor)
+                        .map(|n| self.expr(n))
+                        .join(", ")
+                )
+            }
+            "vector" => format!(
+                "[{}]",
+                named_children(node).map(|n| self.expr(n)).join(", ")
+            ),
+            "attributes" => self.attributes(node),
+            _ => self.text(node),
+        }
+    }
+}
+fn indent_unit(options: &FormattingOptions) -> String {
+    if options.insert_spaces {
+        " ".repeat(options.tab_size as usize)
+    } else {
+        "\t".into()
+    }
+}
+fn uvl_source(draft: &Draft) -> Option<(&Rope, &Tree)> {
+    match draft {
+        Draft::UVL { source, tree, .. } if !tree.root_node().has_error() => Some((source, tree)),
+        _ => None,
+    }
+}
+//Formats a whole file, sections are reordered and separated by a blank line
+pub fn format(draft: &Draft, options: &FormattingOptions) -> Option<Vec<TextEdit>> {
+    let (source, tree) = uvl_source(draft)?;
+    let indent = indent_unit(options);
+    let mut comments = Vec::new();
+    collect_comments(tree.root_node(), &mut comments);
+    comments.sort_by_key(|c| c.start_byte());
+    let mut comments = comments.into_iter().peekable();
+    let mut sections = Vec::new();
+    for blk in top_level(tree) {
+        let owned = comments
+            .peeking_take_while(|c| c.start_byte() < blk.start_byte() || owns(source, blk, *c))
+            .collect();
+        let mut printer = Printer::new(source, &indent, owned);
+        printer.blk(blk, 0);
+        sections.push((section_rank(blk), printer.out));
+    }
+    let rest = comments.collect_vec();
+    if !rest.is_empty() {
+        let mut printer = Printer::new(source, &indent, rest);
+        printer.comments_before(0, source.len_bytes());
+        sections.push((usize::MAX, printer.out));
+    }
+    sections.sort_by_key(|(rank, _)| *rank);
+    let text = sections.into_iter().map(|(_, text)| text).join("\n");
+    if *source == *text {
+        return Some(vec![]);
+    }
+    Some(vec![TextEdit {
+        range: lsp_range(0..source.len_bytes(), source)?,
+        new_text: text,
+    }])
+}
+//Formats all top level sections touched by range in place without reordering them
+pub fn format_range(
+    draft: &Draft,
+    range: &Range,
+    options: &FormattingOptions,
+) -> Option<Vec<TextEdit>> {
+    let (source, tree) = uvl_source(draft)?;
+    let indent = indent_unit(options);
+    let start = byte_offset(&range.start, source);
+    let end = byte_offset(&range.end, source);
+    let mut comments = Vec::new();
+    collect_comments(tree.root_node(), &mut comments);
+    comments.sort_by_key(|c| c.start_byte());
+    let mut edits = Vec::new();
+    for blk in top_level(tree)
+        .into_iter()
+        .filter(|blk| blk.start_byte() <= end && blk.end_byte() >= start)
+    {
+        let owned = comments
+            .iter()
+            .filter(|c| owns(source, blk, **c))
+            .cloned()
+            .collect_vec();
+        let mut printer = Printer::new(source, &indent, owned);
+        printer.blk(blk, 0);
+        let blk_end = printer
+            .comments
+            .iter()
+            .map(|c| c.end_byte())
+            .fold(blk.end_byte(), usize::max);
+        let span_start = source.line_to_byte(source.byte_to_line(blk.start_byte()));
+        let span_end = span_start + source.slice_raw(span_start..blk_end).trim_end().len();
+        let new_text = printer.out.trim_end().to_string();
+        if *source.slice_raw(span_start..span_end) != *new_text {
+            edits.push(TextEdit {
+                range: lsp_range(span_start..span_end, source)?,
+                new_text,
+            });
+        }
+    }
+    Some(edits)
+}
+//Indents the line after a newline was typed. The indent is derived from the blk of the
+//closest line above, so a line closing a multiline structure dedents back to its owner.
+//Sections, groups and features in the features section open a new indent level.
+pub fn on_type(
+    draft: &Draft,
+    pos: &Position,
+    options: &FormattingOptions,
+) -> Option<Vec<TextEdit>> {
+    let (source, tree) = match draft {
+        Draft::UVL { source, tree, .. } => (source, tree),
+        _ => return None,
+    };
+    let line = pos.line as usize;
+    let node = (0..line.min(source.len_lines())).rev().find_map(|row| {
+        let text: String = source.line(row).into();
+        if text.trim().is_empty() {
+            return None;
+        }
+        let point = Point {
+            row,
+            column: text.len() - text.trim_start().len(),
+        };
+        tree.root_node()
+            .descendant_for_point_range(point, point)
+            .filter(|node| node.kind() != "comment")
+    })?;
+    let blk = if node.kind() == "blk" {
+        node
+    } else {
+        containing_blk(node)?
+    };
+    let header = blk.child_by_field_name("header")?;
+    let nested = !header.is_missing()
+        && match header.kind() {
+            "group_mode" | "cardinality" | "features" | "imports" | "constraints" | "include" => {
+                true
+            }
+            "name" | "typed_feature" | "ref" => find_section(blk) == Section::Features,
+            _ => false,
+        };
+    let mut indent = line_indent(source, blk.start_position().row);
+    if nested {
+        indent.push_str(&indent_unit(options));
+    }
+    let current = line_indent(source, line);
+    if current == indent {
+        return Some(vec![]);
+    }
+    Some(vec![TextEdit {
+        range: Range {
+            start: Position {
+                line: pos.line,
+                character: 0,
+            },
+            end: Position {
+                line: pos.line,
+                character: current.len() as u32,
+            },
+        },
+        new_text: indent,
+    }])
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::Instant;
+    fn draft(text: &str) -> Draft {
+        let source = Rope::from_str(text);
+        let tree = parse(&source, None);
+        Draft::UVL {
+            source,
+            tree,
+            timestamp: Instant::now(),
+        }
+    }
+    fn options() -> FormattingOptions {
+        FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        }
+    }
+    fn formatted(text: &str) -> String {
+        let edits = format(&draft(text), &options()).unwrap();
+        match edits.as_slice() {
+            [] => text.to_string(),
+            [edit] => edit.new_text.clone(),
+            _ => panic!("expected a single edit"),
+        }
+    }
+    const MESSY: &str = "constraints\n  A=>   B // keep A\nfeatures\n Root {abstract}\n  optional\n      A cardinality [1..3]\n    //before B\n      B\nnamespace Test\n";
+    #[test]
+    fn sections_reordered() {
+        assert_eq!(
+            formatted(MESSY),
+            "namespace Test\n\nfeatures\n    Root {abstract}\n        optional\n            A cardinality [1..3]\n            //before B\n            B\n\nconstraints\n    A => B // keep A\n"
+        );
+    }
+    #[test]
+    fn idempotent() {
+        for text in [
+            MESSY,
+            "features\n\tA\n\t\tor\n\t\t\t\"B\"\n\t\t\t\"C D\"\n",
+            "imports\n  a.b as c\n\n\n\nfeatures\n  Root\n//trailing\n",
+        ] {
+            let once = formatted(text);
+            assert_eq!(formatted(&once), once);
+            assert!(format(&draft(&once), &options()).unwrap().is_empty());
+        }
+    }
+    #[test]
+    fn comments_kept() {
+        let text = "//header\nfeatures\n  A // on A\n    /* before or */\n    or\n      B\n//end\n";
+        let out = formatted(text);
+        for comment in ["//header", "// on A", "/* before or */", "//end"] {
+            assert!(out.contains(comment), "{comment} missing in {out}");
+        }
+        assert!(out.contains("A // on A\n"));
+    }
+    #[test]
+    fn syntax_error_not_formatted() {
+        assert!(format(&draft("features\n  A {\n"), &options()).is_none());
+    }
+    //Indent the editor should use on line after a newline was typed there
+    fn indent_after(text: &str, line: u32) -> String {
+        let draft = draft(text);
+        let edits = on_type(&draft, &Position { line, character: 0 }, &options()).unwrap();
+        match edits.as_slice() {
+            [] => line_indent(&Rope::from_str(text), line as usize),
+            [edit] => {
+                assert_eq!(edit.range.start, Position { line, character: 0 });
+                edit.new_text.clone()
+            }
+            _ => panic!("expected a single edit"),
+        }
+    }
+    #[test]
+    fn on_type_nested() {
+        assert_eq!(indent_after("features\n\n", 1), "    ");
+        assert_eq!(indent_after("features\n    Root\n    \n", 2), "        ");
+        assert_eq!(
+            indent_after("features\n    Root\n        optional\n\n", 3),
+            "            "
+        );
+        //tabs of the current line are replaced
+        let text = "features\n    Root\n        optional\n            A\n\t\n";
+        assert_eq!(indent_after(text, 4), "                ");
+        assert_eq!(indent_after("constraints\n\n", 1), "    ");
+    }
+    #[test]
+    fn on_type_same_level() {
+        assert_eq!(indent_after("constraints\n    A => B\n    \n", 2), "    ");
+    }
+    #[test]
+    fn on_type_dedent_closing_line() {
+        let text = "constraints\n    A => (B &\n        C)\n        \n";
+        assert_eq!(indent_after(text, 3), "    ");
+    }
+    #[test]
+    fn on_type_unchanged() {
+        let text = "features\n    Root\n        \n";
+        assert!(on_type(
+            &draft(text),
+            &Position {
+                line: 2,
+                character: 8
+            },
+            &options()
+        )
+        .unwrap()
+        .is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::core::*;
+use crate::ide::location::find_definitions;
+use crate::smt::{fixed_value, FixedState, SMTValueState};
+use itertools::Itertools;
+use std::fmt::Write;
+use tower_lsp::lsp_types::*;
+use ustr::Ustr;
+//Hover cards are rendered as markdown from the linked RootGraph. The hovered text object is
+//resolved the same way as goto definition, so references inside constraints and imported
+//symbols show the card of their target.
+
+fn file_name(file: &AstDocument) -> &str {
+    file.uri.path().rsplit('/').next().unwrap_or("")
+}
+//Namespace of a file, files without a namespace use their file name
+fn qualifier(file: &AstDocument) -> Ustr {
+    file.namespace()
+        .map(|ns| ns.to_string().as_str().into())
+        .or_else(|| file.path.last().cloned())
+        .unwrap_or_else(|| "".into())
+}
+fn qualified_path(file: &AstDocument, sym: Symbol) -> String {
+    let mut names = file.prefix(sym);
+    if matches!(sym, Symbol::Attribute(..)) {
+        names.extend(file.name(file.scope(sym)));
+    }
+    names.push(qualifier(file));
+    names.iter().rev().join(".")
+}
+fn format_value(value: &ast::Value) -> String {
+    match value {
+        ast::Value::Void => "".into(),
+        ast::Value::Number(n) => format!("{n}"),
+        ast::Value::String(s) => format!("'{s}'"),
+        ast::Value::Bool(b) => format!("{b}"),
+        ast::Value::Vector => "[...]".into(),
+        ast::Value::Attributes => "{...}".into(),
+    }
+}
+fn format_cardinality(cardinality: &Cardinality) -> Option<String> {
+    match cardinality {
+        Cardinality::Range(min, max) if min == max => Some(format!("[{min}]")),
+        Cardinality::Range(min, max) => Some(format!("[{min}..{max}]")),
+        Cardinality::Fixed => None,
+    }
+}
+fn format_group_mode(mode: &GroupMode) -> String {
+    match mode {
+        GroupMode::Or => "or".into(),
+        GroupMode::Alternative => "alternative".into(),
+        GroupMode::Optional => "optional".into(),
+        GroupMode::Mandatory => "mandatory".into(),
+        GroupMode::Cardinality(c) => format_cardinality(c).unwrap_or("[*]".into()),
+    }
+}
+
+fn feature_card(file: &AstDocument, sym: Symbol, id: usize, fixed: &FixedState) -> String {
+    let mut out = String::new();
+    let feature = file.get_feature(id).unwrap();
+    let _ = writeln!(out, "**Feature** `{}`\n", qualified_path(file, sym));
+    let _ = writeln!(out, "- Type: `{:?}`", feature.ty);
+    if let Some(mode) = file.parent(sym, false).and_then(|p| file.group_mode(p)) {
+        let _ = writeln!(out, "- Group: `{}`", format_group_mode(&mode));
+    }
+    if let Some(cardinality) = feature.cardinality.as_ref().and_then(format_cardinality) {
+        let _ = writeln!(out, "- Cardinality: `{cardinality}`");
+    }
+    match fixed_value(fixed, &RootSymbol { file: file.id, sym }) {
+        Some(SMTValueState::Off) => {
+            let _ = writeln!(out, "- Analysis: dead feature");
+        }
+        Some(SMTValueState::On) => {
+            let _ = writeln!(out, "- Analysis: core feature");
+        }
+        _ => {}
+    }
+    let mut attributes = String::new();
+    file.visit_attributes(sym, |owner, attrib, prefix| {
+        if owner == sym {
+            let _ = writeln!(
+                attributes,
+                "- `{}` {}",
+                prefix.iter().join("."),
+                format_value(file.value(attrib).unwrap())
+            );
+        }
+    });
+    if !attributes.is_empty() {
+        let _ = writeln!(out, "\n**Attributes**\n\n{attributes}");
+    }
+    out
+}
+fn attribute_card(file: &AstDocument, sym: Symbol) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "**Attribute** `{}`\n", qualified_path(file, sym));
+    if let Some(ty) = file.type_of(sym) {
+        let _ = writeln!(out, "- Type: `{ty:?}`");
+    }
+    if let Some(value) = file.value(sym).map(format_value) {
+        if !value.is_empty() {
+            let _ = writeln!(out, "- Value: `{value}`");
+        }
+    }
+    out
+}
+fn file_card(file: &AstDocument) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "**Model** `{}`\n", qualifier(file));
+    let _ = writeln!(out, "- Features: {}", file.all_features().count());
+    let _ = writeln!(out, "- Constraints: {}", file.all_constraints().count());
+    out
+}
+
+pub fn hover(
+    root: &Snapshot,
+    draft: &Draft,
+    pos: &Position,
+    uri: &Url,
+    fixed: &FixedState,
+) -> Option<Hover> {
+    let origin = root.file_id(uri)?;
+    let defs = find_definitions(root, draft, pos, uri)?;
+    let mut cards = Vec::new();
+    for RootSymbol { file, sym } in defs.iter().take(8) {
+        let doc = root.file(*file);
+        let mut card = match sym {
+            Symbol::Feature(id) => feature_card(doc, *sym, *id, fixed),
+            Symbol::Attribute(..) => attribute_card(doc, *sym),
+            Symbol::Root => file_card(doc),
+            _ => continue,
+        };
+        if *file != origin {
+            let _ = writeln!(card, "\n*defined in* `{}`", file_name(doc));
+        }
+        cards.push(card);
+    }
+    if cards.is_empty() {
+        return None;
+    }
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: cards.join("\n---\n"),
+        }),
+        range: None,
+    })
+}
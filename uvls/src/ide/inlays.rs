@@ -138,6 +138,7 @@ fn generate(model: &OwnedSMTModel, id: FileID, range: Span) -> Option<Vec<InlayH
                     })
                     .collect::<Vec<_>>()
                     .into_iter(),
+                SMTModel::UNKNOWN => Vec::new().into_iter(),
             })
             .collect()
     })
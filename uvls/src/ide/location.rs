@@ -1,10 +1,12 @@
 use crate::core::*;
 use crate::ide::completion::*;
 use hashbrown::HashMap;
+use itertools::Itertools;
 use log::info;
 use ropey::Rope;
 use tower_lsp::lsp_types::*;
 use tree_sitter::Node;
+use ustr::Ustr;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum TextObjectKind {
@@ -170,7 +172,7 @@ pub fn find_text_object(
     }
 }
 
-fn find_definitions(
+pub fn find_definitions(
     root: &Snapshot,
     draft: &Draft,
     pos: &Position,
@@ -393,12 +395,13 @@ fn reverse_resolve(
         .collect()
 }
 
-fn find_references_symboles(
+//Symbols whose references are searched for the text object at pos
+fn find_reference_targets(
     root: &Snapshot,
     draft: &Draft,
     pos: &Position,
     uri: &Url,
-) -> Option<Vec<(RootSymbol, Option<Range>)>> {
+) -> Option<Vec<RootSymbol>> {
     let file_id = root.file_id(uri)?;
     let file = root.file(file_id);
     let obj = find_text_object(draft, pos, file_id, root)?;
@@ -408,33 +411,107 @@ fn find_references_symboles(
                 matches!(sym, Symbol::Feature(..))
             })
             .next()
-            .map(|f| reverse_resolve(root, file_id, f)),
+            .map(|sym| vec![RootSymbol { file: file_id, sym }]),
 
         TextObjectKind::Attribute => file
             .lookup(Symbol::Root, &obj.path.names, |sym| {
                 matches!(sym, Symbol::Feature(..) | Symbol::Attribute(..))
             })
             .next()
-            .map(|a| reverse_resolve(root, file_id, a)),
+            .map(|sym| vec![RootSymbol { file: file_id, sym }]),
 
         TextObjectKind::FeatureReference
         | TextObjectKind::Reference(..)
-        | TextObjectKind::Aggregate(..) => find_definitions(root, draft, pos, uri).map(|defs| {
-            defs.iter()
-                .flat_map(|def| reverse_resolve(root, def.file, def.sym))
-                .collect()
-        }),
+        | TextObjectKind::Aggregate(..) => find_definitions(root, draft, pos, uri),
         _ => None,
     }
 }
-
+fn config_keys<'a>(entries: &'a [ConfigEntry], out: &mut Vec<&'a Path>) {
+    for entry in entries {
+        match entry {
+            ConfigEntry::Value(
+                key,
+                ConfigValue::Cardinality(CardinalityEntry::CardinalityLvl(lvl)),
+            ) => {
+                out.push(key);
+                for entries in lvl.iter() {
+                    config_keys(entries, out);
+                }
+            }
+            ConfigEntry::Value(key, _) => out.push(key),
+            ConfigEntry::Import(key, children) => {
+                out.push(key);
+                config_keys(children, out);
+            }
+        }
+    }
+}
+//Key segments in configuration files that name tgt, found through the source map of
+//Module::resolve_config. Keys are relative to their instance, a feature is always the first
+//segment and attributes follow their feature.
+fn config_references(root: &Snapshot, tgt: RootSymbol) -> Vec<Location> {
+    let file = root.file(tgt.file);
+    let segment = match tgt.sym {
+        Symbol::Feature(..) => 0,
+        Symbol::Attribute(..) => file.prefix(tgt.sym).len(),
+        _ => return Vec::new(),
+    };
+    let below = |mut sym: Symbol| loop {
+        if sym == tgt.sym {
+            return true;
+        }
+        match file.parent(sym, false) {
+            Some(p @ Symbol::Attribute(..)) => sym = p,
+            Some(p) if matches!(sym, Symbol::Attribute(..)) && p == tgt.sym => return true,
+            _ => return false,
+        }
+    };
+    let mut out = Vec::new();
+    for (id, config) in root.cache().config_modules.iter() {
+        let Some(doc) = root.configs.get(id) else {
+            continue;
+        };
+        let Some(content) = doc.config.as_ref() else {
+            continue;
+        };
+        let mut keys = Vec::new();
+        config_keys(&content.config, &mut keys);
+        for (sym, span) in config.source_map.iter() {
+            if config.module.file(sym.instance).id != tgt.file || !below(sym.sym) {
+                continue;
+            }
+            let Some(key) = keys.iter().find(|k| k.range() == *span) else {
+                continue;
+            };
+            if let Some(range) = key
+                .spans
+                .get(segment)
+                .and_then(|span| lsp_range(span.clone(), &doc.source))
+            {
+                out.push(Location {
+                    uri: doc.uri.clone(),
+                    range,
+                });
+            }
+        }
+    }
+    out
+}
 pub fn find_references(
     root: &Snapshot,
     draft: &Draft,
     pos: &Position,
     uri: &Url,
 ) -> Option<Vec<Location>> {
-    let refs = find_references_symboles(root, draft, pos, uri)?;
+    let targets = find_reference_targets(root, draft, pos, uri)?;
+    let refs: Vec<_> = targets
+        .iter()
+        .flat_map(|def| reverse_resolve(root, def.file, def.sym))
+        .collect();
+    let config_refs: Vec<_> = targets
+        .iter()
+        .flat_map(|def| config_references(root, *def))
+        .collect();
     Some(
         refs.iter()
             .filter_map(|(sym, range)| {
@@ -450,6 +527,7 @@ pub fn find_references(
                     }),
                 }
             })
+            .chain(config_refs)
             .collect(),
     )
 }
@@ -495,3 +573,155 @@ pub fn rename(
         change_annotations: None,
     })
 }
+//New uri of a file affected by one of the renames, renaming a folder moves every file below it
+fn renamed(uri: &Url, renames: &[(Url, Url)]) -> Option<Url> {
+    renames.iter().find_map(|(old, new)| {
+        if uri == old {
+            return Some(new.clone());
+        }
+        let rest = uri
+            .as_str()
+            .strip_prefix(old.as_str().trim_end_matches('/'))?
+            .strip_prefix('/')?;
+        Url::parse(&format!("{}/{}", new.as_str().trim_end_matches('/'), rest)).ok()
+    })
+}
+//Import path of file once it lives at uri, the namespace replaces the trailing segments
+fn path_at(file: &AstDocument, uri: &Url) -> Option<Vec<Ustr>> {
+    let mut path = uri_to_path(uri)?;
+    if let Some(ns) = file.namespace() {
+        path.truncate(path.len().saturating_sub(ns.names.len()));
+        path.extend_from_slice(&ns.names);
+    }
+    Some(path)
+}
+//Edits for workspace/willRenameFiles, they are applied before the files move so all edits
+//target the old uris. Imports can't leave the importing directory, when a rename would need
+//that no edit is made and the broken imports are returned as error.
+pub fn rename_files(
+    root: &RootGraph,
+    renames: &[FileRename],
+) -> std::result::Result<Option<WorkspaceEdit>, String> {
+    let renames: Vec<(Url, Url)> = renames
+        .iter()
+        .filter_map(|r| Some((Url::parse(&r.old_uri).ok()?, Url::parse(&r.new_uri).ok()?)))
+        .collect();
+    let new_uri = |uri: &Url| renamed(uri, &renames).unwrap_or_else(|| uri.clone());
+    let mut changes = std::collections::HashMap::<Url, Vec<TextEdit>>::new();
+    let mut broken = Vec::new();
+    for file in root.files.values() {
+        let Some(src) = path_at(file, &new_uri(&file.uri)) else {
+            continue;
+        };
+        let dir = &src[..src.len().saturating_sub(1)];
+        for (import, tgt) in root.fs().imports(file.id) {
+            let target = root.file(tgt);
+            if new_uri(&file.uri) == file.uri && new_uri(&target.uri) == target.uri {
+                continue;
+            }
+            let Some(new_path) = path_at(target, &new_uri(&target.uri)) else {
+                continue;
+            };
+            let Some(rel) = new_path.strip_prefix(dir) else {
+                broken.push(format!(
+                    "{} can't import {}",
+                    new_uri(&file.uri),
+                    new_uri(&target.uri)
+                ));
+                continue;
+            };
+            if rel == file.path(import) {
+                continue;
+            }
+            let Symbol::Import(i) = import else {
+                continue;
+            };
+            let Some(decl) = file.get_import(i) else {
+                continue;
+            };
+            let edits = changes.entry(file.uri.clone()).or_default();
+            edits.extend(
+                lsp_range(decl.path.range(), &file.source).map(|range| TextEdit {
+                    range,
+                    new_text: rel.iter().map(|n| quoted(n.as_str())).join("."),
+                }),
+            );
+            if decl.alias.is_some() {
+                continue;
+            }
+            //Without an alias the import path is the prefix of every reference through it
+            for r in file.all_references() {
+                let path = file.path(r);
+                let Some(end) = root
+                    .resolve_with_binding(file.id, path)
+                    .find_map(|binding| {
+                        binding
+                            .first()
+                            .filter(|(s, _)| s.file == file.id && s.sym == import)
+                            .map(|(_, end)| *end)
+                    })
+                else {
+                    continue;
+                };
+                edits.extend(file.lsp_range(r).map(|range| {
+                    TextEdit {
+                        range,
+                        new_text: rel
+                            .iter()
+                            .chain(path[end..].iter())
+                            .map(|n| quoted(n.as_str()))
+                            .join("."),
+                    }
+                }));
+            }
+        }
+    }
+    for config in root.configs.values() {
+        let Some(file_config) = config.config.as_ref() else {
+            continue;
+        };
+        let target = file_config.file.url();
+        let (config_uri, target_uri) = (new_uri(&config.uri), new_uri(&target));
+        if config_uri == config.uri && target_uri == target {
+            continue;
+        }
+        let (Ok(config_path), Ok(target_path)) =
+            (config_uri.to_file_path(), target_uri.to_file_path())
+        else {
+            continue;
+        };
+        let Some(dir) = config_path.parent() else {
+            continue;
+        };
+        let Some(relative) = pathdiff::diff_paths(&target_path, dir) else {
+            continue;
+        };
+        let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .join("/");
+        if let Some(range) = lsp_range(file_config.file_span.clone(), &config.source) {
+            changes
+                .entry(config.uri.clone())
+                .or_default()
+                .push(TextEdit {
+                    range,
+                    new_text: format!("\"{}\"", relative),
+                });
+        }
+    }
+    if !broken.is_empty() {
+        return Err(format!(
+            "Imports are not updated, they can't leave the directory of the importing file: {}",
+            broken.join(", ")
+        ));
+    }
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(WorkspaceEdit {
+        changes: Some(changes),
+        document_changes: None,
+        change_annotations: None,
+    }))
+}
@@ -1,4 +1,8 @@
 pub mod color;
 pub mod completion;
+pub mod format;
+pub mod hover;
 pub mod inlays;
 pub mod location;
+pub mod signature;
+pub mod symbols;
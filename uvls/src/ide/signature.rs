@@ -0,0 +1,147 @@
+use crate::core::*;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Point;
+//Signature help for the builtin functions, the arity rules mirror opt_aggregate,
+//opt_integer and the len branch of opt_numeric in the transformer.
+
+struct Signature {
+    label: &'static str,
+    doc: &'static str,
+    params: &'static [(&'static str, &'static str)],
+}
+const AGGREGATE_ATTRIB: (&str, &str) = (
+    "attribute",
+    "Path of a numeric attribute, it is looked up on every feature of the aggregated subtree",
+);
+const AGGREGATE_CONTEXT: (&str, &str) = (
+    "context",
+    "Feature whose subtree is aggregated, without it the whole model is used",
+);
+const SUM: &[Signature] = &[
+    Signature {
+        label: "sum(attribute)",
+        doc: "Sum of attribute over all selected features",
+        params: &[AGGREGATE_ATTRIB],
+    },
+    Signature {
+        label: "sum(context, attribute)",
+        doc: "Sum of attribute over all selected features below context",
+        params: &[AGGREGATE_CONTEXT, AGGREGATE_ATTRIB],
+    },
+];
+const AVG: &[Signature] = &[
+    Signature {
+        label: "avg(attribute)",
+        doc: "Average of attribute over all selected features",
+        params: &[AGGREGATE_ATTRIB],
+    },
+    Signature {
+        label: "avg(context, attribute)",
+        doc: "Average of attribute over all selected features below context",
+        params: &[AGGREGATE_CONTEXT, AGGREGATE_ATTRIB],
+    },
+];
+const LEN: &[Signature] = &[Signature {
+    label: "len(string)",
+    doc: "Length of a string, requires the string-constraints language level",
+    params: &[(
+        "string",
+        "String attribute or literal, exactly one argument",
+    )],
+}];
+const FLOOR: &[Signature] = &[Signature {
+    label: "floor(number)",
+    doc: "Rounds down to the next integer, requires the numeric-constraints language level",
+    params: &[("number", "Numeric expression, exactly one argument")],
+}];
+const CEIL: &[Signature] = &[Signature {
+    label: "ceil(number)",
+    doc: "Rounds up to the next integer, requires the numeric-constraints language level",
+    params: &[("number", "Numeric expression, exactly one argument")],
+}];
+
+fn signatures(name: &str) -> Option<&'static [Signature]> {
+    match name {
+        "sum" => Some(SUM),
+        "avg" => Some(AVG),
+        "len" => Some(LEN),
+        "floor" => Some(FLOOR),
+        "ceil" => Some(CEIL),
+        _ => None,
+    }
+}
+//Scans the line backwards for the innermost open call, the green tree is usually broken
+//while the arguments are typed. Returns the function name and the argument index.
+fn open_call(line: &str) -> Option<(&str, usize)> {
+    let mut depth = 0;
+    let mut commas = 0;
+    let mut in_string = false;
+    for (i, c) in line.char_indices().rev() {
+        match c {
+            '\'' => in_string = !in_string,
+            _ if in_string => {}
+            ')' | ']' => depth += 1,
+            '[' if depth > 0 => depth -= 1,
+            ',' if depth == 0 => commas += 1,
+            '(' if depth > 0 => depth -= 1,
+            '(' => {
+                let head = line[..i].trim_end();
+                let start = head
+                    .rfind(|c: char| !(c == '_' || c.is_alphanumeric()))
+                    .map(|i| i + 1)
+                    .unwrap_or(0);
+                return Some((&head[start..], commas));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+pub fn signature_help(draft: &Draft, pos: &Position) -> Option<SignatureHelp> {
+    let (source, tree) = match draft {
+        Draft::UVL { source, tree, .. } => (source, tree),
+        _ => return None,
+    };
+    let offset = char_offset(pos, source);
+    let row = source.char_to_line(offset);
+    let line = source.slice(source.line_to_char(row)..offset).to_string();
+    let point = Point {
+        row,
+        column: line.len(),
+    };
+    if tree
+        .root_node()
+        .descendant_for_point_range(point, point)
+        .map(|n| n.kind() == "comment")
+        .unwrap_or(false)
+    {
+        return None;
+    }
+    let (name, arg) = open_call(&line)?;
+    let candidates = signatures(name)?;
+    let active = candidates
+        .iter()
+        .position(|s| s.params.len() > arg)
+        .unwrap_or(candidates.len() - 1);
+    Some(SignatureHelp {
+        signatures: candidates
+            .iter()
+            .map(|s| SignatureInformation {
+                label: s.label.into(),
+                documentation: Some(Documentation::String(s.doc.into())),
+                parameters: Some(
+                    s.params
+                        .iter()
+                        .map(|(name, doc)| ParameterInformation {
+                            label: ParameterLabel::Simple(name.to_string()),
+                            documentation: Some(Documentation::String(doc.to_string())),
+                        })
+                        .collect(),
+                ),
+                active_parameter: None,
+            })
+            .collect(),
+        active_signature: Some(active as u32),
+        active_parameter: Some(arg as u32),
+    })
+}
@@ -0,0 +1,433 @@
+use crate::core::*;
+use crate::ide::completion::{completion_weight, CompletionEnv, CompletionKind, TopN};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use itertools::Itertools;
+use ropey::Rope;
+use tower_lsp::lsp_types::{SymbolKind, *};
+//Document outlines, sections are taken from the green tree while their content
+//comes from the AstDocument
+
+#[allow(deprecated)]
+fn symbol(
+    name: String,
+    detail: Option<String>,
+    kind: SymbolKind,
+    range: Range,
+    selection_range: Range,
+    children: Vec<DocumentSymbol>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        //empty names are rejected by some clients
+        name: if name.is_empty() { "_".into() } else { name },
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range,
+        children: if children.is_empty() {
+            None
+        } else {
+            Some(children)
+        },
+    }
+}
+//Range of the whole block declaring the symbol at span
+fn blk_range(file: &AstDocument, span: Span) -> Option<Range> {
+    let node = file
+        .tree
+        .root_node()
+        .descendant_for_byte_range(span.start, span.end)?;
+    containing_blk(node).map(|blk| node_range(blk, &file.source))
+}
+fn source_text(span: Span, source: &Rope) -> String {
+    source
+        .byte_slice(span)
+        .to_string()
+        .split_whitespace()
+        .join(" ")
+}
+
+fn outline_attribute(file: &AstDocument, sym: Symbol) -> Option<DocumentSymbol> {
+    let Symbol::Attribute(id) = sym else {
+        return None;
+    };
+    let attribute = file.get_attribute(id)?;
+    let selection = file.lsp_range(sym)?;
+    let range = lsp_range(
+        attribute.name.span.start..attribute.value.span.end.max(attribute.name.span.end),
+        &file.source,
+    )
+    .unwrap_or(selection);
+    let detail = match &attribute.value.value {
+        ast::Value::Void | ast::Value::Attributes | ast::Value::Vector => None,
+        _ => Some(source_text(attribute.value.span.clone(), &file.source)),
+    };
+    Some(symbol(
+        attribute.name.name.to_string(),
+        detail,
+        SymbolKind::PROPERTY,
+        range,
+        selection,
+        file.direct_children(sym)
+            .filter_map(|c| outline_attribute(file, c))
+            .collect(),
+    ))
+}
+fn outline_group(file: &AstDocument, sym: Symbol) -> Option<DocumentSymbol> {
+    let span = file.span(sym)?;
+    let selection = lsp_range(span.clone(), &file.source)?;
+    Some(symbol(
+        source_text(span.clone(), &file.source),
+        None,
+        SymbolKind::ENUM,
+        blk_range(file, span).unwrap_or(selection),
+        selection,
+        file.direct_children(sym)
+            .filter_map(|c| match c {
+                Symbol::Feature(..) => outline_feature(file, c),
+                Symbol::Reference(..) => {
+                    let range = file.lsp_range(c)?;
+                    Some(symbol(
+                        file.path(c).iter().join("."),
+                        None,
+                        SymbolKind::INTERFACE,
+                        range,
+                        range,
+                        Vec::new(),
+                    ))
+                }
+                _ => None,
+            })
+            .collect(),
+    ))
+}
+fn outline_feature(file: &AstDocument, sym: Symbol) -> Option<DocumentSymbol> {
+    let Symbol::Feature(id) = sym else {
+        return None;
+    };
+    let feature = file.get_feature(id)?;
+    let selection = file.lsp_range(sym)?;
+    let detail = match (feature.ty, &feature.cardinality) {
+        (_, Some(Cardinality::Range(min, max))) => Some(format!("[{min}..{max}]")),
+        (Type::Bool, _) => None,
+        (ty, _) => Some(format!("{ty:?}")),
+    };
+    Some(symbol(
+        feature.name.name.to_string(),
+        detail,
+        SymbolKind::CLASS,
+        blk_range(file, feature.name.span.clone()).unwrap_or(selection),
+        selection,
+        file.direct_children(sym)
+            .filter_map(|c| match c {
+                Symbol::Attribute(..) => outline_attribute(file, c),
+                Symbol::Group(..) => outline_group(file, c),
+                _ => None,
+            })
+            .collect(),
+    ))
+}
+
+pub fn document_symbols_uvl(file: &AstDocument) -> Vec<DocumentSymbol> {
+    let mut out = Vec::new();
+    let mut cursor = file.tree.walk();
+    for blk in file
+        .tree
+        .root_node()
+        .children(&mut cursor)
+        .filter(|n| n.kind() == "blk")
+    {
+        let Some(header) = blk.child_by_field_name("header") else {
+            continue;
+        };
+        let range = node_range(blk, &file.source);
+        let selection = node_range(header, &file.source);
+        match header.kind() {
+            "namespace" => {
+                if let Some(ns) = file.namespace() {
+                    out.push(symbol(
+                        ns.to_string(),
+                        None,
+                        SymbolKind::NAMESPACE,
+                        range,
+                        selection,
+                        Vec::new(),
+                    ));
+                }
+            }
+            "include" => out.push(symbol(
+                "include".into(),
+                None,
+                SymbolKind::PACKAGE,
+                range,
+                selection,
+                file.all_lang_lvls()
+                    .filter_map(|sym| {
+                        let span = file.span(sym)?;
+                        let range = lsp_range(span.clone(), &file.source)?;
+                        Some(symbol(
+                            source_text(span, &file.source),
+                            None,
+                            SymbolKind::KEY,
+                            range,
+                            range,
+                            Vec::new(),
+                        ))
+                    })
+                    .collect(),
+            )),
+            "imports" => out.push(symbol(
+                "imports".into(),
+                None,
+                SymbolKind::MODULE,
+                range,
+                selection,
+                file.imports()
+                    .iter()
+                    .filter_map(|import| {
+                        let range = lsp_range(import.path.range(), &file.source)?;
+                        Some(symbol(
+                            import.path.to_string(),
+                            import.alias.as_ref().map(|a| format!("as {}", a.name)),
+                            SymbolKind::FILE,
+                            range,
+                            range,
+                            Vec::new(),
+                        ))
+                    })
+                    .collect(),
+            )),
+            "features" => out.push(symbol(
+                "features".into(),
+                None,
+                SymbolKind::MODULE,
+                range,
+                selection,
+                file.direct_children(Symbol::Root)
+                    .filter_map(|c| outline_feature(file, c))
+                    .collect(),
+            )),
+            "constraints" => out.push(symbol(
+                "constraints".into(),
+                None,
+                SymbolKind::MODULE,
+                range,
+                selection,
+                file.direct_children(Symbol::Root)
+                    .filter(|c| matches!(c, Symbol::Constraint(..)))
+                    .filter_map(|c| {
+                        let span = file.span(c)?;
+                        let range = lsp_range(span.clone(), &file.source)?;
+                        Some(symbol(
+                            source_text(span, &file.source),
+                            None,
+                            SymbolKind::OPERATOR,
+                            range,
+                            range,
+                            Vec::new(),
+                        ))
+                    })
+                    .collect(),
+            )),
+            _ => {}
+        }
+    }
+    out
+}
+
+fn outline_config(entries: &[ConfigEntry], source: &Rope) -> Vec<DocumentSymbol> {
+    entries
+        .iter()
+        .filter_map(|entry| match entry {
+            ConfigEntry::Value(path, value) => {
+                let range = lsp_range(path.range(), source)?;
+                let (kind, children) = match value {
+                    ConfigValue::Bool(..) => (SymbolKind::BOOLEAN, Vec::new()),
+                    ConfigValue::Number(..) => (SymbolKind::NUMBER, Vec::new()),
+                    ConfigValue::String(..) => (SymbolKind::STRING, Vec::new()),
+                    ConfigValue::Cardinality(CardinalityEntry::CardinalityLvl(instances)) => (
+                        SymbolKind::ARRAY,
+                        instances
+                            .iter()
+                            .enumerate()
+                            .map(|(i, instance)| {
+                                symbol(
+                                    format!("[{i}]"),
+                                    None,
+                                    SymbolKind::OBJECT,
+                                    range,
+                                    range,
+                                    outline_config(instance, source),
+                                )
+                            })
+                            .collect(),
+                    ),
+                    ConfigValue::Cardinality(CardinalityEntry::EntitiyLvl(entries)) => {
+                        (SymbolKind::OBJECT, outline_config(entries, source))
+                    }
+                };
+                Some(symbol(
+                    path.to_string(),
+                    match value {
+                        ConfigValue::Cardinality(..) => None,
+                        _ => Some(value.to_string()),
+                    },
+                    kind,
+                    range,
+                    range,
+                    children,
+                ))
+            }
+            ConfigEntry::Import(path, entries) => {
+                let range = lsp_range(path.range(), source)?;
+                Some(symbol(
+                    path.to_string(),
+                    None,
+                    SymbolKind::MODULE,
+                    range,
+                    range,
+                    outline_config(entries, source),
+                ))
+            }
+        })
+        .collect()
+}
+pub fn document_symbols_config(doc: &ConfigDocument) -> Vec<DocumentSymbol> {
+    let Some(config) = doc.config.as_ref() else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    if let Some(range) = lsp_range(config.file_span.clone(), &doc.source) {
+        out.push(symbol(
+            "file".into(),
+            Some(doc.source.byte_slice(config.file_span.clone()).to_string()),
+            SymbolKind::FILE,
+            range,
+            range,
+            Vec::new(),
+        ));
+    }
+    let children = outline_config(&config.config, &doc.source);
+    if let (Some(first), Some(last)) = (children.first(), children.last()) {
+        let range = Range {
+            start: first.range.start,
+            end: last.range.end,
+        };
+        out.push(symbol(
+            "config".into(),
+            None,
+            SymbolKind::OBJECT,
+            range,
+            range,
+            children,
+        ));
+    }
+    out
+}
+pub fn document_symbols(root: &Snapshot, uri: &Url) -> Option<DocumentSymbolResponse> {
+    if let Some(file) = root.file_by_uri(uri) {
+        Some(DocumentSymbolResponse::Nested(document_symbols_uvl(file)))
+    } else {
+        root.config_by_uri(uri)
+            .map(|doc| DocumentSymbolResponse::Nested(document_symbols_config(doc)))
+    }
+}
+
+static MAX_WORKSPACE_SYMBOLS: usize = 128;
+//A workspace symbol ranked like a completion option
+struct RankedSymbol {
+    rank: f32,
+    info: SymbolInformation,
+}
+impl PartialEq for RankedSymbol {
+    fn eq(&self, other: &Self) -> bool {
+        self.rank == other.rank
+    }
+}
+impl Eq for RankedSymbol {}
+impl PartialOrd for RankedSymbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for RankedSymbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.rank.total_cmp(&other.rank)
+    }
+}
+//Import path under which a file is known to other files, falls back to the file name
+fn import_path(root: &RootGraph, file: &AstDocument) -> String {
+    root.fs()
+        .imported(file.id)
+        .map(|(sym, importer)| root.file(importer).path(sym).iter().join("."))
+        .sorted()
+        .next()
+        .or_else(|| file.path.last().map(|name| name.to_string()))
+        .unwrap_or_default()
+}
+pub fn workspace_symbols(root: &RootGraph, query: &str) -> Vec<SymbolInformation> {
+    let matcher = SkimMatcherV2::default();
+    let mut top = TopN::new(MAX_WORKSPACE_SYMBOLS);
+    let mut push = |name: String,
+                    container: &str,
+                    kind: SymbolKind,
+                    ty: CompletionKind,
+                    location: Location| {
+        if !query.is_empty() && matcher.fuzzy_match(&name, query).is_none() {
+            return;
+        }
+        #[allow(deprecated)]
+        let info = SymbolInformation {
+            name,
+            kind,
+            tags: None,
+            deprecated: None,
+            location,
+            container_name: (!container.is_empty()).then(|| container.into()),
+        };
+        top.push(RankedSymbol {
+            rank: completion_weight(query, &info.name, 0, &CompletionEnv::Feature, ty),
+            info,
+        });
+    };
+    for file in root.files.values() {
+        let container = import_path(root, file);
+        push(
+            container.clone(),
+            "",
+            SymbolKind::FILE,
+            CompletionKind::File,
+            Location {
+                uri: file.uri.clone(),
+                range: Range::default(),
+            },
+        );
+        for sym in file.all_features().chain(file.all_attributes()) {
+            let Some(range) = file.lsp_range(sym) else {
+                continue;
+            };
+            let (name, kind) = match sym {
+                Symbol::Feature(..) => (file.name(sym).unwrap().to_string(), SymbolKind::CLASS),
+                _ => (
+                    file.name(file.scope(sym))
+                        .into_iter()
+                        .chain(file.prefix(sym).into_iter().rev())
+                        .join("."),
+                    SymbolKind::PROPERTY,
+                ),
+            };
+            push(
+                name,
+                &container,
+                kind,
+                file.type_of(sym).map(CompletionKind::from).unwrap(),
+                Location {
+                    uri: file.uri.clone(),
+                    range,
+                },
+            );
+        }
+    }
+    top.into_sorted_vec().into_iter().map(|s| s.info).collect()
+}
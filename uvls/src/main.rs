@@ -9,7 +9,7 @@ use log::info;
 use percent_encoding::percent_decode_str;
 use serde::Serialize;
 use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::SystemTime;
 use tokio::{join, spawn};
@@ -21,24 +21,35 @@ mod ide;
 mod smt;
 mod webview;
 use crate::core::*;
-use crate::smt::{smt_lib::Expr, uvl2smt, SmtSolver};
-struct Settings {
+use crate::smt::{new_backend, smt_lib::Expr, uvl2smt, SatResult};
+#[derive(Default)]
+struct ClientState {
     //can the client show websites on its own
     //ie client==vscode
     has_webview: bool,
+    //can the client show $/progress
+    has_progress: bool,
+    //can the client resolve the edit of a code action
+    has_resolve: bool,
+    //can the client watch globs relative to a workspace folder
+    has_relative_watch: bool,
+    //the file watchers are registered
+    watching: bool,
 }
-impl Default for Settings {
-    fn default() -> Self {
-        Settings { has_webview: false }
-    }
+//Running configuration web server
+struct WebHandler {
+    port: u16,
+    task: tokio::task::JoinHandle<()>,
 }
 //The LSP
 struct Backend {
     client: Client,
     coloring: Arc<ide::color::State>,
     pipeline: AsyncPipeline,
-    web_handler_uri: String,
-    settings: parking_lot::Mutex<Settings>,
+    web_handler: parking_lot::Mutex<Option<WebHandler>>,
+    //open workspace folders
+    folders: parking_lot::Mutex<Vec<Url>>,
+    client_state: parking_lot::Mutex<ClientState>,
 }
 impl Backend {
     fn load(&self, uri: Url) {
@@ -47,6 +58,141 @@ impl Backend {
             load_blocking(uri, &pipeline);
         });
     }
+    //Register new workspace folders and load them in the background
+    async fn add_folders(&self, folders: Vec<Url>) {
+        self.register_folders(&folders).await;
+        self.load_folders(folders);
+    }
+    async fn register_folders(&self, folders: &[Url]) {
+        let all = {
+            let mut known = self.folders.lock();
+            for folder in folders.iter() {
+                if !known.contains(folder) {
+                    known.push(folder.clone());
+                }
+            }
+            known.clone()
+        };
+        self.pipeline.set_workspace_folders(all).await;
+    }
+    fn load_folders(&self, folders: Vec<Url>) {
+        let progress = self.client_state.lock().has_progress;
+        for folder in folders {
+            let Ok(path) = folder.to_file_path() else {
+                continue;
+            };
+            spawn(load_folder(path, self.pipeline.clone(), progress));
+        }
+    }
+    //Unload workspace folders, files that are still inside another folder are kept
+    async fn remove_folders(&self, folders: Vec<Url>) {
+        let all = {
+            let mut known = self.folders.lock();
+            known.retain(|f| !folders.contains(f));
+            known.clone()
+        };
+        self.pipeline.set_workspace_folders(all.clone()).await;
+        let remaining: Vec<PathBuf> = all.iter().filter_map(|f| f.to_file_path().ok()).collect();
+        for folder in folders {
+            self.pipeline
+                .unload_folder(&folder, |uri| {
+                    uri.to_file_path()
+                        .map(|path| remaining.iter().any(|f| path.starts_with(f)))
+                        .unwrap_or(false)
+                })
+                .await;
+        }
+    }
+    //Watch the included uvl files of each folder and all configs, replaces older watchers
+    async fn register_watchers(&self) {
+        let (relative, watching) = {
+            let state = self.client_state.lock();
+            (state.has_relative_watch, state.watching)
+        };
+        if watching {
+            let unreg = Unregistration {
+                id: "watcher".to_string(),
+                method: "workspace/didChangeWatchedFiles".to_string(),
+            };
+            let _ = self.client.unregister_capability(vec![unreg]).await;
+        }
+        let globs = workspace::watch_globs(&settings());
+        let mut watchers = vec![FileSystemWatcher {
+            glob_pattern: GlobPattern::String("**/*.uvl.json".to_string()),
+            kind: None,
+        }];
+        if relative {
+            let folders = self.folders.lock().clone();
+            for folder in folders {
+                watchers.extend(globs.iter().map(|glob| FileSystemWatcher {
+                    glob_pattern: GlobPattern::Relative(RelativePattern {
+                        base_uri: OneOf::Right(folder.clone()),
+                        pattern: glob.clone(),
+                    }),
+                    kind: None,
+                }));
+            }
+        } else {
+            watchers.extend(globs.iter().map(|glob| FileSystemWatcher {
+                glob_pattern: GlobPattern::String(if glob.starts_with("**/") {
+                    glob.clone()
+                } else {
+                    format!("**/{glob}")
+                }),
+                kind: None,
+            }));
+        }
+        let reg = Registration {
+            id: "watcher".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+                watchers,
+            })
+            .ok(),
+        };
+        let watching = self.client.register_capability(vec![reg]).await.is_ok();
+        if !watching {
+            info!("failed to initialize file watchers");
+        }
+        self.client_state.lock().watching = watching;
+    }
+    //Start, restart or stop the web server to match the settings
+    fn update_web_handler(&self, settings: &WebSettings) {
+        let mut web = self.web_handler.lock();
+        if let Some(handler) = web.as_ref() {
+            if settings.enabled && settings.port.map(|p| p == handler.port).unwrap_or(true) {
+                return;
+            }
+            handler.task.abort();
+            *web = None;
+        }
+        if !settings.enabled {
+            return;
+        }
+        let port = settings.port.or_else(|| {
+            get_port::tcp::TcpPort::in_range(
+                "127.0.0.1",
+                get_port::Range {
+                    min: 3000,
+                    max: 6000,
+                },
+            )
+        });
+        if let Some(port) = port {
+            *web = Some(WebHandler {
+                port,
+                task: spawn(webview::web_handler(self.pipeline.clone(), port)),
+            });
+        } else {
+            info!("no free port for the web server");
+        }
+    }
+    fn web_handler_uri(&self) -> Option<String> {
+        self.web_handler
+            .lock()
+            .as_ref()
+            .map(|handler| format!("http://localhost:{}", handler.port))
+    }
     async fn snapshot(&self, uri: &Url, sync: bool) -> Result<Option<(Draft, Arc<RootGraph>)>> {
         self.pipeline
             .snapshot(uri, sync)
@@ -71,20 +217,85 @@ fn load_blocking(uri: Url, pipeline: &AsyncPipeline) {
         info!("Failed to load file {} : {}", uri, e);
     }
 }
-//load all files under given a path
-fn load_all_blocking(path: &Path, pipeline: AsyncPipeline) {
-    for e in walkdir::WalkDir::new(path)
+//Files loaded between two progress reports
+const LOAD_CHUNK: usize = 64;
+async fn report_progress(client: &Client, token: &NumberOrString, progress: WorkDoneProgress) {
+    client
+        .send_notification::<notification::Progress>(ProgressParams {
+            token: token.clone(),
+            value: ProgressParamsValue::WorkDone(progress),
+        })
+        .await;
+}
+//load all files under a workspace folder, large folders report their progress
+async fn load_folder(path: PathBuf, pipeline: AsyncPipeline, progress: bool) {
+    let files = {
+        let path = path.clone();
+        let settings = settings();
+        match tokio::task::spawn_blocking(move || workspace::scan(&path, &settings)).await {
+            Ok(files) => files,
+            Err(_) => return,
+        }
+    };
+    let client = pipeline.client();
+    let token = NumberOrString::String(format!("uvls/load/{}", path.display()));
+    let progress = progress
+        && files.len() > LOAD_CHUNK
+        && client
+            .send_request::<request::WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: token.clone(),
+            })
+            .await
+            .is_ok();
+    if progress {
+        report_progress(
+            &client,
+            &token,
+            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: "Loading UVL files".into(),
+                cancellable: Some(false),
+                message: Some(format!("{}", path.display())),
+                percentage: Some(0),
+            }),
+        )
+        .await;
+    }
+    //files are loaded by the scheduler in idle time, imports of open documents first
+    let total = files.len();
+    let pending: Vec<_> = files
         .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().is_file())
-        .filter(|e| {
-            e.path()
-                .extension()
-                .map(|e| e == std::ffi::OsStr::new("uvl"))
-                .unwrap_or(false)
+        .filter_map(|file| Url::from_file_path(file).ok())
+        .map(|uri| {
+            let job = (uri.clone(), pipeline.clone());
+            pipeline
+                .scheduler()
+                .schedule(uri, Priority::Background, move || {
+                    load_blocking(job.0, &job.1)
+                })
         })
-    {
-        load_blocking(Url::from_file_path(e.path()).unwrap(), &pipeline)
+        .collect();
+    for (i, done) in pending.into_iter().enumerate() {
+        let _ = done.await;
+        if progress && ((i + 1) % LOAD_CHUNK == 0 || i + 1 == total) {
+            report_progress(
+                &client,
+                &token,
+                WorkDoneProgress::Report(WorkDoneProgressReport {
+                    cancellable: Some(false),
+                    message: Some(format!("{}/{total}", i + 1)),
+                    percentage: Some(((i + 1) * 100 / total) as u32),
+                }),
+            )
+            .await;
+        }
+    }
+    if progress {
+        report_progress(
+            &client,
+            &token,
+            WorkDoneProgress::End(WorkDoneProgressEnd { message: None }),
+        )
+        .await;
     }
 }
 fn shutdown_error() -> tower_lsp::jsonrpc::Error {
@@ -94,34 +305,99 @@ fn shutdown_error() -> tower_lsp::jsonrpc::Error {
         data: None,
     }
 }
+fn web_disabled_error() -> tower_lsp::jsonrpc::Error {
+    tower_lsp::jsonrpc::Error {
+        code: tower_lsp::jsonrpc::ErrorCode::InvalidRequest,
+        message: "the configuration web server is disabled".into(),
+        data: None,
+    }
+}
 //Handler for different LSP requests
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
     async fn initialize(&self, init_params: InitializeParams) -> Result<InitializeResult> {
+        if let Some(settings) = init_params
+            .initialization_options
+            .as_ref()
+            .and_then(Settings::from_value)
+        {
+            update_settings(settings);
+        }
+        self.update_web_handler(&settings().web);
+        //Fall back to the root when the client has no workspace folders
         #[allow(deprecated)]
-        let root_folder = init_params
-            .root_path
-            .as_deref()
-            .or_else(|| init_params.root_uri.as_ref().map(|p| p.path()))
-            .map(PathBuf::from);
-        if let Some(root_folder) = root_folder {
-            let semantic = self.pipeline.clone();
-            //cheap fix for better intial load, we should really use priority model to prefer
-            //editor owned files
-            spawn(async move {
-                tokio::task::spawn_blocking(move || {
-                    load_all_blocking(&root_folder, semantic);
-                })
-                .await
-            });
+        let folders = init_params
+            .workspace_folders
+            .as_ref()
+            .map(|folders| folders.iter().map(|f| f.uri.clone()).collect())
+            .or_else(|| {
+                init_params
+                    .root_uri
+                    .clone()
+                    .or_else(|| {
+                        init_params
+                            .root_path
+                            .as_ref()
+                            .and_then(|p| Url::from_directory_path(p).ok())
+                    })
+                    .map(|uri| vec![uri])
+            })
+            .unwrap_or_default();
+        //the folders are loaded after initialized, $/progress is not allowed before
+        self.register_folders(&folders).await;
+        let encoding = PositionEncoding::negotiate(
+            init_params
+                .capabilities
+                .general
+                .as_ref()
+                .and_then(|general| general.position_encodings.as_deref()),
+        );
+        encoding.set();
+        //Clients that can pull diagnostics get them on request instead of push notifications
+        if init_params
+            .capabilities
+            .text_document
+            .as_ref()
+            .and_then(|doc| doc.diagnostic.as_ref())
+            .is_some()
+        {
+            let refresh = init_params
+                .capabilities
+                .workspace
+                .as_ref()
+                .and_then(|ws| ws.diagnostic.as_ref())
+                .and_then(|diag| diag.refresh_support)
+                .unwrap_or(false);
+            self.pipeline.diagnostics().enable_pull(refresh);
         }
         if init_params
             .client_info
             .map(|info| matches!(info.name.as_str(), "Visual Studio Code"))
             .unwrap_or(false)
         {
-            self.settings.lock().has_webview = true;
+            self.client_state.lock().has_webview = true;
         }
+        self.client_state.lock().has_progress = init_params
+            .capabilities
+            .window
+            .as_ref()
+            .and_then(|window| window.work_done_progress)
+            .unwrap_or(false);
+        self.client_state.lock().has_resolve = init_params
+            .capabilities
+            .text_document
+            .as_ref()
+            .and_then(|doc| doc.code_action.as_ref())
+            .and_then(|action| action.resolve_support.as_ref())
+            .map(|support| support.properties.iter().any(|p| p == "edit"))
+            .unwrap_or(false);
+        self.client_state.lock().has_relative_watch = init_params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|ws| ws.did_change_watched_files.as_ref())
+            .and_then(|watch| watch.relative_pattern_support)
+            .unwrap_or(false);
 
         Ok(InitializeResult {
             server_info: Some(ServerInfo {
@@ -129,6 +405,7 @@ impl LanguageServer for Backend {
                 version: None,
             }),
             capabilities: ServerCapabilities {
+                position_encoding: Some(encoding.kind()),
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
                     TextDocumentSyncKind::INCREMENTAL,
                 )),
@@ -139,6 +416,12 @@ impl LanguageServer for Backend {
                     ..Default::default()
                 }),
                 definition_provider: Some(OneOf::Left(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec!["(".into(), ",".into()]),
+                    retrigger_characters: None,
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                }),
                 semantic_tokens_provider: Some(
                     SemanticTokensServerCapabilities::SemanticTokensOptions(
                         SemanticTokensOptions {
@@ -155,13 +438,68 @@ impl LanguageServer for Backend {
                     ),
                 ),
                 folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "\n".into(),
+                    more_trigger_character: None,
+                }),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
                 rename_provider: Some(OneOf::Left(true)),
                 code_lens_provider: Some(CodeLensOptions {
                     resolve_provider: Some(true),
                 }),
                 inlay_hint_provider: Some(OneOf::Left(true)),
-                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                        will_rename: Some(FileOperationRegistrationOptions {
+                            filters: vec![
+                                FileOperationFilter {
+                                    scheme: Some("file".into()),
+                                    pattern: FileOperationPattern {
+                                        glob: "**/*.{uvl,json}".into(),
+                                        matches: Some(FileOperationPatternKind::File),
+                                        options: None,
+                                    },
+                                },
+                                FileOperationFilter {
+                                    scheme: Some("file".into()),
+                                    pattern: FileOperationPattern {
+                                        glob: "**".into(),
+                                        matches: Some(FileOperationPatternKind::Folder),
+                                        options: None,
+                                    },
+                                },
+                            ],
+                        }),
+                        ..Default::default()
+                    }),
+                }),
+                diagnostic_provider: Some(DiagnosticServerCapabilities::Options(
+                    DiagnosticOptions {
+                        identifier: Some("uvls".into()),
+                        inter_file_dependencies: true,
+                        workspace_diagnostics: true,
+                        work_done_progress_options: Default::default(),
+                    },
+                )),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::QUICKFIX,
+                            CodeActionKind::REFACTOR_EXTRACT,
+                            CodeActionKind::REFACTOR_INLINE,
+                        ]),
+                        resolve_provider: Some(true),
+                        ..Default::default()
+                    },
+                )),
                 execute_command_provider: Some(ExecuteCommandOptions {
                     commands: vec![
                         "uvls/show_config".into(),
@@ -184,25 +522,49 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::INFO, "server initialized!")
             .await;
-
-        let watcher_uvl = FileSystemWatcher {
-            glob_pattern: GlobPattern::String("**/*.uvl".to_string()),
-            kind: None,
-        };
-        let watcher_config = FileSystemWatcher {
-            glob_pattern: GlobPattern::String("**/*.uvl.json".to_string()),
-            kind: None,
-        };
+        let folders = self.folders.lock().clone();
+        self.load_folders(folders);
+        self.register_watchers().await;
+        //Some clients only notify about settings changes after a registration
         let reg = Registration {
-            id: "watcher".to_string(),
-            method: "workspace/didChangeWatchedFiles".to_string(),
-            register_options: serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
-                watchers: vec![watcher_config, watcher_uvl],
-            })
-            .ok(),
+            id: "configuration".to_string(),
+            method: "workspace/didChangeConfiguration".to_string(),
+            register_options: None,
         };
         if self.client.register_capability(vec![reg]).await.is_err() {
-            info!("failed to initialize file watchers");
+            info!("failed to register for configuration changes");
+        }
+    }
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        let uris = |folders: Vec<WorkspaceFolder>| -> Vec<Url> {
+            folders.into_iter().map(|f| f.uri).collect()
+        };
+        self.remove_folders(uris(params.event.removed)).await;
+        self.add_folders(uris(params.event.added)).await;
+        self.register_watchers().await;
+    }
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        //Clients using the pull model send no settings, so we ask for them
+        let value = if params.settings.is_null() {
+            self.client
+                .configuration(vec![ConfigurationItem {
+                    scope_uri: None,
+                    section: Some("uvls".into()),
+                }])
+                .await
+                .ok()
+                .and_then(|mut values| values.pop())
+                .unwrap_or_default()
+        } else {
+            params.settings
+        };
+        if let Some(new) = Settings::from_value(&value) {
+            if let Some(old) = update_settings(new) {
+                self.update_web_handler(&settings().web);
+                if old.include != settings().include {
+                    self.register_watchers().await;
+                }
+            }
         }
     }
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
@@ -217,7 +579,9 @@ impl LanguageServer for Backend {
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri.clone();
         self.pipeline.update(params);
-        self.client.publish_diagnostics(uri, vec![], None).await;
+        if !self.pipeline.diagnostics().is_pull() {
+            self.client.publish_diagnostics(uri, vec![], None).await;
+        }
         info!("done did_change");
     }
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
@@ -250,6 +614,97 @@ impl LanguageServer for Backend {
         }
         Ok(None)
     }
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = &params.text_document.uri;
+        if let Some((_, root)) = self.snapshot(uri, true).await? {
+            Ok(ide::symbols::document_symbols(&root, uri))
+        } else {
+            Ok(None)
+        }
+    }
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        let root = self
+            .pipeline
+            .sync_root_global()
+            .await
+            .map_err(|_| shutdown_error())?;
+        Ok(Some(ide::symbols::workspace_symbols(&root, &params.query)))
+    }
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        if let Some(draft) = self
+            .pipeline
+            .snapshot_draft(uri)
+            .await
+            .map_err(|_| shutdown_error())?
+        {
+            Ok(ide::signature::signature_help(
+                &draft,
+                &params.text_document_position_params.position,
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let uri = &params.text_document.uri;
+        if let Some(draft) = self
+            .pipeline
+            .snapshot_draft(uri)
+            .await
+            .map_err(|_| shutdown_error())?
+        {
+            Ok(ide::format::format(&draft, &params.options))
+        } else {
+            Ok(None)
+        }
+    }
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let uri = &params.text_document.uri;
+        if let Some(draft) = self
+            .pipeline
+            .snapshot_draft(uri)
+            .await
+            .map_err(|_| shutdown_error())?
+        {
+            Ok(ide::format::format_range(
+                &draft,
+                &params.range,
+                &params.options,
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let uri = &params.text_document_position.text_document.uri;
+        if let Some(draft) = self
+            .pipeline
+            .snapshot_draft(uri)
+            .await
+            .map_err(|_| shutdown_error())?
+        {
+            Ok(ide::format::on_type(
+                &draft,
+                &params.text_document_position.position,
+                &params.options,
+            ))
+        } else {
+            Ok(None)
+        }
+    }
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
@@ -266,6 +721,20 @@ impl LanguageServer for Backend {
             Ok(None)
         }
     }
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        if let Some((draft, root)) = self.snapshot(uri, true).await? {
+            Ok(ide::hover::hover(
+                &root,
+                &draft,
+                &params.text_document_position_params.position,
+                uri,
+                self.pipeline.fixed_state(),
+            ))
+        } else {
+            Ok(None)
+        }
+    }
     async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
         let uri = &params.text_document_position.text_document.uri;
         if let Some((draft, root)) = self.snapshot(uri, true).await? {
@@ -294,6 +763,92 @@ impl LanguageServer for Backend {
             return Ok(None);
         }
     }
+    async fn will_rename_files(&self, params: RenameFilesParams) -> Result<Option<WorkspaceEdit>> {
+        let root = self
+            .pipeline
+            .sync_root_global()
+            .await
+            .map_err(|_| shutdown_error())?;
+        match ide::location::rename_files(&root, &params.files) {
+            Ok(edit) => Ok(edit),
+            Err(msg) => {
+                self.client.show_message(MessageType::WARNING, msg).await;
+                Ok(None)
+            }
+        }
+    }
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReportResult> {
+        let uri = &params.text_document.uri;
+        let store = self.pipeline.diagnostics();
+        if let Some((_, root)) = self.snapshot(uri, true).await? {
+            store
+                .sync(root.revision())
+                .await
+                .map_err(|_| shutdown_error())?;
+        }
+        Ok(DocumentDiagnosticReportResult::Report(store.report(
+            FileID::from_uri(uri),
+            params.previous_result_id.as_deref(),
+        )))
+    }
+    async fn workspace_diagnostic(
+        &self,
+        params: WorkspaceDiagnosticParams,
+    ) -> Result<WorkspaceDiagnosticReportResult> {
+        let root = self
+            .pipeline
+            .sync_root_global()
+            .await
+            .map_err(|_| shutdown_error())?;
+        let store = self.pipeline.diagnostics();
+        store
+            .sync(root.revision())
+            .await
+            .map_err(|_| shutdown_error())?;
+        let previous: HashMap<_, _> = params
+            .previous_result_ids
+            .into_iter()
+            .map(|id| (id.uri, id.value))
+            .collect();
+        let items = root
+            .files
+            .keys()
+            .chain(root.configs.keys())
+            .filter(|file| !file.is_virtual())
+            .map(|file| {
+                let uri = file.url();
+                let version = None;
+                match store.report(*file, previous.get(&uri).map(String::as_str)) {
+                    DocumentDiagnosticReport::Full(report) => {
+                        WorkspaceDocumentDiagnosticReport::Full(
+                            WorkspaceFullDocumentDiagnosticReport {
+                                uri,
+                                version,
+                                full_document_diagnostic_report: report
+                                    .full_document_diagnostic_report,
+                            },
+                        )
+                    }
+                    DocumentDiagnosticReport::Unchanged(report) => {
+                        WorkspaceDocumentDiagnosticReport::Unchanged(
+                            WorkspaceUnchangedDocumentDiagnosticReport {
+                                uri,
+                                version,
+                                unchanged_document_diagnostic_report: report
+                                    .unchanged_document_diagnostic_report,
+                            },
+                        )
+                    }
+                }
+            })
+            .collect();
+        Ok(WorkspaceDiagnosticReportResult::Report(
+            WorkspaceDiagnosticReport { items },
+        ))
+    }
     async fn semantic_tokens_full(
         &self,
         params: SemanticTokensParams,
@@ -337,6 +892,9 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::INFO, "file closed!")
             .await;
+        self.pipeline
+            .scheduler()
+            .remove_focus(FileID::from_uri(&params.text_document.uri));
         self.pipeline
             .delete(&params.text_document.uri, DocumentState::OwnedByEditor)
             .await;
@@ -344,7 +902,22 @@ impl LanguageServer for Backend {
     }
     async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
         info!("file change {:?}", params);
+        let folders: Vec<PathBuf> = self
+            .folders
+            .lock()
+            .iter()
+            .filter_map(|f| f.to_file_path().ok())
+            .collect();
+        let settings = settings();
         for i in params.changes {
+            if i.typ != FileChangeType::DELETED
+                && i.uri
+                    .to_file_path()
+                    .map(|path| workspace::is_ignored(&path, &folders, &settings))
+                    .unwrap_or(false)
+            {
+                continue;
+            }
             match i.typ {
                 FileChangeType::CREATED => {
                     self.load(i.uri);
@@ -372,13 +945,19 @@ impl LanguageServer for Backend {
         }
         match params.command.as_str() {
             "uvls/load_config" => {
-                let target = format!("{}/load{}", self.web_handler_uri, uri.path());
+                let Some(web_handler_uri) = self.web_handler_uri() else {
+                    return Err(web_disabled_error());
+                };
+                let target = format!("{}/load{}", web_handler_uri, uri.path());
                 let response: serde_json::Value =
                     serde_json::to_value(OpenArgs { uri: target }).unwrap();
                 return Ok(Some(response));
             }
             "uvls/open_config" => {
-                let target = format!("{}/create{}", self.web_handler_uri, uri.path());
+                let Some(web_handler_uri) = self.web_handler_uri() else {
+                    return Err(web_disabled_error());
+                };
+                let target = format!("{}/create{}", web_handler_uri, uri.path());
                 info!("{}", target);
                 let response: serde_json::Value =
                     serde_json::to_value(OpenArgs { uri: target }).unwrap();
@@ -449,32 +1028,19 @@ impl LanguageServer for Backend {
 
                 let smt_module = uvl2smt(&module, &HashMap::new());
 
-                let solver = SmtSolver::new(
-                    smt_module.to_source(&module),
-                    &root_graph.cancellation_token(),
-                )
-                .await;
+                let solver =
+                    new_backend(&smt_module, &module, &root_graph.cancellation_token()).await;
 
                 match solver {
                     Ok(mut smt_solver) => match smt_solver.check_sat().await {
-                        Ok(true) => {
-                            let query = smt_module
-                                .variables
-                                .iter()
-                                .enumerate()
-                                .fold(String::new(), |acc, (i, _)| format!("{acc} v{i}"));
+                        Ok(SatResult::Sat) => {
                             // generate the unique n solutions
                             for i in 1..=n {
-                                if !smt_solver.check_sat().await.unwrap_or(false) {
+                                if !matches!(smt_solver.check_sat().await, Ok(SatResult::Sat)) {
                                     break; // no more solutions
                                 }
-                                let values = smt_solver
-                                    .values(query.clone())
-                                    .await
-                                    .unwrap_or(String::from(""));
-
                                 let values_parsed: HashMap<ModuleSymbol, ConfigValue> =
-                                    smt_module.parse_values(&values, &module).collect();
+                                    smt_solver.model(&smt_module).await.unwrap_or_default();
 
                                 // Store solution in file
                                 let config_module = ConfigModule {
@@ -541,13 +1107,7 @@ impl LanguageServer for Backend {
                                         },
                                     );
 
-                                    let assertion_string = smt_module.assert_to_source(
-                                        0,
-                                        &None,
-                                        &Expr::Or(assertion.clone()),
-                                        false,
-                                    );
-                                    let _ = smt_solver.push(assertion_string).await;
+                                    let _ = smt_solver.assert(&Expr::Or(assertion)).await;
                                 }
                             }
                         }
@@ -676,25 +1236,52 @@ impl LanguageServer for Backend {
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
-        for diagnostic in params.clone().context.diagnostics {
-            // Checks if there is a quick fix for the current diagnostic message
-            match diagnostic.clone().data {
-                Some(serde_json::value::Value::Number(number)) => {
-                    match ErrorType::from_u32(number.as_u64().unwrap_or(0) as u32) {
-                        ErrorType::Any => info!("No Quickfix for this Error"),
-                        ErrorType::FeatureNameContainsDashes => {
-                            return actions::rename_dash(
-                                params.clone(),
-                                diagnostic,
-                                self.snapshot(&params.text_document.uri, false).await,
-                            )
-                        }
-                    }
+        //Quick fixes are looked up by diagnostic code in the registry of core::actions
+        let fixable: Vec<_> = params
+            .context
+            .diagnostics
+            .iter()
+            .map(|d| (d, actions::quick_fixes(ErrorType::from_diagnostic(d))))
+            .filter(|(_, fixes)| !fixes.is_empty())
+            .collect();
+        //Fixes need the linked state the diagnostics were computed on
+        let sync = !fixable.is_empty();
+        let (draft, root) = match self.snapshot(&params.text_document.uri, sync).await? {
+            Some(snapshot) => snapshot,
+            None => return Ok(None),
+        };
+        let mut actions: Vec<_> = fixable
+            .into_iter()
+            .flat_map(|(diagnostic, fixes)| {
+                fixes
+                    .iter()
+                    .flat_map(|fix| fix(&params, diagnostic, &draft, &root))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        actions.extend(actions::refactorings(&params, &draft, &root));
+        //Without resolve support the edits of refactorings are computed right away
+        if !self.client_state.lock().has_resolve {
+            for action in actions.iter_mut() {
+                if let CodeActionOrCommand::CodeAction(action) = action {
+                    *action = actions::resolve_refactoring(action.clone(), &root);
                 }
-                _ => (),
             }
         }
-        return Ok(None);
+        if actions.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(actions))
+        }
+    }
+    async fn code_action_resolve(&self, params: CodeAction) -> Result<CodeAction> {
+        let Some(uri) = actions::refactoring_uri(&params) else {
+            return Ok(params);
+        };
+        match self.snapshot(&uri, true).await? {
+            Some((_, root)) => Ok(actions::resolve_refactoring(params, &root)),
+            None => Ok(params),
+        }
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -742,18 +1329,10 @@ async fn server_main() {
     let (service, socket) = LspService::new(|client| {
         let pipeline = AsyncPipeline::new(client.clone());
         info!("create service");
-        let port = get_port::tcp::TcpPort::in_range(
-            "127.0.0.1",
-            get_port::Range {
-                min: 3000,
-                max: 6000,
-            },
-        )
-        .unwrap();
-        spawn(webview::web_handler(pipeline.clone(), port));
         Backend {
-            settings: parking_lot::Mutex::new(Settings::default()),
-            web_handler_uri: format!("http://localhost:{port}"),
+            client_state: parking_lot::Mutex::new(ClientState::default()),
+            web_handler: parking_lot::Mutex::new(None),
+            folders: parking_lot::Mutex::new(Vec::new()),
             pipeline,
             coloring: Arc::new(ide::color::State::new()),
             client,
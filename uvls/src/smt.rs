@@ -3,35 +3,40 @@ use crate::{
     ide::inlays::{InlayHandler, InlaySource},
     webview,
 };
+use dashmap::DashMap;
 use futures::future::join_all;
 use hashbrown::{HashMap, HashSet};
 
-use lazy_static::lazy_static;
 use log::info;
 
-use std::fmt::Write;
 use std::sync::Arc;
 use tokio::{
-    io::Lines,
-    process::{ChildStdin, ChildStdout, Command},
+    select,
     sync::{mpsc, watch},
     time::Instant,
 };
-use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter},
-    process::Child,
-    spawn,
-};
 
 use tokio_util::sync::CancellationToken;
 use tower_lsp::lsp_types::*;
+mod backbone;
+pub mod backend;
+mod embedded;
 mod parse;
+mod process;
+mod sat;
+mod session;
 pub mod smt_lib;
+use backbone::backbone;
+pub use backend::*;
+pub use process::can_run_solver;
+use session::Session;
 pub use smt_lib::*;
+//core also exports an Expr, the SMT one is meant here
+use smt_lib::Expr;
 
-//SMT semantic analysis with Z3, communication with the solver happens over stdio and SMT-LIB2.
-//While the performance is worse than linking with Z3, we are solver independent and don't have to interact
-//with any C-Bindings. UVL is translated directly into SMT-LIB, both attributes and features are treated as
+//SMT semantic analysis, the solvers are behind the SolverBackend trait. Boolean models are decided
+//in process, everything else with a solver process like Z3 that talks SMT-LIB2.
+//UVL is translated directly into SMT-LIB, both attributes and features are treated as
 //free variables. The rest is encoded in named asserts, this allows to get a accurate unsat core.
 //Eg. each attribute is restricted with an assert that allows it to either be its defined value or 0 depending
 //on the parent feature value.
@@ -40,115 +45,10 @@ pub use smt_lib::*;
 //Variables are named as v{n} where n is an index into a lookup table of UVL ModuleSymbols
 //Asserts are encoded similarly as a{n} where n is and index into a list of naming information
 //that links uvl expression to asserts.
-pub struct SmtSolver {
-    _proc: Child,
-    stdin: BufWriter<ChildStdin>,
-    stdout: Lines<BufReader<ChildStdout>>,
-    cancel: CancellationToken,
-}
-impl SmtSolver {
-    pub async fn new(model: String, cancel: &CancellationToken) -> Result<Self> {
-        let mut proc = Command::new("z3")
-            .arg("-in")
-            .arg("-smt2")
-            .stdin(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .kill_on_drop(true)
-            .spawn()?;
-
-        let mut stdin = BufWriter::new(proc.stdin.take().unwrap());
-        let stdout = BufReader::new(proc.stdout.take().unwrap()).lines();
-        let mut stderr = BufReader::new(proc.stderr.take().unwrap()).lines();
-        spawn(async move {
-            while let Some(err) = stderr.next_line().await? {
-                info!("smt error {:?}", err);
-            }
-            return tokio::io::Result::Ok(());
-        });
-        maybe_cancel(&cancel, stdin.write_all(model.as_bytes())).await??;
-        stdin.flush().await?;
-        Ok(SmtSolver {
-            cancel: cancel.clone(),
-            _proc: proc,
-            stdin,
-            stdout,
-        })
-    }
-
-    pub async fn read_block(&mut self) -> Result<String> {
-        let mut out = String::new();
-        let mut nesting = 0;
-        while let Some(line) = maybe_cancel(&self.cancel, self.stdout.next_line()).await?? {
-            let _ = write!(out, " {}", line);
-            for i in line.chars() {
-                match i {
-                    '(' => nesting += 1,
-                    ')' => nesting -= 1,
-                    _ => {}
-                }
-            }
-            if nesting == 0 {
-                break;
-            }
-        }
-        Ok(out)
-    }
-    pub async fn check_sat(&mut self) -> Result<bool> {
-        self.stdin.write_all("(check-sat)\n".as_bytes()).await?;
-        self.stdin.flush().await?;
-        let ret = maybe_cancel(&self.cancel, self.stdout.next_line())
-            .await??
-            .ok_or("failed to get line")?;
-        match ret.as_str() {
-            "sat" => Ok(true),
-            "unsat" => Ok(false),
-            s => Err(format!("Bad response {s}"))?,
-        }
-    }
-    pub async fn unsat_core(&mut self) -> Result<String> {
-        self.stdin
-            .write_all("(get-unsat-core)\n".as_bytes())
-            .await?;
-        self.stdin.flush().await?;
-        self.read_block().await
-    }
-    pub async fn push(&mut self, cmd: String) -> Result<()> {
-        self.stdin.write_all(cmd.as_bytes()).await?;
-        self.stdin.flush().await?;
-        Ok(())
-    }
-
-    #[allow(dead_code)]
-    pub async fn model(&mut self) -> Result<String> {
-        self.stdin.write_all(b"(get-model)\n").await?;
-        self.stdin.flush().await?;
-        self.read_block().await
-    }
-    pub async fn values(&mut self, active: String) -> Result<String> {
-        if active.is_empty() {
-            return Ok(String::new());
-        }
-        let query = format!("(get-value ({active}))\n");
-        self.stdin.write_all(query.as_bytes()).await?;
-        self.stdin.flush().await?;
-        self.read_block().await
-    }
-}
-pub fn can_run_z3() -> bool {
-    Command::new("z3")
-        .stdin(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .spawn()
-        .is_ok()
-}
+//Editor owned modules keep a solver Session between checks, so an edit only changes the asserts
+//that differ from the previous check.
 
-lazy_static! {
-    static ref HAS_Z3: bool = can_run_z3();
-}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SMTValueState {
     Any,
     On,
@@ -163,6 +63,21 @@ pub enum SMTModel {
     UNSAT {
         reasons: Vec<AssertInfo>,
     },
+    //the solver hit a limit or gave up
+    UNKNOWN,
+}
+//Dead and core features of the latest base model check of each module, keyed by the root file
+//of the module. A file imported by several modules has an entry in each of them.
+pub type FixedState = Arc<DashMap<FileID, HashMap<RootSymbol, SMTValueState>>>;
+//State of sym in the module rooted at its own file, otherwise the state all modules using it
+//agree on
+pub fn fixed_value(fixed: &FixedState, sym: &RootSymbol) -> Option<SMTValueState> {
+    if let Some(val) = fixed.get(&sym.file).and_then(|m| m.get(sym).cloned()) {
+        return Some(val);
+    }
+    let mut states = fixed.iter().filter_map(|m| m.get(sym).cloned());
+    let first = states.next()?;
+    states.all(|s| s == first).then_some(first)
 }
 pub struct OwnedSMTModel {
     pub model: SMTModel,
@@ -176,130 +91,82 @@ impl Default for SMTModel {
         }
     }
 }
-//find constant boolean values for dead features and other cool analysis
-//this is quite naive and should be improved with a better solver
+//find constant boolean values for dead features and core features, the backbone of the module
 async fn find_fixed(
-    solve: &mut SmtSolver,
+    solve: &mut dyn SolverBackend,
     base_module: &Module,
     module: &SMTModule,
     initial_model: impl Iterator<Item = (ModuleSymbol, ConfigValue)>,
     cancel: CancellationToken,
 ) -> Result<HashMap<ModuleSymbol, SMTValueState>> {
+    let model = initial_model
+        .filter_map(|(s, v)| match v {
+            ConfigValue::Bool(value) => Some((module.var(s), value)),
+            _ => None,
+        })
+        .collect();
+    let (fixed, free) = backbone(solve, model).await?;
     let mut state = HashMap::new();
-    for (s, v) in initial_model {
-        match v {
-            ConfigValue::Bool(true) => {
-                state.insert(s, SMTValueState::On);
-            }
-            ConfigValue::Bool(false) => {
-                state.insert(s, SMTValueState::Off);
-            }
-            _ => {}
-        }
+    for (i, value) in fixed {
+        match value {
+            true => state.insert(module.variables[i], SMTValueState::On),
+            false => state.insert(module.variables[i], SMTValueState::Off),
+        };
     }
-    let keys: Vec<ModuleSymbol> = state.keys().cloned().collect();
-    for k in keys {
-        match &state[&k] {
-            SMTValueState::Any => {
-                continue;
-            }
-            SMTValueState::On => {
-                solve
-                    .push(format!(
-                        "(push 1)(assert (not {}))",
-                        module.pseudo_bool(k, base_module)
-                    ))
-                    .await?;
-            }
-            SMTValueState::Off => {
-                solve
-                    .push(format!(
-                        "(push 1)(assert {})",
-                        module.pseudo_bool(k, base_module)
-                    ))
-                    .await?;
-            }
-        }
-        //let time = Instant::now();
-        if solve.check_sat().await? {
-            let unknown = state
-                .iter()
-                .filter(|(_, v)| !matches!(*v, SMTValueState::Any))
-                .fold(String::new(), |acc, (k, _)| {
-                    format!("{acc} v{}", module.var(*k))
-                });
-            let values = solve.values(unknown).await?;
-            for (s, v) in module.parse_values(&values, base_module) {
-                if let Some(old) = state.get(&s) {
-                    match (v, old) {
-                        (ConfigValue::Bool(true), SMTValueState::Off) => {
-                            state.insert(s, SMTValueState::Any);
-                        }
-                        (ConfigValue::Bool(false), SMTValueState::On) => {
-                            state.insert(s, SMTValueState::Any);
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        }
-        solve.push("(pop 1)".into()).await?;
+    //only proven values are reported
+    for i in free {
+        state.insert(module.variables[i], SMTValueState::Any);
     }
     //check if a constraint is a tautologie
 
     // load in the module all variable and all constraints as Asserts
     let smt_module_constraint = uvl2smt_constraints(&base_module);
-    // create source for smtsolver, but only with the variable
-    let mut source_variable = smt_module_constraint.config_to_source();
-    let _ = writeln!(
-        source_variable,
-        "{}",
-        smt_module_constraint.variable_to_source(&base_module)
-    );
-    //create SMTSolver for the constraints
-    let mut solver_constraint = SmtSolver::new(source_variable, &cancel).await?;
-    for (i, Assert(info, expr)) in smt_module_constraint.asserts.iter().enumerate() {
-        //get the negated constraint source
-        let constraint_assert = smt_module_constraint.assert_to_source(i, info, expr, true);
+    // create a solver for the constraints, but only with the variables
+    let variables = SMTModule {
+        variables: smt_module_constraint.variables.clone(),
+        asserts: Vec::new(),
+    };
+    let mut solver_constraint =
+        create_backend(&smt_module_constraint, base_module, &cancel).await?;
+    solver_constraint.load(&variables, base_module).await?;
+    for Assert(info, expr) in smt_module_constraint.asserts.iter() {
         //push negated constraint
+        solver_constraint.push().await?;
         solver_constraint
-            .push(format!("(push 1) {}", constraint_assert))
+            .assert(&Expr::Not(expr.clone().into()))
             .await?;
         //check if negated constraint is unsat
         let sat = solver_constraint.check_sat().await?;
-        if !sat {
+        if sat == SatResult::Unsat {
             let module_symbol = info.clone().unwrap().0;
             state.insert(module_symbol, SMTValueState::On);
         }
         //pop negated constraint
-        solver_constraint.push("(pop 1)".into()).await?;
+        solver_constraint.pop().await?;
     }
 
     Ok(state)
 }
 async fn create_model(
+    solver: &mut dyn SolverBackend,
     base_module: &Module,
     cancel: CancellationToken,
     module: SMTModule,
-    source: String,
     fixed: bool,
     value: bool,
 ) -> Result<SMTModel> {
     let time = Instant::now();
-    let mut solver = SmtSolver::new(source, &cancel).await?;
+    solver.set_cancel(&cancel);
+    solver.load(&module, base_module).await?;
     info!("create model: {:?}", time.elapsed());
-    if solver.check_sat().await? {
+    let sat = solver.check_sat().await?;
+    if sat == SatResult::Unknown {
+        return Ok(SMTModel::UNKNOWN);
+    }
+    if sat == SatResult::Sat {
         let values = if value | fixed {
-            let query = module
-                .variables
-                .iter()
-                .enumerate()
-                .fold(String::new(), |acc, (i, _)| format!("{acc} v{i}"));
-
-            let values = solver.values(query).await?;
-
             let time = Instant::now();
-            let values = module.parse_values(&values, base_module).collect();
+            let values = solver.model(&module).await?;
             info!("parse values: {:?}", time.elapsed());
             values
         } else {
@@ -308,7 +175,7 @@ async fn create_model(
         Ok(SMTModel::SAT {
             fixed: if fixed {
                 find_fixed(
-                    &mut solver,
+                    solver,
                     base_module,
                     &module,
                     values.iter().map(|(k, v)| (*k, v.clone())),
@@ -323,55 +190,109 @@ async fn create_model(
     } else {
         let core = solver.unsat_core().await?;
         Ok(SMTModel::UNSAT {
-            reasons: module.parse_unsat_core(&core).collect(),
+            reasons: core
+                .into_iter()
+                .filter_map(|i| module.asserts[i].0.clone())
+                .collect(),
         })
     }
 }
 
+//Background checks are dropped after the configured timeout, this also kills the solver
+async fn with_timeout(
+    check: impl std::future::Future<Output = Result<SMTModel>>,
+) -> Result<SMTModel> {
+    match settings().smt.timeout {
+        0 => check.await,
+        ms => tokio::time::timeout(tokio::time::Duration::from_millis(ms), check)
+            .await
+            .unwrap_or(Ok(SMTModel::UNKNOWN)),
+    }
+}
+//Message for checks that neither found a model nor a proof
+pub fn inconclusive() -> String {
+    match settings().smt.timeout {
+        0 => "analysis inconclusive".into(),
+        ms if ms % 1000 == 0 => format!("analysis inconclusive within {}s", ms / 1000),
+        ms => format!("analysis inconclusive within {ms}ms"),
+    }
+}
+//A session that was interrupted may be out of sync with its solver
+fn keep_session(model: &Result<SMTModel>) -> bool {
+    matches!(model, Ok(SMTModel::SAT { .. } | SMTModel::UNSAT { .. }))
+}
 async fn check_base_sat(
     root: &RootGraph,
     tx_err: &mpsc::Sender<DiagnosticUpdate>,
-    latest_revisions: HashMap<FileID, Instant>,
+    fixed_state: &FixedState,
+    mut latest_revisions: HashMap<FileID, Instant>,
+    sessions: &mut HashMap<FileID, Session>,
+    select: impl Fn(&Module) -> bool,
 ) -> HashMap<FileID, Instant> {
-    let active = root.cache().modules.iter().filter(|(k, v)| {
-        latest_revisions
-            .get(*k)
-            .map(|old| old != &v.timestamp)
-            .unwrap_or(true)
-            && v.ok
-    });
-    let models = join_all(active.map(|(_, v)| {
-        let module = v.clone();
-        async move {
-            let smt_module = uvl2smt(&module, &HashMap::new());
-            let source = smt_module.to_source(&module);
-            let model = create_model(
-                &module,
-                root.cancellation_token(),
-                smt_module,
-                source,
-                true,
-                false,
-            )
-            .await;
-            model.map(|m| (m, module))
-        }
-    }))
+    //modules that were removed or have errors are no longer checked
+    fixed_state.retain(|k, _| root.cache().modules.get(k).map(|m| m.ok).unwrap_or(false));
+    let active: Vec<_> = root
+        .cache()
+        .modules
+        .iter()
+        .filter(|(k, v)| {
+            latest_revisions
+                .get(*k)
+                .map(|old| old != &v.timestamp)
+                .unwrap_or(true)
+                && v.ok
+                && select(v)
+        })
+        .map(|(k, v)| (*k, v.clone(), sessions.remove(k).unwrap_or_default()))
+        .collect();
+    let models = join_all(
+        active
+            .into_iter()
+            .map(|(k, module, mut session)| async move {
+                let smt_module = uvl2smt(&module, &HashMap::new());
+                let model = with_timeout(create_model(
+                    &mut session,
+                    &module,
+                    root.cancellation_token(),
+                    smt_module,
+                    true,
+                    false,
+                ))
+                .await;
+                let session = keep_session(&model).then_some(session);
+                (k, session, model.map(|m| (m, module)))
+            }),
+    )
     .await;
 
     let mut e = ErrorsAcc::new(root);
-    for k in models.into_iter() {
+    for (file, session, k) in models.into_iter() {
+        if let Some(session) = session {
+            sessions.insert(file, session);
+        }
+        //only a model gives fixed values, anything else clears them
+        if !matches!(k, Ok((SMTModel::SAT { .. }, _))) {
+            fixed_state.remove(&file);
+        }
         match k {
             Ok((SMTModel::SAT { fixed, .. }, module)) => {
                 let mut visited = HashSet::new();
+                let mut state = HashMap::new();
                 for (m, file) in module.instances() {
                     file.visit_children(Symbol::Root, true, |sym| match sym {
                         Symbol::Feature(..) => {
                             if let Some(val) = fixed.get(&m.sym(sym)) {
+                                state.insert(RootSymbol { file: file.id, sym }, val.clone());
                                 match val {
                                     SMTValueState::Off => {
                                         if visited.insert((sym, file.id)) {
-                                            e.sym_info(sym, file.id, 10, "dead feature");
+                                            e.sym_info(
+                                                sym,
+                                                file.id,
+                                                10,
+                                                ErrorType::DeadFeature,
+                                                "dead feature",
+                                            );
                                         }
                                         false
                                     }
@@ -391,7 +312,13 @@ async fn check_base_sat(
                                 match val {
                                     SMTValueState::On => {
                                         if visited.insert((sym, file.id)) {
-                                            e.sym_info(sym, file.id, 10, "TAUT: constraint");
+                                            e.sym_info(
+                                                sym,
+                                                file.id,
+                                                10,
+                                                ErrorType::RedundantConstraint,
+                                                "TAUT: constraint",
+                                            );
                                         }
                                         false
                                     }
@@ -404,6 +331,7 @@ async fn check_base_sat(
                         _ => false,
                     })
                 }
+                fixed_state.insert(file, state);
             }
             Ok((SMTModel::UNSAT { reasons }, module)) => {
                 let mut visited = HashSet::new();
@@ -413,14 +341,46 @@ async fn check_base_sat(
                     if !void_is_marked {
                         // works only if keyword feature is the only keyword stored in the Keyword vector in the AST, but since I see no reason
                         // why another keyword is needed in the green tree, so the features keyword would always have id 0.
-                        e.sym(Symbol::Keyword(0), file, 12, "void feature model");
+                        e.sym(
+                            Symbol::Keyword(0),
+                            file,
+                            12,
+                            ErrorType::VoidModel,
+                            "void feature model",
+                        );
                         void_is_marked = true;
                     }
                     if visited.insert((r.0.sym, file)) {
-                        e.sym(r.0.sym, file, 12, format!("UNSAT: {}", r.1))
+                        e.sym(
+                            r.0.sym,
+                            file,
+                            12,
+                            ErrorType::UnsatConstraint,
+                            format!("UNSAT: {}", r.1),
+                        )
                     }
                 }
             }
+            Ok((SMTModel::UNKNOWN, module)) => {
+                let file = module.file(InstanceID(0));
+                if file.lsp_range(Symbol::Keyword(0)).is_some() {
+                    e.sym_info(
+                        Symbol::Keyword(0),
+                        file.id,
+                        10,
+                        ErrorType::AnalysisInconclusive,
+                        inconclusive(),
+                    );
+                } else {
+                    e.span_info(
+                        0..0,
+                        file.id,
+                        10,
+                        ErrorType::AnalysisInconclusive,
+                        inconclusive(),
+                    );
+                }
+            }
             Err(e) => {
                 info!("SMT check failed: {e}");
             }
@@ -432,11 +392,12 @@ async fn check_base_sat(
             error_state: e.errors,
         })
         .await;
-    root.cache()
-        .modules
-        .iter()
-        .map(|(k, v)| (*k, v.timestamp))
-        .collect()
+    for (k, v) in root.cache().modules.iter() {
+        if select(v) {
+            latest_revisions.insert(*k, v.timestamp);
+        }
+    }
+    latest_revisions
 }
 
 async fn check_config(
@@ -444,6 +405,7 @@ async fn check_config(
     tx_err: &mpsc::Sender<DiagnosticUpdate>,
     inlay_state: &InlayHandler,
     latest_revisions: HashMap<FileID, Instant>,
+    sessions: &mut HashMap<FileID, Session>,
 ) -> HashMap<FileID, Instant> {
     //Reset inlays
     for (k, v) in latest_revisions.iter() {
@@ -458,51 +420,60 @@ async fn check_config(
             inlay_state.maybe_reset(InlaySource::File(*k)).await;
         }
     }
-    let active = root.cache().config_modules.iter().filter(|(k, v)| {
-        latest_revisions
-            .get(*k)
-            .map(|old| old != &v.module.timestamp)
-            .unwrap_or(true)
-            && v.module.ok
-            && k.is_config()
-    });
-    let models = join_all(active.map(|(k, v)| {
-        let k = k.clone();
-        let module = v.clone();
-        async move {
-            info!("checking {k:?}");
-            let smt_module = uvl2smt(&module, &module.values);
-            let source = smt_module.to_source(&module);
-            let is_active = inlay_state.is_active(InlaySource::File(k));
-            let model = create_model(
-                &module.module,
-                root.cancellation_token(),
-                smt_module,
-                source,
-                !k.is_config(),
-                is_active,
-            )
-            .await;
-            if let Ok(model) = model.as_ref() {
-                inlay_state
-                    .maybe_publish(InlaySource::File(k), Instant::now(), || {
-                        Arc::new(OwnedSMTModel {
-                            model: model.clone(),
-                            module: module.module.clone(),
+    let active: Vec<_> = root
+        .cache()
+        .config_modules
+        .iter()
+        .filter(|(k, v)| {
+            latest_revisions
+                .get(*k)
+                .map(|old| old != &v.module.timestamp)
+                .unwrap_or(true)
+                && v.module.ok
+                && k.is_config()
+        })
+        .map(|(k, v)| (*k, v.clone(), sessions.remove(k).unwrap_or_default()))
+        .collect();
+    let models = join_all(
+        active
+            .into_iter()
+            .map(|(k, module, mut session)| async move {
+                info!("checking {k:?}");
+                let smt_module = uvl2smt(&module, &module.values);
+                let is_active = inlay_state.is_active(InlaySource::File(k));
+                let model = with_timeout(create_model(
+                    &mut session,
+                    &module.module,
+                    root.cancellation_token(),
+                    smt_module,
+                    !k.is_config(),
+                    is_active,
+                ))
+                .await;
+                if let Ok(model) = model.as_ref() {
+                    inlay_state
+                        .maybe_publish(InlaySource::File(k), Instant::now(), || {
+                            Arc::new(OwnedSMTModel {
+                                model: model.clone(),
+                                module: module.module.clone(),
+                            })
                         })
-                    })
-                    .await;
-            } else {
-                inlay_state.maybe_reset(InlaySource::File(k)).await;
-            }
+                        .await;
+                } else {
+                    inlay_state.maybe_reset(InlaySource::File(k)).await;
+                }
 
-            model.map(|m| (m, k, module))
-        }
-    }))
+                let session = keep_session(&model).then_some(session);
+                (session, model.map(|m| (m, k, module)))
+            }),
+    )
     .await;
 
     let mut e = ErrorsAcc::new(root);
-    for k in models.into_iter() {
+    for (session, k) in models.into_iter() {
+        if let (Some(session), Ok((_, file, _))) = (session, k.as_ref()) {
+            sessions.insert(*file, session);
+        }
         match k {
             Ok((SMTModel::SAT { .. }, ..)) => {
                 //Do something?
@@ -514,11 +485,21 @@ async fn check_config(
                             module.source_map[&r.0].clone(),
                             root_file,
                             12,
+                            ErrorType::InvalidConfig,
                             format!("UNSAT!"),
                         );
                     }
                 }
             }
+            Ok((SMTModel::UNKNOWN, root_file, _)) => {
+                e.span_info(
+                    0..0,
+                    root_file,
+                    10,
+                    ErrorType::AnalysisInconclusive,
+                    inconclusive(),
+                );
+            }
             Err(e) => {
                 info!("SMT check failed: {e}");
             }
@@ -543,27 +524,97 @@ pub async fn check_handler(
     tx_err: mpsc::Sender<DiagnosticUpdate>,
     client: tower_lsp::Client,
     inlay_state: InlayHandler,
+    fixed_state: FixedState,
+    scheduler: Scheduler,
 ) {
-    if !can_run_z3() {
-        client
-            .send_notification::<tower_lsp::lsp_types::notification::ShowMessage>(
-                ShowMessageParams {
-                    typ: MessageType::INFO,
-                    message: "UVLS: Z3 was not found on you're system. It is required for semantic analysis".into(),
-                },
-            )
-            .await;
-    }
+    let mut rx_settings = watch_settings();
     let mut latest_versions: HashMap<FileID, Instant> = HashMap::new();
     let mut latest_versions_config: HashMap<FileID, Instant> = HashMap::new();
+    //solver sessions of editor owned modules and configs, they are reused for the next edit
+    let mut sessions: HashMap<FileID, Session> = HashMap::new();
+    let mut sessions_config: HashMap<FileID, Session> = HashMap::new();
+    //the solver is probed once per settings change instead of on every relink
+    let mut probed = false;
     loop {
-        info!("Check SMT");
-        let root = rx_root.borrow_and_update().clone();
-        latest_versions = check_base_sat(&root, &tx_err, latest_versions).await;
-        latest_versions_config =
-            check_config(&root, &tx_err, &inlay_state, latest_versions_config).await;
-        if rx_root.changed().await.is_err() {
-            break;
+        let smt = rx_settings.borrow_and_update().smt.clone();
+        if smt.enabled {
+            if !probed && smt.backend != BackendKind::Embedded {
+                probed = true;
+                if !can_run_solver() {
+                    client
+                    .send_notification::<tower_lsp::lsp_types::notification::ShowMessage>(
+                        ShowMessageParams {
+                            typ: MessageType::INFO,
+                            message: format!("UVLS: {} was not found on you're system. It is required for semantic analysis of models with Real or String values", smt.solver_path),
+                        },
+                    )
+                    .await;
+                }
+            }
+            info!("Check SMT");
+            let root = rx_root.borrow_and_update().clone();
+            //modules with editor owned documents first, the rest only if the root is still current
+            let focus = |module: &Module| module.files.keys().any(|f| scheduler.is_focus(*f));
+            //a session lives as long as its module has an editor owned document
+            let owned = |module: &Module| module.files.keys().any(|f| scheduler.is_owned(*f));
+            sessions.retain(|k, _| root.cache().modules.get(k).is_some_and(|m| owned(m)));
+            sessions_config.retain(|k, _| {
+                root.cache().config_modules.contains_key(k) && scheduler.is_owned(*k)
+            });
+            latest_versions = check_base_sat(
+                &root,
+                &tx_err,
+                &fixed_state,
+                latest_versions,
+                &mut sessions,
+                focus,
+            )
+            .await;
+            if !rx_root.has_changed().unwrap_or(true) {
+                //the rest of the workspace does not keep a solver alive
+                latest_versions = check_base_sat(
+                    &root,
+                    &tx_err,
+                    &fixed_state,
+                    latest_versions,
+                    &mut HashMap::new(),
+                    |module| !focus(module),
+                )
+                .await;
+                latest_versions_config = check_config(
+                    &root,
+                    &tx_err,
+                    &inlay_state,
+                    latest_versions_config,
+                    &mut sessions_config,
+                )
+                .await;
+            }
+        }
+        select! {
+            changed = rx_root.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+            }
+            Ok(()) = rx_settings.changed() => {
+                //another solver may give different results, so everything is checked again
+                let settings = rx_settings.borrow().smt.clone();
+                //the linker republishes the diagnostics without solver results
+                if smt.enabled && !settings.enabled {
+                    for k in latest_versions_config.keys() {
+                        inlay_state.maybe_reset(InlaySource::File(*k)).await;
+                    }
+                    fixed_state.clear();
+                }
+                if settings != smt {
+                    probed = false;
+                    latest_versions.clear();
+                    latest_versions_config.clear();
+                    sessions.clear();
+                    sessions_config.clear();
+                }
+            }
         }
     }
 }
@@ -573,6 +624,8 @@ pub async fn web_view_handler(
     inlay_state: InlayHandler,
     inlay_source: InlaySource,
 ) -> Result<()> {
+    //each change of the configuration only touches a few asserts
+    let mut session = Session::default();
     loop {
         let (module, cancel, tag, config_ok) = {
             let lock = state.borrow_and_update();
@@ -582,8 +635,10 @@ pub async fn web_view_handler(
 
         if module.ok && config_ok {
             let smt_module = uvl2smt(&module, &module.values);
-            let source = smt_module.to_source(&module);
-            let res = create_model(&module, cancel, smt_module, source, false, true).await;
+            let res = create_model(&mut session, &module, cancel, smt_module, false, true).await;
+            if !keep_session(&res) {
+                session = Session::default();
+            }
             match res {
                 Ok(model) => {
                     inlay_state
@@ -618,3 +673,310 @@ pub async fn web_view_handler(
         state.changed().await?;
     }
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded::Embedded;
+    use process::SmtSolver;
+    use ropey::Rope;
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///ws/{}", name)).unwrap()
+    }
+    pub(super) fn graph(files: &[(&str, &str)]) -> RootGraph {
+        let mut ast = HashMap::new();
+        let mut configs = HashMap::new();
+        for (name, text) in files {
+            let source = Rope::from_str(text);
+            if name.ends_with(".json") {
+                let tree = crate::core::parse::parse_json(&source, None);
+                let doc = crate::core::config::parse_json(tree, source, uri(name), Instant::now());
+                configs.insert(doc.id, Arc::new(doc));
+            } else {
+                let tree = crate::core::parse::parse(&source, None);
+                let doc = AstDocument::new(source, tree, uri(name), Instant::now());
+                ast.insert(doc.id, Arc::new(doc));
+            }
+        }
+        let mut err = ErrorsAcc {
+            errors: HashMap::new(),
+            files: &ast,
+            configs: &configs,
+        };
+        RootGraph::new(
+            &ast,
+            &configs,
+            &[],
+            0,
+            &Cache::default(),
+            &mut err,
+            &mut HashMap::new(),
+        )
+    }
+    pub(super) fn module(root: &RootGraph, name: &str) -> Arc<Module> {
+        root.cache().modules[&FileID::from_uri(&uri(name))].clone()
+    }
+    fn name(module: &Module, sym: &ModuleSymbol) -> String {
+        module
+            .file(sym.instance)
+            .name(sym.sym)
+            .map(|n| n.to_string())
+            .unwrap_or_default()
+    }
+    //Fixed features by name
+    fn fixed(module: &Module, model: &SMTModel) -> Vec<(String, String)> {
+        let SMTModel::SAT { fixed, .. } = model else {
+            panic!("{model:?}")
+        };
+        let mut fixed: Vec<(String, String)> = fixed
+            .iter()
+            .filter(|(sym, _)| matches!(sym.sym, Symbol::Feature(..)))
+            .map(|(sym, state)| (name(module, sym), format!("{state:?}")))
+            .collect();
+        fixed.sort();
+        fixed
+    }
+    //Kinds of the asserts in the unsat core
+    fn reasons(model: &SMTModel) -> Vec<String> {
+        let SMTModel::UNSAT { reasons } = model else {
+            panic!("{model:?}")
+        };
+        let mut reasons: Vec<String> = reasons.iter().map(|r| format!("{:?}", r.1)).collect();
+        reasons.sort();
+        reasons
+    }
+    async fn check(
+        solver: &mut dyn SolverBackend,
+        module: &Module,
+        values: &HashMap<ModuleSymbol, ConfigValue>,
+        fixed: bool,
+    ) -> SMTModel {
+        let smt_module = uvl2smt(module, values);
+        create_model(
+            solver,
+            module,
+            CancellationToken::new(),
+            smt_module,
+            fixed,
+            true,
+        )
+        .await
+        .unwrap()
+    }
+    const CAR: &str = "features\n\tCar\n\t\toptional\n\t\t\tEngine\n\t\t\tRadio\n\t\tmandatory\n\t\t\tWheels\nconstraints\n\tRadio => !Car\n";
+    const VOID: &str = "features\n\tCar\n\t\tmandatory\n\t\t\tEngine\nconstraints\n\t!Engine\n";
+    #[tokio::test]
+    async fn dead_and_core_features() {
+        let root = graph(&[("car.uvl", CAR)]);
+        let module = module(&root, "car.uvl");
+        let cancel = CancellationToken::new();
+        let model = check(&mut Embedded::new(&cancel), &module, &HashMap::new(), true).await;
+        let expected: Vec<(String, String)> = [
+            ("Car", "On"),
+            ("Engine", "Any"),
+            ("Radio", "Off"),
+            ("Wheels", "On"),
+        ]
+        .iter()
+        .map(|(a, b)| (a.to_string(), b.to_string()))
+        .collect();
+        assert_eq!(fixed(&module, &model), expected);
+    }
+    #[tokio::test]
+    async fn void_model_core() {
+        let root = graph(&[("void.uvl", VOID)]);
+        let module = module(&root, "void.uvl");
+        let cancel = CancellationToken::new();
+        let model = check(&mut Embedded::new(&cancel), &module, &HashMap::new(), true).await;
+        assert!(reasons(&model).contains(&"Constraint".to_string()));
+        //the core is named by selectors, retracting the constraint makes the model valid again
+        let smt_module = uvl2smt(&module, &HashMap::new());
+        let mut solver = Embedded::new(&cancel);
+        solver.load(&smt_module, &module).await.unwrap();
+        assert_eq!(solver.check_sat().await.unwrap(), SatResult::Unsat);
+        let core = solver.unsat_core().await.unwrap();
+        let constraint: Vec<usize> = core
+            .iter()
+            .cloned()
+            .filter(|i| {
+                matches!(
+                    smt_module.asserts[*i].0,
+                    Some(AssertInfo(_, AssertName::Constraint))
+                )
+            })
+            .collect();
+        assert_eq!(constraint.len(), 1);
+        solver.retract(&constraint).await.unwrap();
+        assert_eq!(solver.check_sat().await.unwrap(), SatResult::Sat);
+    }
+    #[test]
+    fn logic_before_definitions() {
+        let root = graph(&[("car.uvl", CAR)]);
+        let module = module(&root, "car.uvl");
+        let source = uvl2smt(&module, &HashMap::new()).to_source(&module, Some("QF_NIRA"));
+        let logic = source.find("(set-logic QF_NIRA)").unwrap();
+        assert!(source.rfind("(set-option").unwrap() < logic);
+        assert!(logic < source.find("(define-fun").unwrap());
+        assert!(logic < source.find("(declare-const").unwrap());
+        let source = uvl2smt(&module, &HashMap::new()).to_source(&module, None);
+        assert!(!source.contains("set-logic"));
+    }
+    #[tokio::test]
+    async fn invalid_config() {
+        let root = graph(&[
+            ("car.uvl", CAR),
+            (
+                "car.uvl.json",
+                "{\n\t\"file\": \"car.uvl\",\n\t\"config\": {\n\t\t\"Radio\": true\n\t}\n}\n",
+            ),
+            (
+                "valid.uvl.json",
+                "{\n\t\"file\": \"car.uvl\",\n\t\"config\": {\n\t\t\"Engine\": true\n\t}\n}\n",
+            ),
+        ]);
+        let cancel = CancellationToken::new();
+        let config = &root.cache().config_modules[&FileID::from_uri(&uri("car.uvl.json"))];
+        let model = check(
+            &mut Embedded::new(&cancel),
+            &config.module,
+            &config.values,
+            false,
+        )
+        .await;
+        assert!(reasons(&model).contains(&"Config".to_string()));
+        let config = &root.cache().config_modules[&FileID::from_uri(&uri("valid.uvl.json"))];
+        let model = check(
+            &mut Embedded::new(&cancel),
+            &config.module,
+            &config.values,
+            false,
+        )
+        .await;
+        assert!(matches!(model, SMTModel::SAT { .. }));
+    }
+    //Result and core of a module, the core by the kind and target of its asserts
+    async fn decide(
+        solver: &mut dyn SolverBackend,
+        smt_module: &SMTModule,
+    ) -> (SatResult, Vec<String>) {
+        let sat = solver.check_sat().await.unwrap();
+        let mut core = Vec::new();
+        if sat == SatResult::Unsat {
+            core = solver
+                .unsat_core()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|i| format!("{:?}", smt_module.asserts[i].0))
+                .collect();
+            core.sort();
+        }
+        (sat, core)
+    }
+    #[tokio::test]
+    async fn fixed_state_per_module() {
+        const SHARED: &str = "features\n\tS\n\t\toptional\n\t\t\tX\n";
+        const B: &str = "imports\n\tshared\nfeatures\n\tB\n\t\toptional\n\t\t\tshared.S\n";
+        let a = |constraint: &str| {
+            format!("imports\n\tshared\nfeatures\n\tA\n\t\toptional\n\t\t\tshared.S\nconstraints\n\t{constraint}\n")
+        };
+        let fixed_state = FixedState::default();
+        let (tx_err, _rx_err) = mpsc::channel(8);
+        let state = |name: &str, sym: &RootSymbol| {
+            fixed_state
+                .get(&FileID::from_uri(&uri(name)))
+                .and_then(|m| m.get(sym).map(|v| format!("{v:?}")))
+        };
+        let text = a("!shared.X");
+        let root = graph(&[("shared.uvl", SHARED), ("a.uvl", &text), ("b.uvl", B)]);
+        let shared = root.file_by_uri(&uri("shared.uvl")).unwrap();
+        let x = RootSymbol {
+            file: shared.id,
+            sym: shared
+                .lookup(Symbol::Root, &["X".into()], |_| true)
+                .next()
+                .unwrap(),
+        };
+        check_base_sat(
+            &root,
+            &tx_err,
+            &fixed_state,
+            HashMap::new(),
+            &mut HashMap::new(),
+            |_| true,
+        )
+        .await;
+        //X is only dead in a, the modules disagree on it
+        assert_eq!(state("a.uvl", &x).as_deref(), Some("Off"));
+        assert_eq!(state("b.uvl", &x).as_deref(), Some("Any"));
+        assert_eq!(fixed_value(&fixed_state, &x), None);
+        //a void a drops its entries, b keeps its own
+        let text = a("!A");
+        let root = graph(&[("shared.uvl", SHARED), ("a.uvl", &text), ("b.uvl", B)]);
+        check_base_sat(
+            &root,
+            &tx_err,
+            &fixed_state,
+            HashMap::new(),
+            &mut HashMap::new(),
+            |_| true,
+        )
+        .await;
+        assert_eq!(state("a.uvl", &x), None);
+        assert_eq!(state("b.uvl", &x).as_deref(), Some("Any"));
+        assert_eq!(fixed_value(&fixed_state, &x), Some(SMTValueState::Any));
+    }
+    #[tokio::test]
+    async fn session_diff() {
+        let edits = [
+            CAR,
+            "features\n\tCar\n\t\toptional\n\t\t\tEngine\n\t\t\tRadio\n\t\tmandatory\n\t\t\tWheels\nconstraints\n\tRadio => !Car\n\t!Wheels\n",
+            "features\n\tCar\n\t\toptional\n\t\t\tEngine\n\t\t\tRadio\n\t\tmandatory\n\t\t\tWheels\nconstraints\n\tRadio => !Car\n\tEngine => Radio\n\tEngine\n",
+            "features\n\tCar\n\t\toptional\n\t\t\tEngine\n\t\t\tRadio\n\t\tmandatory\n\t\t\tWheels\nconstraints\n\tEngine => Radio\n",
+        ];
+        let cancel = CancellationToken::new();
+        let mut session = Session::default();
+        session.set_cancel(&cancel);
+        let mut variables = None;
+        let mut unsat = 0;
+        for text in edits {
+            let root = graph(&[("car.uvl", text)]);
+            let module = module(&root, "car.uvl");
+            let smt_module = uvl2smt(&module, &HashMap::new());
+            //same variables, so the session only adds and retracts asserts
+            let names: Vec<String> = smt_module
+                .variables
+                .iter()
+                .map(|v| name(&module, v))
+                .collect();
+            assert_eq!(variables.get_or_insert_with(|| names.clone()), &names);
+            session.load(&smt_module, &module).await.unwrap();
+            let diffed = decide(&mut session, &smt_module).await;
+            let mut fresh = Embedded::new(&cancel);
+            fresh.load(&smt_module, &module).await.unwrap();
+            let fresh = decide(&mut fresh, &smt_module).await;
+            assert_eq!(diffed, fresh, "{text}");
+            unsat += (fresh.0 == SatResult::Unsat) as usize;
+        }
+        assert_eq!(unsat, 2);
+    }
+    //Both backends decide the same, only runs where the solver binary is installed
+    #[tokio::test]
+    async fn process_agreement() {
+        if !can_run_solver() {
+            return;
+        }
+        let root = graph(&[("car.uvl", CAR), ("void.uvl", VOID)]);
+        let cancel = CancellationToken::new();
+        let smt = settings().smt.clone();
+        let car = module(&root, "car.uvl");
+        let embedded = check(&mut Embedded::new(&cancel), &car, &HashMap::new(), true).await;
+        let mut process = SmtSolver::new(&smt, &cancel).await.unwrap();
+        let process = check(&mut process, &car, &HashMap::new(), true).await;
+        assert_eq!(fixed(&car, &embedded), fixed(&car, &process));
+        let void = module(&root, "void.uvl");
+        let embedded = check(&mut Embedded::new(&cancel), &void, &HashMap::new(), true).await;
+        let mut process = SmtSolver::new(&smt, &cancel).await.unwrap();
+        let process = check(&mut process, &void, &HashMap::new(), true).await;
+        assert_eq!(reasons(&embedded), reasons(&process));
+    }
+}
@@ -0,0 +1,296 @@
+use super::{
+    backend::{SatResult, SolverBackend},
+    Expr,
+};
+use crate::core::*;
+use hashbrown::HashMap;
+use log::info;
+use tokio::time::Instant;
+//Backbone of a satisfiable module: the Boolean variables that have the same value in every model.
+//Candidates are the values of a first model, every later model drops the candidates it flips.
+//A chunk of candidates is flipped at once under assumptions: a model drops all of them, an unsat
+//core with one assumption proves that candidate and a larger core is decided with a single
+//check that at least one of its candidates flips.
+
+const MAX_CHUNK: usize = 256;
+
+fn lit(var: usize, value: bool) -> Expr {
+    match value {
+        true => Expr::Var(var),
+        false => Expr::Not(Expr::Var(var).into()),
+    }
+}
+#[derive(Default)]
+struct Stats {
+    checks: usize,
+    models: usize,
+}
+//Drop the candidates that have another value in the model of the last check
+async fn filter(
+    solve: &mut dyn SolverBackend,
+    candidates: &mut Vec<(usize, bool)>,
+    free: &mut Vec<usize>,
+    stats: &mut Stats,
+) -> Result<()> {
+    stats.models += 1;
+    let vars: Vec<usize> = candidates.iter().map(|(i, _)| *i).collect();
+    let values: HashMap<usize, ConfigValue> = solve.values(&vars).await?.into_iter().collect();
+    candidates.retain(|(i, value)| match values.get(i) {
+        Some(ConfigValue::Bool(v)) if v != value => {
+            free.push(*i);
+            false
+        }
+        _ => true,
+    });
+    Ok(())
+}
+//Variables of model that are fixed to their value, the rest is free or could not be decided
+pub async fn backbone(
+    solve: &mut dyn SolverBackend,
+    model: Vec<(usize, bool)>,
+) -> Result<(Vec<(usize, bool)>, Vec<usize>)> {
+    let time = Instant::now();
+    let total = model.len();
+    let mut stats = Stats::default();
+    let mut candidates = model;
+    let mut fixed = Vec::new();
+    let mut free = Vec::new();
+    let mut chunk_size = 8;
+    while !candidates.is_empty() {
+        let chunk: Vec<(usize, bool)> = candidates
+            .iter()
+            .take(chunk_size)
+            .map(|(i, value)| (*i, !*value))
+            .collect();
+        stats.checks += 1;
+        match solve.check_assuming(&chunk).await? {
+            SatResult::Sat => {
+                filter(solve, &mut candidates, &mut free, &mut stats).await?;
+                chunk_size = (chunk_size * 2).min(MAX_CHUNK);
+            }
+            //smaller chunks until a single candidate is given up
+            SatResult::Unknown => {
+                if chunk.len() == 1 {
+                    candidates.retain(|(i, _)| *i != chunk[0].0);
+                    free.push(chunk[0].0);
+                }
+                chunk_size = (chunk.len() / 2).max(1);
+            }
+            SatResult::Unsat => {
+                let mut core: Vec<(usize, bool)> = solve
+                    .unsat_assumptions()
+                    .await?
+                    .into_iter()
+                    .map(|i| chunk[i])
+                    .collect();
+                if core.is_empty() {
+                    core = chunk;
+                }
+                if let &[(i, flipped)] = core.as_slice() {
+                    candidates.retain(|(j, _)| *j != i);
+                    fixed.push((i, !flipped));
+                    continue;
+                }
+                //the core candidates can't flip together, check if any of them can flip alone
+                solve.push().await?;
+                solve
+                    .assert(&Expr::Or(
+                        core.iter().map(|(i, flipped)| lit(*i, *flipped)).collect(),
+                    ))
+                    .await?;
+                stats.checks += 1;
+                match solve.check_sat().await? {
+                    SatResult::Sat => {
+                        filter(solve, &mut candidates, &mut free, &mut stats).await?;
+                    }
+                    SatResult::Unsat => {
+                        candidates.retain(|(i, _)| !core.iter().any(|(j, _)| i == j));
+                        fixed.extend(core.iter().map(|(i, flipped)| (*i, !*flipped)));
+                    }
+                    SatResult::Unknown => {
+                        chunk_size = 1;
+                    }
+                }
+                solve.pop().await?;
+            }
+        }
+    }
+    info!(
+        "backbone of {total} variables: {} fixed, {} free with {} checks and {} models in {:?}",
+        fixed.len(),
+        free.len(),
+        stats.checks,
+        stats.models,
+        time.elapsed()
+    );
+    Ok((fixed, free))
+}
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        embedded::Embedded,
+        tests::{graph, module},
+        uvl2smt, Assert, SMTModule,
+    };
+    use super::*;
+    use std::time::Duration;
+    use tokio_util::sync::CancellationToken;
+    //Embedded solver that counts the checks and gives up on assumptions about a hard variable
+    struct Probe {
+        solver: Embedded,
+        hard: Option<usize>,
+        or_checks: usize,
+    }
+    #[tower_lsp::async_trait]
+    impl SolverBackend for Probe {
+        async fn load(&mut self, module: &SMTModule, base: &Module) -> Result<()> {
+            self.solver.load(module, base).await
+        }
+        async fn assert(&mut self, expr: &Expr) -> Result<()> {
+            self.solver.assert(expr).await
+        }
+        async fn add(&mut self, asserts: &[(usize, &Expr)]) -> Result<()> {
+            self.solver.add(asserts).await
+        }
+        async fn retract(&mut self, ids: &[usize]) -> Result<()> {
+            self.solver.retract(ids).await
+        }
+        async fn push(&mut self) -> Result<()> {
+            self.solver.push().await
+        }
+        async fn pop(&mut self) -> Result<()> {
+            self.solver.pop().await
+        }
+        async fn check_sat(&mut self) -> Result<SatResult> {
+            self.or_checks += 1;
+            self.solver.check_sat().await
+        }
+        async fn check_assuming(&mut self, lits: &[(usize, bool)]) -> Result<SatResult> {
+            if lits.iter().any(|(i, _)| Some(*i) == self.hard) {
+                return Ok(SatResult::Unknown);
+            }
+            self.solver.check_assuming(lits).await
+        }
+        async fn unsat_assumptions(&mut self) -> Result<Vec<usize>> {
+            self.solver.unsat_assumptions().await
+        }
+        async fn values(&mut self, vars: &[usize]) -> Result<Vec<(usize, ConfigValue)>> {
+            self.solver.values(vars).await
+        }
+        async fn unsat_core(&mut self) -> Result<Vec<usize>> {
+            self.solver.unsat_core().await
+        }
+        async fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+            self.solver.set_timeout(timeout).await
+        }
+        async fn set_rlimit(&mut self, rlimit: Option<u64>) -> Result<()> {
+            self.solver.set_rlimit(rlimit).await
+        }
+        fn set_cancel(&mut self, cancel: &CancellationToken) {
+            self.solver.set_cancel(cancel);
+        }
+    }
+    fn var(i: usize) -> Expr {
+        Expr::Var(i)
+    }
+    fn not(i: usize) -> Expr {
+        Expr::Not(var(i).into())
+    }
+    //x0, x0 => x1, x2 | x3, !x4 | !x0, x5 | !x5 and x6 = x7 under at most one of x6, x7
+    //The backbone is x0, x1, !x4, !x6 and !x7
+    fn cnf() -> Vec<Expr> {
+        vec![
+            var(0),
+            Expr::Or(vec![not(0), var(1)]),
+            Expr::Or(vec![var(2), var(3)]),
+            Expr::Or(vec![not(4), not(0)]),
+            Expr::Or(vec![var(5), not(5)]),
+            Expr::Equal(vec![var(6), var(7)]),
+            Expr::AtMost(1, vec![var(6), var(7)]),
+        ]
+    }
+    //Boolean features as variables of the cnf
+    async fn load(hard: Option<usize>) -> Probe {
+        let root = graph(&[(
+            "vars.uvl",
+            "features\n\tA\n\t\toptional\n\t\t\tB\n\t\t\tC\n\t\t\tD\n\t\t\tE\n\t\t\tF\n\t\t\tG\n\t\t\tH\n",
+        )]);
+        let base = module(&root, "vars.uvl");
+        let mut smt_module = uvl2smt(&base, &HashMap::new());
+        assert_eq!(smt_module.variables.len(), 8);
+        smt_module.asserts = cnf().into_iter().map(|e| Assert(None, e)).collect();
+        let mut solver = Probe {
+            solver: Embedded::new(&CancellationToken::new()),
+            hard,
+            or_checks: 0,
+        };
+        solver.load(&smt_module, &base).await.unwrap();
+        solver
+    }
+    async fn first_model(solve: &mut Probe) -> Vec<(usize, bool)> {
+        assert_eq!(solve.check_assuming(&[]).await.unwrap(), SatResult::Sat);
+        solve
+            .values(&(0..8).collect::<Vec<_>>())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(i, v)| (i, v == ConfigValue::Bool(true)))
+            .collect()
+    }
+    //A variable is fixed if the model can't flip it
+    async fn naive(solve: &mut Probe, model: &[(usize, bool)]) -> Vec<(usize, bool)> {
+        let mut fixed = Vec::new();
+        for (i, value) in model {
+            if solve.check_assuming(&[(*i, !*value)]).await.unwrap() == SatResult::Unsat {
+                fixed.push((*i, *value));
+            }
+        }
+        fixed
+    }
+    fn sorted<T: Ord>(mut v: Vec<T>) -> Vec<T> {
+        v.sort();
+        v
+    }
+    #[tokio::test]
+    async fn known_backbone() {
+        let mut solve = load(None).await;
+        let model = first_model(&mut solve).await;
+        let expected = naive(&mut solve, &model).await;
+        assert_eq!(
+            sorted(expected.clone()),
+            vec![(0, true), (1, true), (4, false), (6, false), (7, false)]
+        );
+        let (fixed, free) = backbone(&mut solve, model).await.unwrap();
+        assert_eq!(sorted(fixed), expected);
+        assert_eq!(sorted(free), vec![2, 3, 5]);
+    }
+    #[tokio::test]
+    async fn multi_element_core() {
+        let mut solve = load(None).await;
+        //x2 and x3 can't flip together, so the first chunk fails with both in its core
+        let model = vec![(2, true), (3, true), (5, true)];
+        let (fixed, free) = backbone(&mut solve, model).await.unwrap();
+        assert!(fixed.is_empty());
+        assert_eq!(sorted(free), vec![2, 3, 5]);
+        assert!(solve.or_checks > 0);
+        //x6 and x7 can neither flip together nor alone
+        let mut solve = load(None).await;
+        let (fixed, free) = backbone(&mut solve, vec![(6, false), (7, false)])
+            .await
+            .unwrap();
+        assert_eq!(sorted(fixed), vec![(6, false), (7, false)]);
+        assert!(free.is_empty());
+    }
+    #[tokio::test]
+    async fn unknown_given_up() {
+        let mut solve = load(Some(1)).await;
+        let model = first_model(&mut solve).await;
+        let (fixed, free) = backbone(&mut solve, model).await.unwrap();
+        //x1 is fixed, but the solver never decides it
+        assert_eq!(
+            sorted(fixed),
+            vec![(0, true), (4, false), (6, false), (7, false)]
+        );
+        assert_eq!(sorted(free), vec![1, 2, 3, 5]);
+    }
+}
@@ -0,0 +1,91 @@
+use super::{embedded::Embedded, process::SmtSolver, Expr, SMTModule};
+use crate::core::*;
+use hashbrown::HashMap;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+//Solvers used for the semantic analysis. Variables are addressed by their index in the loaded
+//SMTModule and named asserts by an id, so a backend is free to use any internal representation.
+//The named asserts of a loaded module use their index as id.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SatResult {
+    Sat,
+    Unsat,
+    //a limit was hit or the solver gave up
+    Unknown,
+}
+#[tower_lsp::async_trait]
+pub trait SolverBackend: Send {
+    //Replace the solver state with the variables and asserts of module
+    async fn load(&mut self, module: &SMTModule, base: &Module) -> Result<()>;
+    //Add an anonymous assert to the current scope
+    async fn assert(&mut self, expr: &Expr) -> Result<()>;
+    //Add named asserts outside of any scope, they are active until they are retracted
+    async fn add(&mut self, asserts: &[(usize, &Expr)]) -> Result<()>;
+    async fn retract(&mut self, ids: &[usize]) -> Result<()>;
+    async fn push(&mut self) -> Result<()>;
+    async fn pop(&mut self) -> Result<()>;
+    async fn check_sat(&mut self) -> Result<SatResult>;
+    //Check with Boolean variables set to these values for this check only
+    async fn check_assuming(&mut self, lits: &[(usize, bool)]) -> Result<SatResult>;
+    //Positions of the assumed values in the core of the last unsat check_assuming
+    async fn unsat_assumptions(&mut self) -> Result<Vec<usize>>;
+    //Values of the variables with these indices in the model of the last check
+    async fn values(&mut self, vars: &[usize]) -> Result<Vec<(usize, ConfigValue)>>;
+    //Ids of the named asserts in the core of the last check
+    async fn unsat_core(&mut self) -> Result<Vec<usize>>;
+    //Limits for each following check, None removes them
+    async fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()>;
+    async fn set_rlimit(&mut self, rlimit: Option<u64>) -> Result<()>;
+    //Token that cancels the following operations
+    fn set_cancel(&mut self, cancel: &CancellationToken);
+    //Clauses learnt by an in process solver, they slow down every following check
+    fn learnt(&self) -> usize {
+        0
+    }
+    //Values of all variables in the model of the last check
+    async fn model(&mut self, module: &SMTModule) -> Result<HashMap<ModuleSymbol, ConfigValue>> {
+        let vars: Vec<usize> = (0..module.variables.len()).collect();
+        Ok(self
+            .values(&vars)
+            .await?
+            .into_iter()
+            .map(|(i, v)| (module.variables[i], v))
+            .collect())
+    }
+}
+
+//Create the backend selected in the settings that is able to decide module
+pub async fn create_backend(
+    module: &SMTModule,
+    base: &Module,
+    cancel: &CancellationToken,
+) -> Result<Box<dyn SolverBackend>> {
+    let smt = settings().smt.clone();
+    let mut backend: Box<dyn SolverBackend> = match smt.backend {
+        BackendKind::Process => Box::new(SmtSolver::new(&smt, cancel).await?),
+        BackendKind::Embedded => Box::new(Embedded::new(cancel)),
+        //Boolean models don't need a solver process
+        BackendKind::Auto if Embedded::supports(module, base) => Box::new(Embedded::new(cancel)),
+        BackendKind::Auto => Box::new(SmtSolver::new(&smt, cancel).await?),
+    };
+    if smt.timeout > 0 {
+        backend
+            .set_timeout(Some(Duration::from_millis(smt.timeout)))
+            .await?;
+    }
+    if smt.rlimit > 0 {
+        backend.set_rlimit(Some(smt.rlimit)).await?;
+    }
+    Ok(backend)
+}
+//Create the backend selected in the settings with module loaded
+pub async fn new_backend(
+    module: &SMTModule,
+    base: &Module,
+    cancel: &CancellationToken,
+) -> Result<Box<dyn SolverBackend>> {
+    let mut backend = create_backend(module, base, cancel).await?;
+    backend.load(module, base).await?;
+    Ok(backend)
+}
@@ -0,0 +1,501 @@
+use super::{
+    backend::{SatResult, SolverBackend},
+    sat::{Lit, Solution, Solver},
+    Assert, Expr, SMTModule,
+};
+use crate::core::*;
+use hashbrown::HashSet;
+use log::info;
+use std::time::{Duration, Instant};
+use tokio::task::spawn_blocking;
+use tokio_util::sync::CancellationToken;
+//In-process backend for models with only Boolean variables, so they are analysed without any
+//solver binary. Asserts are lowered to CNF with a Tseitin encoding and decided by the CDCL solver
+//in sat.rs. Each named assert and each scope is guarded by a selector literal that is assumed
+//during checks, a failed selector set is the unsat core. Pop and retract just disable a selector.
+
+//Lowers Boolean expressions, every returned literal is equivalent to its expression
+struct Encoder<'a> {
+    solver: &'a mut Solver,
+    top: Lit,
+}
+impl<'a> Encoder<'a> {
+    fn fresh(&mut self) -> Lit {
+        Lit::new(self.solver.new_var(), true)
+    }
+    fn and(&mut self, lits: Vec<Lit>) -> Lit {
+        if lits.contains(&!self.top) {
+            return !self.top;
+        }
+        let mut lits: Vec<Lit> = lits.into_iter().filter(|l| *l != self.top).collect();
+        lits.sort();
+        lits.dedup();
+        match lits.len() {
+            0 => self.top,
+            1 => lits[0],
+            _ => {
+                let x = self.fresh();
+                for l in lits.iter() {
+                    self.solver.add_clause(&[!x, *l]);
+                }
+                let mut all: Vec<Lit> = lits.iter().map(|l| !*l).collect();
+                all.push(x);
+                self.solver.add_clause(&all);
+                x
+            }
+        }
+    }
+    fn or(&mut self, lits: Vec<Lit>) -> Lit {
+        let neg = lits.into_iter().map(|l| !l).collect();
+        !self.and(neg)
+    }
+    fn iff(&mut self, a: Lit, b: Lit) -> Lit {
+        if a == b {
+            return self.top;
+        }
+        if a == !b {
+            return !self.top;
+        }
+        let x = self.fresh();
+        self.solver.add_clause(&[!x, !a, b]);
+        self.solver.add_clause(&[!x, a, !b]);
+        self.solver.add_clause(&[x, a, b]);
+        self.solver.add_clause(&[x, !a, !b]);
+        x
+    }
+    fn ite(&mut self, c: Lit, a: Lit, b: Lit) -> Lit {
+        if c == self.top || a == b {
+            return a;
+        }
+        if c == !self.top {
+            return b;
+        }
+        let x = self.fresh();
+        self.solver.add_clause(&[!x, !c, a]);
+        self.solver.add_clause(&[!x, c, b]);
+        self.solver.add_clause(&[x, !c, !a]);
+        self.solver.add_clause(&[x, c, !b]);
+        x
+    }
+    //Sequential counter, count[j] is true if at least j of the literals seen so far are true
+    fn at_least(&mut self, lits: Vec<Lit>, k: usize) -> Lit {
+        if k == 0 {
+            return self.top;
+        }
+        if k > lits.len() {
+            return !self.top;
+        }
+        let mut count = vec![!self.top; k + 1];
+        count[0] = self.top;
+        for l in lits {
+            for j in (1..=k).rev() {
+                let carry = self.and(vec![l, count[j - 1]]);
+                count[j] = self.or(vec![count[j], carry]);
+            }
+        }
+        count[k]
+    }
+    fn lit(&mut self, e: &Expr) -> Lit {
+        match e {
+            Expr::Bool(true) => self.top,
+            Expr::Bool(false) => !self.top,
+            Expr::Var(i) => Lit::new(*i, true),
+            Expr::Not(e) => !self.lit(e),
+            Expr::And(v) => {
+                let lits = v.iter().map(|e| self.lit(e)).collect();
+                self.and(lits)
+            }
+            Expr::Or(v) => {
+                let lits = v.iter().map(|e| self.lit(e)).collect();
+                self.or(lits)
+            }
+            //right associative like SMT-LIB
+            Expr::Implies(v) => {
+                let lits: Vec<Lit> = v.iter().map(|e| self.lit(e)).collect();
+                let Some((last, init)) = lits.split_last() else {
+                    return self.top;
+                };
+                init.iter()
+                    .rev()
+                    .fold(*last, |rhs, lhs| self.or(vec![!*lhs, rhs]))
+            }
+            Expr::Equal(v) => {
+                let lits: Vec<Lit> = v.iter().map(|e| self.lit(e)).collect();
+                let pairs = lits.windows(2).map(|w| self.iff(w[0], w[1])).collect();
+                self.and(pairs)
+            }
+            Expr::AtLeast(k, v) => {
+                let lits = v.iter().map(|e| self.lit(e)).collect();
+                self.at_least(lits, *k)
+            }
+            Expr::AtMost(k, v) => {
+                let lits = v.iter().map(|e| self.lit(e)).collect();
+                !self.at_least(lits, *k + 1)
+            }
+            Expr::Ite(c, l, r) => {
+                let (c, l, r) = (self.lit(c), self.lit(l), self.lit(r));
+                self.ite(c, l, r)
+            }
+            _ => unreachable!("not a Boolean expression"),
+        }
+    }
+}
+fn is_boolean(e: &Expr) -> bool {
+    match e {
+        Expr::Bool(_) | Expr::Var(_) => true,
+        Expr::Not(e) => is_boolean(e),
+        Expr::And(v)
+        | Expr::Or(v)
+        | Expr::Implies(v)
+        | Expr::Equal(v)
+        | Expr::AtLeast(_, v)
+        | Expr::AtMost(_, v) => v.iter().all(is_boolean),
+        Expr::Ite(c, l, r) => is_boolean(c) && is_boolean(l) && is_boolean(r),
+        _ => false,
+    }
+}
+
+pub struct Embedded {
+    cancel: CancellationToken,
+    solver: Solver,
+    //always true literal
+    top: Lit,
+    //variables of the SMTModule, they are the first solver variables
+    vars: usize,
+    //selectors of the named asserts and their id
+    named: Vec<(Lit, usize)>,
+    //selectors of the pushed scopes
+    scopes: Vec<Lit>,
+    timeout: Option<Duration>,
+    //conflicts per check
+    rlimit: Option<u64>,
+    //variable values assumed in the last check
+    assumed: Vec<Lit>,
+    last: Option<Solution>,
+}
+impl Embedded {
+    pub fn new(cancel: &CancellationToken) -> Self {
+        let mut solver = Solver::default();
+        let top = Lit::new(solver.new_var(), true);
+        solver.add_clause(&[top]);
+        Self {
+            cancel: cancel.clone(),
+            solver,
+            top,
+            vars: 0,
+            named: Vec::new(),
+            scopes: Vec::new(),
+            timeout: None,
+            rlimit: None,
+            assumed: Vec::new(),
+            last: None,
+        }
+    }
+    //Only Boolean variables and logic operators can be decided
+    pub fn supports(module: &SMTModule, base: &Module) -> bool {
+        module
+            .variables
+            .iter()
+            .all(|v| base.type_of(*v) == Type::Bool)
+            && module.asserts.iter().all(|Assert(_, e)| is_boolean(e))
+    }
+    fn encode(&mut self, e: &Expr) -> Lit {
+        Encoder {
+            solver: &mut self.solver,
+            top: self.top,
+        }
+        .lit(e)
+    }
+    fn selector(&mut self) -> Lit {
+        Lit::new(self.solver.new_var(), true)
+    }
+    //Solve on a blocking thread, the solver is moved there and back. None if a limit was hit
+    async fn solve(&mut self, assumptions: Vec<Lit>) -> Result<Option<Solution>> {
+        let mut solver = std::mem::take(&mut self.solver);
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let budget = self.rlimit;
+        let cancel = self.cancel.clone();
+        let (solver, solution) = spawn_blocking(move || {
+            let stop =
+                || cancel.is_cancelled() || deadline.map(|d| Instant::now() > d).unwrap_or(false);
+            let solution = solver.solve(&assumptions, budget, &stop);
+            (solver, solution)
+        })
+        .await?;
+        self.solver = solver;
+        if self.cancel.is_cancelled() {
+            return Err("cancelled".into());
+        }
+        Ok(solution)
+    }
+    fn assumptions(&self, named: impl Iterator<Item = Lit>) -> Vec<Lit> {
+        self.scopes.iter().cloned().chain(named).collect()
+    }
+}
+#[tower_lsp::async_trait]
+impl SolverBackend for Embedded {
+    async fn load(&mut self, module: &SMTModule, base: &Module) -> Result<()> {
+        if !Self::supports(module, base) {
+            return Err("the embedded solver only supports Boolean models".into());
+        }
+        //variable i of the module is Lit::new(i, ..), top takes the next free variable
+        let mut solver = Solver::default();
+        for _ in 0..module.variables.len() {
+            solver.new_var();
+        }
+        self.top = Lit::new(solver.new_var(), true);
+        solver.add_clause(&[self.top]);
+        self.solver = solver;
+        self.vars = module.variables.len();
+        self.named.clear();
+        self.scopes.clear();
+        self.last = None;
+        for (i, Assert(info, e)) in module.asserts.iter().enumerate() {
+            let root = self.encode(e);
+            if info.is_some() {
+                let s = self.selector();
+                self.solver.add_clause(&[!s, root]);
+                self.named.push((s, i));
+            } else {
+                self.solver.add_clause(&[root]);
+            }
+        }
+        Ok(())
+    }
+    async fn assert(&mut self, expr: &Expr) -> Result<()> {
+        if !is_boolean(expr) {
+            return Err("the embedded solver only supports Boolean asserts".into());
+        }
+        let root = self.encode(expr);
+        match self.scopes.last() {
+            Some(s) => self.solver.add_clause(&[!*s, root]),
+            None => self.solver.add_clause(&[root]),
+        }
+        Ok(())
+    }
+    async fn add(&mut self, asserts: &[(usize, &Expr)]) -> Result<()> {
+        for (id, e) in asserts {
+            if !is_boolean(e) {
+                return Err("the embedded solver only supports Boolean asserts".into());
+            }
+            let root = self.encode(e);
+            let s = self.selector();
+            self.solver.add_clause(&[!s, root]);
+            self.named.push((s, *id));
+        }
+        Ok(())
+    }
+    async fn retract(&mut self, ids: &[usize]) -> Result<()> {
+        let ids: HashSet<usize> = ids.iter().cloned().collect();
+        let solver = &mut self.solver;
+        self.named.retain(|(s, id)| {
+            if ids.contains(id) {
+                solver.add_clause(&[!*s]);
+                false
+            } else {
+                true
+            }
+        });
+        Ok(())
+    }
+    async fn push(&mut self) -> Result<()> {
+        let s = self.selector();
+        self.scopes.push(s);
+        Ok(())
+    }
+    async fn pop(&mut self) -> Result<()> {
+        let s = self.scopes.pop().ok_or("pop without push")?;
+        //the clauses of the scope are satisfied from now on
+        self.solver.add_clause(&[!s]);
+        Ok(())
+    }
+    async fn check_sat(&mut self) -> Result<SatResult> {
+        self.check_assuming(&[]).await
+    }
+    async fn check_assuming(&mut self, lits: &[(usize, bool)]) -> Result<SatResult> {
+        if lits.iter().any(|(i, _)| *i >= self.vars) {
+            return Err("assumption on an unknown variable".into());
+        }
+        self.assumed = lits.iter().map(|(i, value)| Lit::new(*i, *value)).collect();
+        let assumptions = self.assumptions(
+            self.named
+                .iter()
+                .map(|(s, _)| *s)
+                .chain(self.assumed.iter().cloned()),
+        );
+        self.last = self.solve(assumptions).await?;
+        Ok(match self.last {
+            Some(Solution::Sat(..)) => SatResult::Sat,
+            Some(Solution::Unsat(..)) => SatResult::Unsat,
+            None => SatResult::Unknown,
+        })
+    }
+    async fn unsat_assumptions(&mut self) -> Result<Vec<usize>> {
+        let Some(Solution::Unsat(failed)) = self.last.as_ref() else {
+            return Err("no unsat assumptions without an unsat check".into());
+        };
+        Ok(self
+            .assumed
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| failed.contains(l))
+            .map(|(i, _)| i)
+            .collect())
+    }
+    async fn values(&mut self, vars: &[usize]) -> Result<Vec<(usize, ConfigValue)>> {
+        let Some(Solution::Sat(model)) = self.last.as_ref() else {
+            return Err("no model without a sat check".into());
+        };
+        Ok(vars
+            .iter()
+            .filter(|i| **i < self.vars)
+            .map(|i| (*i, ConfigValue::Bool(model[*i])))
+            .collect())
+    }
+    async fn unsat_core(&mut self) -> Result<Vec<usize>> {
+        let Some(Solution::Unsat(failed)) = self.last.clone() else {
+            return Err("no unsat core without an unsat check".into());
+        };
+        let time = Instant::now();
+        let mut core: Vec<Lit> = failed
+            .into_iter()
+            .filter(|l| !self.scopes.contains(l))
+            .collect();
+        //drop selectors that are not needed, the remaining core only shrinks
+        let mut i = 0;
+        while i < core.len() {
+            let rest: Vec<Lit> = core
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, l)| *l)
+                .collect();
+            match self.solve(self.assumptions(rest.iter().cloned())).await? {
+                Some(Solution::Unsat(failed)) => {
+                    core = rest.into_iter().filter(|l| failed.contains(l)).collect();
+                }
+                _ => i += 1,
+            }
+        }
+        info!("minimized unsat core in {:?}", time.elapsed());
+        Ok(self
+            .named
+            .iter()
+            .filter(|(s, _)| core.contains(s))
+            .map(|(_, i)| *i)
+            .collect())
+    }
+    async fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+    async fn set_rlimit(&mut self, rlimit: Option<u64>) -> Result<()> {
+        self.rlimit = rlimit;
+        Ok(())
+    }
+    fn set_cancel(&mut self, cancel: &CancellationToken) {
+        self.cancel = cancel.clone();
+    }
+    fn learnt(&self) -> usize {
+        self.solver.learnt()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn var(i: usize) -> Expr {
+        Expr::Var(i)
+    }
+    //The encoding of e has to be equivalent to eval for all assignments of vars
+    fn truth(vars: usize, e: &Expr, eval: impl Fn(&[bool]) -> bool) {
+        let mut solver = Solver::default();
+        for _ in 0..vars {
+            solver.new_var();
+        }
+        let top = Lit::new(solver.new_var(), true);
+        solver.add_clause(&[top]);
+        let root = Encoder {
+            solver: &mut solver,
+            top,
+        }
+        .lit(e);
+        for bits in 0..1usize << vars {
+            let values: Vec<bool> = (0..vars).map(|i| (bits >> i) & 1 == 1).collect();
+            for expected in [true, false] {
+                let mut assumptions: Vec<Lit> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| Lit::new(i, *v))
+                    .collect();
+                assumptions.push(if expected { root } else { !root });
+                let sat = matches!(
+                    solver.solve(&assumptions, None, &|| false),
+                    Some(Solution::Sat(_))
+                );
+                assert_eq!(sat, eval(&values) == expected, "{e:?} with {values:?}");
+            }
+        }
+    }
+    fn count(v: &[bool]) -> usize {
+        v.iter().filter(|v| **v).count()
+    }
+    #[test]
+    fn cardinality() {
+        let all = vec![var(0), var(1), var(2), var(3)];
+        for k in 0..=5 {
+            truth(4, &Expr::AtLeast(k, all.clone()), |v| count(v) >= k);
+            truth(4, &Expr::AtMost(k, all.clone()), |v| count(v) <= k);
+        }
+        //repeated literals count twice
+        truth(2, &Expr::AtLeast(2, vec![var(0), var(0), var(1)]), |v| {
+            2 * v[0] as usize + v[1] as usize >= 2
+        });
+    }
+    #[test]
+    fn implies_right_associative() {
+        truth(3, &Expr::Implies(vec![var(0), var(1), var(2)]), |v| {
+            !v[0] || !v[1] || v[2]
+        });
+        truth(1, &Expr::Implies(vec![var(0)]), |v| v[0]);
+        truth(1, &Expr::Implies(vec![]), |_| true);
+    }
+    #[test]
+    fn ite_and_equal() {
+        truth(
+            3,
+            &Expr::Ite(var(0).into(), var(1).into(), var(2).into()),
+            |v| {
+                if v[0] {
+                    v[1]
+                } else {
+                    v[2]
+                }
+            },
+        );
+        truth(
+            2,
+            &Expr::Ite(var(0).into(), var(1).into(), var(1).into()),
+            |v| v[1],
+        );
+        truth(3, &Expr::Equal(vec![var(0), var(1), var(2)]), |v| {
+            v[0] == v[1] && v[1] == v[2]
+        });
+        truth(
+            2,
+            &Expr::Equal(vec![var(0), Expr::Not(var(0).into())]),
+            |_| false,
+        );
+    }
+    #[test]
+    fn constants() {
+        let e = Expr::And(vec![
+            Expr::Bool(true),
+            var(0),
+            Expr::Or(vec![Expr::Bool(false), var(1)]),
+        ]);
+        truth(2, &e, |v| v[0] && v[1]);
+        truth(1, &Expr::And(vec![Expr::Bool(false), var(0)]), |_| false);
+        truth(1, &Expr::Or(vec![]), |_| false);
+        truth(1, &Expr::And(vec![]), |_| true);
+    }
+}
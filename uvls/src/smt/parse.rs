@@ -10,7 +10,6 @@ use nom::{
     IResult,
 };
 
-use super::SMTModule;
 fn boolean(input: &str) -> IResult<&str, bool> {
     alt((map(tag("true"), |_| true), map(tag("false"), |_| false)))(input)
 }
@@ -109,19 +108,17 @@ where
     }
 }
 
-pub fn iter_values<'a>(
-    smt_module: &'a SMTModule,
-    module: &'a Module,
+//Values of a get-value response by variable index, var_ty gives the type of each variable
+pub fn values_by_index<'a, F: Fn(usize) -> Type + 'a>(
     mut values: &'a str,
-) -> impl Iterator<Item = (ModuleSymbol, ConfigValue)> + 'a {
+    var_ty: F,
+) -> impl Iterator<Item = (usize, ConfigValue)> + 'a {
     values = &values[values.find('(').map(|i| i + 1).unwrap_or(0)..];
-    let parser = ValueParser {
-        var_ty: |id| module.type_of(smt_module.variables[id]),
-    };
+    let parser = ValueParser { var_ty };
     std::iter::from_fn(move || match parser.parse(values) {
-        Ok((i, (v, c))) => {
+        Ok((i, v)) => {
             values = i;
-            Some((smt_module.variables[v], c))
+            Some(v)
         }
         Err(_e) => None,
     })
@@ -0,0 +1,318 @@
+use super::{
+    backend::{SatResult, SolverBackend},
+    parse::values_by_index,
+    Expr, SMTModule,
+};
+use crate::core::*;
+use hashbrown::HashSet;
+use lazy_static::lazy_static;
+use log::info;
+use regex::Regex;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter, Lines},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    spawn,
+};
+use tokio_util::sync::CancellationToken;
+//Backend for any solver binary that reads SMT-LIB2 from stdin like z3, cvc5 or yices-smt2.
+//While the performance is worse than linking with a solver, we don't have to interact with
+//any C-Bindings. Options outside of the standard are only set for solvers we know.
+//Added asserts are guarded by a constant s{id} and checked with check-sat-assuming, so they
+//can be retracted without a push/pop around them.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Dialect {
+    Z3,
+    Cvc5,
+    Yices,
+    Generic,
+}
+impl Dialect {
+    fn detect(solver_path: &str) -> Self {
+        let name = Path::new(solver_path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if name.starts_with("z3") {
+            Self::Z3
+        } else if name.starts_with("cvc5") || name.starts_with("cvc4") {
+            Self::Cvc5
+        } else if name.starts_with("yices") {
+            Self::Yices
+        } else {
+            Self::Generic
+        }
+    }
+    fn options(&self) -> &'static str {
+        match self {
+            Self::Z3 => "(set-option :smt.core.minimize true)\n",
+            _ => "",
+        }
+    }
+    //yices-smt2 refuses declarations without a logic, the model may use nonlinear mixed arithmetic
+    fn logic(&self) -> Option<&'static str> {
+        match self {
+            Self::Yices => Some("QF_NIRA"),
+            _ => None,
+        }
+    }
+    //Per query limits, solvers without these options are only limited by the background check timeout
+    fn limits(&self, timeout: Option<Duration>, rlimit: Option<u64>) -> String {
+        let rlimit = rlimit.unwrap_or(0);
+        match self {
+            //z3 has no value to disable the timeout, its default is the maximum
+            Self::Z3 => {
+                let ms = timeout.map(|t| t.as_millis()).unwrap_or(u32::MAX as u128);
+                format!("(set-option :timeout {ms})(set-option :rlimit {rlimit})\n")
+            }
+            Self::Cvc5 => {
+                let ms = timeout.map(|t| t.as_millis()).unwrap_or(0);
+                format!("(set-option :tlimit-per {ms})(set-option :rlimit-per {rlimit})\n")
+            }
+            Self::Yices | Self::Generic => String::new(),
+        }
+    }
+}
+pub struct SmtSolver {
+    _proc: Child,
+    stdin: BufWriter<ChildStdin>,
+    stdout: Lines<BufReader<ChildStdout>>,
+    cancel: CancellationToken,
+    dialect: Dialect,
+    //logic of the settings or the dialect default
+    logic: Option<String>,
+    timeout: Option<Duration>,
+    rlimit: Option<u64>,
+    //variable types of the loaded module to parse values
+    types: Vec<Type>,
+    //ids of the active added asserts
+    guards: HashSet<usize>,
+    //variable values assumed in the last check
+    assumed: Vec<(usize, bool)>,
+    loaded: bool,
+}
+impl SmtSolver {
+    pub async fn new(smt: &SmtSettings, cancel: &CancellationToken) -> Result<Self> {
+        let mut proc = Command::new(&smt.solver_path)
+            .args(&smt.solver_args)
+            .stdin(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = BufWriter::new(proc.stdin.take().unwrap());
+        let stdout = BufReader::new(proc.stdout.take().unwrap()).lines();
+        let mut stderr = BufReader::new(proc.stderr.take().unwrap()).lines();
+        spawn(async move {
+            while let Some(err) = stderr.next_line().await? {
+                info!("smt error {:?}", err);
+            }
+            tokio::io::Result::Ok(())
+        });
+        let dialect = Dialect::detect(&smt.solver_path);
+        Ok(SmtSolver {
+            cancel: cancel.clone(),
+            _proc: proc,
+            stdin,
+            stdout,
+            dialect,
+            logic: smt
+                .logic
+                .clone()
+                .or_else(|| dialect.logic().map(String::from)),
+            timeout: None,
+            rlimit: None,
+            types: Vec::new(),
+            guards: HashSet::new(),
+            assumed: Vec::new(),
+            loaded: false,
+        })
+    }
+    async fn write(&mut self, cmd: &str) -> Result<()> {
+        maybe_cancel(&self.cancel, self.stdin.write_all(cmd.as_bytes())).await??;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+
+    async fn check(&mut self, lits: &[(usize, bool)]) -> Result<SatResult> {
+        self.assumed = lits.to_vec();
+        if self.guards.is_empty() && lits.is_empty() {
+            self.write("(check-sat)\n").await?;
+        } else {
+            let guards = self
+                .guards
+                .iter()
+                .fold(String::new(), |acc, id| format!("{acc} s{id}"));
+            let lits = lits
+                .iter()
+                .fold(String::new(), |acc, (i, value)| match value {
+                    true => format!("{acc} v{i}"),
+                    false => format!("{acc} (not v{i})"),
+                });
+            self.write(&format!("(check-sat-assuming ({guards}{lits}))\n"))
+                .await?;
+        }
+        let ret = maybe_cancel(&self.cancel, self.stdout.next_line())
+            .await??
+            .ok_or("failed to get line")?;
+        match ret.trim() {
+            "sat" => Ok(SatResult::Sat),
+            "unsat" => Ok(SatResult::Unsat),
+            "unknown" => Ok(SatResult::Unknown),
+            s => Err(format!("Bad response {s}"))?,
+        }
+    }
+    //limits are part of the loaded source, later changes are send directly
+    async fn update_limits(&mut self) -> Result<()> {
+        if !self.loaded {
+            return Ok(());
+        }
+        let limits = self.dialect.limits(self.timeout, self.rlimit);
+        self.write(&limits).await
+    }
+    async fn read_block(&mut self) -> Result<String> {
+        let mut out = String::new();
+        let mut nesting = 0;
+        while let Some(line) = maybe_cancel(&self.cancel, self.stdout.next_line()).await?? {
+            let _ = write!(out, " {}", line);
+            for i in line.chars() {
+                match i {
+                    '(' => nesting += 1,
+                    ')' => nesting -= 1,
+                    _ => {}
+                }
+            }
+            if nesting == 0 {
+                break;
+            }
+        }
+        Ok(out)
+    }
+}
+#[tower_lsp::async_trait]
+impl SolverBackend for SmtSolver {
+    async fn load(&mut self, module: &SMTModule, base: &Module) -> Result<()> {
+        let mut source = String::new();
+        if self.loaded {
+            source.push_str("(reset)\n");
+        }
+        //options are lost on reset
+        source.push_str(self.dialect.options());
+        source.push_str(&self.dialect.limits(self.timeout, self.rlimit));
+        source.push_str(&module.to_source(base, self.logic.as_deref()));
+        self.write(&source).await?;
+        self.types = module.variables.iter().map(|v| base.type_of(*v)).collect();
+        self.guards.clear();
+        self.loaded = true;
+        Ok(())
+    }
+    async fn assert(&mut self, expr: &Expr) -> Result<()> {
+        let cmd = SMTModule::assert_to_source(0, &None, expr, false);
+        self.write(&cmd).await
+    }
+    async fn add(&mut self, asserts: &[(usize, &Expr)]) -> Result<()> {
+        let mut source = String::new();
+        for (id, expr) in asserts {
+            let _ = write!(source, "(declare-const s{id} Bool)(assert (=> s{id} ");
+            SMTModule::expr_to_source(expr, &mut source);
+            source.push_str("))\n");
+            self.guards.insert(*id);
+        }
+        self.write(&source).await
+    }
+    async fn retract(&mut self, ids: &[usize]) -> Result<()> {
+        let mut source = String::new();
+        for id in ids {
+            if self.guards.remove(id) {
+                //lets the solver drop the assert for good
+                let _ = writeln!(source, "(assert (not s{id}))");
+            }
+        }
+        self.write(&source).await
+    }
+    async fn push(&mut self) -> Result<()> {
+        self.write("(push 1)\n").await
+    }
+    async fn pop(&mut self) -> Result<()> {
+        self.write("(pop 1)\n").await
+    }
+    async fn check_sat(&mut self) -> Result<SatResult> {
+        self.check(&[]).await
+    }
+    async fn check_assuming(&mut self, lits: &[(usize, bool)]) -> Result<SatResult> {
+        self.check(lits).await
+    }
+    async fn values(&mut self, vars: &[usize]) -> Result<Vec<(usize, ConfigValue)>> {
+        if vars.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = vars
+            .iter()
+            .fold(String::new(), |acc, i| format!("{acc} v{i}"));
+        self.write(&format!("(get-value ({query}))\n")).await?;
+        let values = self.read_block().await?;
+        let types = &self.types;
+        Ok(values_by_index(&values, |i| types[i]).collect())
+    }
+    async fn unsat_core(&mut self) -> Result<Vec<usize>> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"\ba(\d+)").unwrap();
+            static ref RE_GUARD: Regex = Regex::new(r"\bs(\d+)").unwrap();
+        };
+        self.write("(get-unsat-core)\n").await?;
+        let core = self.read_block().await?;
+        let mut ids: Vec<usize> = RE
+            .captures_iter(&core)
+            .filter_map(|i| i[1].parse().ok())
+            .collect();
+        if !self.guards.is_empty() {
+            self.write("(get-unsat-assumptions)\n").await?;
+            let assumptions = self.read_block().await?;
+            ids.extend(
+                RE_GUARD
+                    .captures_iter(&assumptions)
+                    .filter_map(|i| i[1].parse::<usize>().ok()),
+            );
+        }
+        Ok(ids)
+    }
+    async fn unsat_assumptions(&mut self) -> Result<Vec<usize>> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"\(\s*not\s+v(\d+)\s*\)|\bv(\d+)\b").unwrap();
+        };
+        self.write("(get-unsat-assumptions)\n").await?;
+        let core = self.read_block().await?;
+        Ok(RE
+            .captures_iter(&core)
+            .filter_map(|i| match (i.get(1), i.get(2)) {
+                (Some(v), _) => Some((v.as_str().parse::<usize>().ok()?, false)),
+                (_, Some(v)) => Some((v.as_str().parse::<usize>().ok()?, true)),
+                _ => None,
+            })
+            .filter_map(|lit| self.assumed.iter().position(|a| *a == lit))
+            .collect())
+    }
+    async fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.timeout = timeout;
+        self.update_limits().await
+    }
+    async fn set_rlimit(&mut self, rlimit: Option<u64>) -> Result<()> {
+        self.rlimit = rlimit;
+        self.update_limits().await
+    }
+    fn set_cancel(&mut self, cancel: &CancellationToken) {
+        self.cancel = cancel.clone();
+    }
+}
+pub fn can_run_solver() -> bool {
+    Command::new(&settings().smt.solver_path)
+        .stdin(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .is_ok()
+}
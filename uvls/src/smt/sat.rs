@@ -0,0 +1,526 @@
+use std::ops::Not;
+//Small CDCL SAT solver for the embedded backend: two watched literals, first UIP learning,
+//VSIDS with phase saving and Luby restarts. Assumptions are decided first, if one of them is
+//refuted the solver returns the assumptions that caused it, which gives us unsat cores.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Lit(u32);
+impl Lit {
+    pub fn new(var: usize, positive: bool) -> Self {
+        Lit((var as u32) << 1 | (!positive) as u32)
+    }
+    pub fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+    pub fn is_positive(self) -> bool {
+        self.0 & 1 == 0
+    }
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+impl Not for Lit {
+    type Output = Lit;
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum Solution {
+    Sat(Vec<bool>),
+    //the assumptions that can't be true together
+    Unsat(Vec<Lit>),
+}
+//Unassigned variables ordered by activity
+#[derive(Default)]
+struct VarOrder {
+    heap: Vec<usize>,
+    position: Vec<Option<usize>>,
+}
+impl VarOrder {
+    fn contains(&self, v: usize) -> bool {
+        self.position[v].is_some()
+    }
+    fn insert(&mut self, v: usize, activity: &[f64]) {
+        if v >= self.position.len() {
+            self.position.resize(v + 1, None);
+        }
+        if self.contains(v) {
+            return;
+        }
+        self.position[v] = Some(self.heap.len());
+        self.heap.push(v);
+        self.sift_up(self.heap.len() - 1, activity);
+    }
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop().unwrap();
+        self.position[top] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.position[last] = Some(0);
+            self.sift_down(0, activity);
+        }
+        Some(top)
+    }
+    //the activity of v increased
+    fn bumped(&mut self, v: usize, activity: &[f64]) {
+        if let Some(i) = self.position[v] {
+            self.sift_up(i, activity);
+        }
+    }
+    fn swap(&mut self, i: usize, j: usize) {
+        self.heap.swap(i, j);
+        self.position[self.heap[i]] = Some(i);
+        self.position[self.heap[j]] = Some(j);
+    }
+    fn sift_up(&mut self, mut i: usize, activity: &[f64]) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if activity[self.heap[parent]] >= activity[self.heap[i]] {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+    }
+    fn sift_down(&mut self, mut i: usize, activity: &[f64]) {
+        loop {
+            let (l, r) = (2 * i + 1, 2 * i + 2);
+            let mut max = i;
+            if l < self.heap.len() && activity[self.heap[l]] > activity[self.heap[max]] {
+                max = l;
+            }
+            if r < self.heap.len() && activity[self.heap[r]] > activity[self.heap[max]] {
+                max = r;
+            }
+            if max == i {
+                break;
+            }
+            self.swap(i, max);
+            i = max;
+        }
+    }
+}
+fn luby(mut i: u64) -> u64 {
+    let (mut size, mut seq) = (1, 0);
+    while size < i + 1 {
+        seq += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != i {
+        size = (size - 1) >> 1;
+        seq -= 1;
+        i %= size;
+    }
+    1 << seq
+}
+pub struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    //clauses to visit when a literal becomes true, they watch its negation
+    watches: Vec<Vec<usize>>,
+    assign: Vec<Option<bool>>,
+    level: Vec<usize>,
+    reason: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    trail_lim: Vec<usize>,
+    qhead: usize,
+    activity: Vec<f64>,
+    var_inc: f64,
+    order: VarOrder,
+    phase: Vec<bool>,
+    seen: Vec<bool>,
+    //false once the clauses are unsat without assumptions
+    ok: bool,
+    conflicts: u64,
+    //learnt clauses, they are never removed
+    learnt: usize,
+}
+impl Default for Solver {
+    fn default() -> Self {
+        Self {
+            clauses: Vec::new(),
+            watches: Vec::new(),
+            assign: Vec::new(),
+            level: Vec::new(),
+            reason: Vec::new(),
+            trail: Vec::new(),
+            trail_lim: Vec::new(),
+            qhead: 0,
+            activity: Vec::new(),
+            var_inc: 1.0,
+            order: VarOrder::default(),
+            phase: Vec::new(),
+            seen: Vec::new(),
+            ok: true,
+            conflicts: 0,
+            learnt: 0,
+        }
+    }
+}
+fn value(assign: &[Option<bool>], l: Lit) -> Option<bool> {
+    assign[l.var()].map(|b| b == l.is_positive())
+}
+impl Solver {
+    pub fn new_var(&mut self) -> usize {
+        let v = self.assign.len();
+        self.assign.push(None);
+        self.level.push(0);
+        self.reason.push(None);
+        self.activity.push(0.0);
+        self.phase.push(false);
+        self.seen.push(false);
+        self.watches.push(Vec::new());
+        self.watches.push(Vec::new());
+        self.order.insert(v, &self.activity);
+        v
+    }
+    pub fn learnt(&self) -> usize {
+        self.learnt
+    }
+    fn decision_level(&self) -> usize {
+        self.trail_lim.len()
+    }
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        if !self.ok {
+            return;
+        }
+        self.backtrack(0);
+        let mut clause = lits.to_vec();
+        clause.sort();
+        clause.dedup();
+        //complementary literals are adjacent after sorting
+        if clause.windows(2).any(|w| w[0] == !w[1]) {
+            return;
+        }
+        if clause.iter().any(|l| value(&self.assign, *l) == Some(true)) {
+            return;
+        }
+        clause.retain(|l| value(&self.assign, *l).is_none());
+        match clause.len() {
+            0 => self.ok = false,
+            1 => {
+                self.enqueue(clause[0], None);
+                if self.propagate().is_some() {
+                    self.ok = false;
+                }
+            }
+            _ => {
+                self.attach(clause);
+            }
+        }
+    }
+    fn attach(&mut self, clause: Vec<Lit>) -> usize {
+        let i = self.clauses.len();
+        self.watches[(!clause[0]).index()].push(i);
+        self.watches[(!clause[1]).index()].push(i);
+        self.clauses.push(clause);
+        i
+    }
+    fn enqueue(&mut self, l: Lit, reason: Option<usize>) {
+        let v = l.var();
+        self.assign[v] = Some(l.is_positive());
+        self.level[v] = self.decision_level();
+        self.reason[v] = reason;
+        self.trail.push(l);
+    }
+    //Unit propagation, returns a conflicting clause
+    fn propagate(&mut self) -> Option<usize> {
+        while self.qhead < self.trail.len() {
+            let p = self.trail[self.qhead];
+            self.qhead += 1;
+            let false_lit = !p;
+            let mut watches = std::mem::take(&mut self.watches[p.index()]);
+            let (mut i, mut j) = (0, 0);
+            let mut conflict = None;
+            while i < watches.len() {
+                let ci = watches[i];
+                i += 1;
+                let clause = &mut self.clauses[ci];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                if value(&self.assign, clause[0]) == Some(true) {
+                    watches[j] = ci;
+                    j += 1;
+                    continue;
+                }
+                if let Some(k) =
+                    (2..clause.len()).find(|k| value(&self.assign, clause[*k]) != Some(false))
+                {
+                    clause.swap(1, k);
+                    self.watches[(!clause[1]).index()].push(ci);
+                    continue;
+                }
+                watches[j] = ci;
+                j += 1;
+                let first = clause[0];
+                if value(&self.assign, first) == Some(false) {
+                    conflict = Some(ci);
+                    while i < watches.len() {
+                        watches[j] = watches[i];
+                        j += 1;
+                        i += 1;
+                    }
+                } else {
+                    self.enqueue(first, Some(ci));
+                }
+            }
+            watches.truncate(j);
+            watches.append(&mut self.watches[p.index()]);
+            self.watches[p.index()] = watches;
+            if conflict.is_some() {
+                self.qhead = self.trail.len();
+                return conflict;
+            }
+        }
+        None
+    }
+    fn bump(&mut self, v: usize) {
+        self.activity[v] += self.var_inc;
+        if self.activity[v] > 1e100 {
+            for a in self.activity.iter_mut() {
+                *a *= 1e-100;
+            }
+            self.var_inc *= 1e-100;
+        }
+        self.order.bumped(v, &self.activity);
+    }
+    //First UIP clause of a conflict and the level to jump back to
+    fn analyze(&mut self, mut conflict: usize) -> (Vec<Lit>, usize) {
+        let mut learnt = vec![Lit(0)];
+        let mut open = 0;
+        let mut index = self.trail.len();
+        let mut skip = 0;
+        let uip = loop {
+            for k in skip..self.clauses[conflict].len() {
+                let q = self.clauses[conflict][k];
+                let v = q.var();
+                if !self.seen[v] && self.level[v] > 0 {
+                    self.seen[v] = true;
+                    self.bump(v);
+                    if self.level[v] >= self.decision_level() {
+                        open += 1;
+                    } else {
+                        learnt.push(q);
+                    }
+                }
+            }
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index].var()] {
+                    break;
+                }
+            }
+            let p = self.trail[index];
+            self.seen[p.var()] = false;
+            open -= 1;
+            if open == 0 {
+                break p;
+            }
+            conflict = self.reason[p.var()].unwrap();
+            skip = 1;
+        };
+        learnt[0] = !uip;
+        for l in learnt[1..].iter() {
+            self.seen[l.var()] = false;
+        }
+        let mut level = 0;
+        if learnt.len() > 1 {
+            let max = (1..learnt.len())
+                .max_by_key(|i| self.level[learnt[*i].var()])
+                .unwrap();
+            learnt.swap(1, max);
+            level = self.level[learnt[1].var()];
+        }
+        (learnt, level)
+    }
+    //The assumptions that imply the negation of the assumption failed, including failed
+    fn analyze_final(&mut self, failed: Lit) -> Vec<Lit> {
+        let mut core = vec![failed];
+        if self.level[failed.var()] == 0 {
+            return core;
+        }
+        self.seen[failed.var()] = true;
+        for i in (self.trail_lim[0]..self.trail.len()).rev() {
+            let v = self.trail[i].var();
+            if !self.seen[v] {
+                continue;
+            }
+            match self.reason[v] {
+                //decisions below the search are assumptions
+                None => core.push(self.trail[i]),
+                Some(c) => {
+                    for q in self.clauses[c][1..].iter() {
+                        if self.level[q.var()] > 0 {
+                            self.seen[q.var()] = true;
+                        }
+                    }
+                }
+            }
+            self.seen[v] = false;
+        }
+        self.seen[failed.var()] = false;
+        core
+    }
+    fn backtrack(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
+        }
+        let lim = self.trail_lim[level];
+        for l in self.trail.drain(lim..).rev() {
+            let v = l.var();
+            self.assign[v] = None;
+            self.reason[v] = None;
+            self.phase[v] = l.is_positive();
+            self.order.insert(v, &self.activity);
+        }
+        self.trail_lim.truncate(level);
+        self.qhead = self.trail.len();
+    }
+    //Decide the clauses with all assumptions true, returns None if the search ran out of its
+    //conflict budget or stop interrupted it
+    pub fn solve(
+        &mut self,
+        assumptions: &[Lit],
+        budget: Option<u64>,
+        stop: &dyn Fn() -> bool,
+    ) -> Option<Solution> {
+        if !self.ok {
+            return Some(Solution::Unsat(Vec::new()));
+        }
+        self.backtrack(0);
+        if self.propagate().is_some() {
+            self.ok = false;
+            return Some(Solution::Unsat(Vec::new()));
+        }
+        let limit = budget.map(|b| self.conflicts + b);
+        let mut restarts = 0;
+        let mut next_restart = self.conflicts + 100;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                self.conflicts += 1;
+                if self.decision_level() == 0 {
+                    self.ok = false;
+                    return Some(Solution::Unsat(Vec::new()));
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let first = learnt[0];
+                    self.learnt += 1;
+                    let ci = self.attach(learnt);
+                    self.enqueue(first, Some(ci));
+                }
+                self.var_inc /= 0.95;
+                if limit.map(|l| self.conflicts >= l).unwrap_or(false)
+                    || (self.conflicts % 64 == 0 && stop())
+                {
+                    self.backtrack(0);
+                    return None;
+                }
+                continue;
+            }
+            if self.conflicts >= next_restart {
+                restarts += 1;
+                next_restart = self.conflicts + 100 * luby(restarts);
+                self.backtrack(0);
+                if stop() {
+                    return None;
+                }
+            }
+            let mut decision = None;
+            while self.decision_level() < assumptions.len() {
+                let a = assumptions[self.decision_level()];
+                match value(&self.assign, a) {
+                    //keep the levels of the assumptions aligned
+                    Some(true) => self.trail_lim.push(self.trail.len()),
+                    Some(false) => {
+                        let core = self.analyze_final(a);
+                        self.backtrack(0);
+                        return Some(Solution::Unsat(core));
+                    }
+                    None => {
+                        decision = Some(a);
+                        break;
+                    }
+                }
+            }
+            let decision = match decision {
+                Some(a) => a,
+                None => loop {
+                    match self.order.pop(&self.activity) {
+                        Some(v) if self.assign[v].is_none() => break Lit::new(v, self.phase[v]),
+                        Some(_) => {}
+                        None => {
+                            let model = self.assign.iter().map(|v| v.unwrap_or(false)).collect();
+                            self.backtrack(0);
+                            return Some(Solution::Sat(model));
+                        }
+                    }
+                },
+            };
+            self.trail_lim.push(self.trail.len());
+            self.enqueue(decision, None);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn lit(v: i32) -> Lit {
+        Lit::new(v.unsigned_abs() as usize - 1, v > 0)
+    }
+    fn solver(vars: usize, clauses: &[&[i32]]) -> Solver {
+        let mut s = Solver::default();
+        for _ in 0..vars {
+            s.new_var();
+        }
+        for c in clauses {
+            s.add_clause(&c.iter().map(|l| lit(*l)).collect::<Vec<_>>());
+        }
+        s
+    }
+    #[test]
+    fn test_sat() {
+        let mut s = solver(3, &[&[1, 2], &[-1, 3], &[-2, 3], &[-3, -1]]);
+        let Some(Solution::Sat(model)) = s.solve(&[], None, &|| false) else {
+            panic!()
+        };
+        assert_eq!(model, vec![false, true, true]);
+    }
+    #[test]
+    fn test_pigeonhole() {
+        //4 pigeons in 3 holes, p(i, j) = pigeon i sits in hole j
+        let p = |i: i32, j: i32| i * 3 + j + 1;
+        let mut clauses: Vec<Vec<i32>> =
+            (0..4).map(|i| (0..3).map(|j| p(i, j)).collect()).collect();
+        for j in 0..3 {
+            for a in 0..4 {
+                for b in a + 1..4 {
+                    clauses.push(vec![-p(a, j), -p(b, j)]);
+                }
+            }
+        }
+        let clauses: Vec<&[i32]> = clauses.iter().map(|c| c.as_slice()).collect();
+        let mut s = solver(12, &clauses);
+        assert_eq!(s.solve(&[], None, &|| false), Some(Solution::Unsat(vec![])));
+    }
+    #[test]
+    fn test_assumptions() {
+        //4 -> 1, 5 -> !1, 6 -> 2
+        let mut s = solver(6, &[&[-4, 1], &[-5, -1], &[-6, 2]]);
+        let Some(Solution::Unsat(mut core)) = s.solve(&[lit(4), lit(6), lit(5)], None, &|| false)
+        else {
+            panic!()
+        };
+        core.sort();
+        assert_eq!(core, vec![lit(4), lit(5)]);
+        assert!(matches!(
+            s.solve(&[lit(4), lit(6)], None, &|| false),
+            Some(Solution::Sat(_))
+        ));
+    }
+}
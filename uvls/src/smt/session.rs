@@ -0,0 +1,183 @@
+use super::{
+    backend::{create_backend, SatResult, SolverBackend},
+    embedded::Embedded,
+    Assert, Expr, SMTModule,
+};
+use crate::core::*;
+use hashbrown::HashMap;
+use indexmap::IndexSet;
+use log::info;
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+//Long lived solver for one module. Loading a new SMTModule diffs its asserts against the
+//active ones by their source, so after an edit only the changed asserts are added or retracted.
+//The backend is rebuilt when the variables change, since asserts refer to them by index.
+//Asserts without a name are never part of a core, so they are asserted for good and a change
+//of them rebuilds the backend as well.
+
+//learnt clauses a session keeps before its backend is rebuilt
+const MAX_LEARNT: usize = 50000;
+
+#[derive(Default)]
+pub struct Session {
+    backend: Option<Box<dyn SolverBackend>>,
+    cancel: CancellationToken,
+    variables: IndexSet<ModuleSymbol>,
+    //backend was picked for a Boolean module
+    boolean: bool,
+    //sources of the asserts without a name
+    fixed: Vec<String>,
+    //ids of the active asserts by their source, equal asserts share a key
+    active: HashMap<String, Vec<usize>>,
+    //index in the loaded SMTModule of each active id
+    index: HashMap<usize, usize>,
+    next_id: usize,
+    //retracted asserts and popped scopes
+    stale: usize,
+}
+impl Session {
+    fn backend(&mut self) -> Result<&mut Box<dyn SolverBackend>> {
+        self.backend
+            .as_mut()
+            .ok_or_else(|| "session is not loaded".into())
+    }
+    fn needs_rebuild(&self, module: &SMTModule, boolean: bool, fixed: &[String]) -> bool {
+        self.backend.is_none()
+            || !self.variables.iter().eq(module.variables.iter())
+            || self.fixed != fixed
+            || (settings().smt.backend == BackendKind::Auto && self.boolean != boolean)
+            //retracted asserts and popped scopes still take space in the solver
+            || self.stale > self.index.len()
+            || self.learnt() > MAX_LEARNT
+    }
+    async fn rebuild(
+        &mut self,
+        module: &SMTModule,
+        base: &Module,
+        fixed: Vec<String>,
+    ) -> Result<()> {
+        self.backend = None;
+        let variables = SMTModule {
+            variables: module.variables.clone(),
+            asserts: Vec::new(),
+        };
+        let mut backend = create_backend(module, base, &self.cancel).await?;
+        backend.load(&variables, base).await?;
+        for Assert(_, expr) in module.asserts.iter().filter(|a| a.0.is_none()) {
+            backend.assert(expr).await?;
+        }
+        self.backend = Some(backend);
+        self.fixed = fixed;
+        self.variables = module.variables.clone();
+        self.boolean = Embedded::supports(module, base);
+        self.active.clear();
+        self.index.clear();
+        self.stale = 0;
+        Ok(())
+    }
+}
+#[tower_lsp::async_trait]
+impl SolverBackend for Session {
+    async fn load(&mut self, module: &SMTModule, base: &Module) -> Result<()> {
+        let time = Instant::now();
+        let source = |expr: &Expr| {
+            let mut key = String::new();
+            SMTModule::expr_to_source(expr, &mut key);
+            key
+        };
+        let fixed: Vec<String> = module
+            .asserts
+            .iter()
+            .filter(|a| a.0.is_none())
+            .map(|a| source(&a.1))
+            .collect();
+        if self.needs_rebuild(module, Embedded::supports(module, base), &fixed) {
+            info!("rebuild solver session");
+            self.rebuild(module, base, fixed).await?;
+        }
+        let mut old = std::mem::take(&mut self.active);
+        let mut added = Vec::new();
+        self.index.clear();
+        for (i, Assert(info, expr)) in module.asserts.iter().enumerate() {
+            if info.is_none() {
+                continue;
+            }
+            let key = source(expr);
+            let id = match old.get_mut(&key).and_then(|ids| ids.pop()) {
+                Some(id) => id,
+                None => {
+                    self.next_id += 1;
+                    added.push((self.next_id, expr));
+                    self.next_id
+                }
+            };
+            self.index.insert(id, i);
+            self.active.entry(key).or_default().push(id);
+        }
+        let retract: Vec<usize> = old.into_values().flatten().collect();
+        self.stale += retract.len();
+        let backend = self.backend()?;
+        backend.retract(&retract).await?;
+        backend.add(&added).await?;
+        info!(
+            "sync solver session: {} added, {} retracted in {:?}",
+            added.len(),
+            retract.len(),
+            time.elapsed()
+        );
+        Ok(())
+    }
+    async fn assert(&mut self, expr: &Expr) -> Result<()> {
+        self.backend()?.assert(expr).await
+    }
+    async fn add(&mut self, asserts: &[(usize, &Expr)]) -> Result<()> {
+        self.backend()?.add(asserts).await
+    }
+    async fn retract(&mut self, ids: &[usize]) -> Result<()> {
+        self.stale += ids.len();
+        self.backend()?.retract(ids).await
+    }
+    async fn push(&mut self) -> Result<()> {
+        self.backend()?.push().await
+    }
+    async fn pop(&mut self) -> Result<()> {
+        self.stale += 1;
+        self.backend()?.pop().await
+    }
+    async fn check_sat(&mut self) -> Result<SatResult> {
+        self.backend()?.check_sat().await
+    }
+    async fn check_assuming(&mut self, lits: &[(usize, bool)]) -> Result<SatResult> {
+        self.backend()?.check_assuming(lits).await
+    }
+    async fn unsat_assumptions(&mut self) -> Result<Vec<usize>> {
+        self.backend()?.unsat_assumptions().await
+    }
+    async fn values(&mut self, vars: &[usize]) -> Result<Vec<(usize, ConfigValue)>> {
+        self.backend()?.values(vars).await
+    }
+    //Maps the ids back to the indices of the loaded module
+    async fn unsat_core(&mut self) -> Result<Vec<usize>> {
+        let core = self.backend()?.unsat_core().await?;
+        Ok(core
+            .into_iter()
+            .filter_map(|id| self.index.get(&id).cloned())
+            .collect())
+    }
+    async fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.backend()?.set_timeout(timeout).await
+    }
+    async fn set_rlimit(&mut self, rlimit: Option<u64>) -> Result<()> {
+        self.backend()?.set_rlimit(rlimit).await
+    }
+    fn set_cancel(&mut self, cancel: &CancellationToken) {
+        self.cancel = cancel.clone();
+        if let Some(backend) = self.backend.as_mut() {
+            backend.set_cancel(cancel);
+        }
+    }
+    fn learnt(&self) -> usize {
+        self.backend.as_ref().map_or(0, |b| b.learnt())
+    }
+}
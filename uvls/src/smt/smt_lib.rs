@@ -109,64 +109,21 @@ impl SMTModule {
             )
         })
     }
-    //extract values from a response string
-    pub fn parse_values<'a>(
-        &'a self,
-        values: &'a str,
-        module: &'a Module,
-    ) -> impl Iterator<Item = (ModuleSymbol, ConfigValue)> + 'a {
-        super::parse::iter_values(self, module, values)
-        /*
-        //This abomination parses a identifier with a bool, negative number, positive number or a string
-        //The problem is that z3 encodes numbers using nested expressions instead of simple floating point
-        //values. Hopefully all cases are covered here...
-        //TODO replace this with a true eval parser(best use NOM)
-
-        lazy_static! {
-            static ref RE: Regex = Regex::new(
-                r#"\(\s*v(\d+)\s+(?:(true|false)|\(- ((?:[0-9]*\.)?[0-9]+)\??|((?:[0-9]*\.)?[0-9]+)\??|"([^"]*)")\s*\)"#
-            )
-            .unwrap();
-        };
-        //info!("{values}");
-        RE.captures_iter(values).map(|i| {
-            let idx: usize = i[1].parse().unwrap();
-            let var = *self.variables.get_index(idx).unwrap();
-            (
-                var,
-                match (i.get(2), i.get(3), i.get(4), i.get(5)) {
-                    (Some(b), _, _, _) => ConfigValue::Bool(match b.as_str() {
-                        "true" => true,
-                        _ => false,
-                    }),
-                    (_, Some(num), _, _) => {
-                        ConfigValue::Number(-(num.as_str().parse::<f64>().unwrap()))
-                    }
-                    (_, _, Some(num), _) => ConfigValue::Number(num.as_str().parse().unwrap()),
-                    (_, _, _, Some(s)) => ConfigValue::String(s.as_str().into()),
-                    _ => unreachable!(),
-                },
-            )
-        })
-        */
-    }
-    pub fn parse_unsat_core<'a>(&'a self, core: &'a str) -> impl Iterator<Item = AssertInfo> + 'a {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"a(\d+)").unwrap();
-        };
-        RE.captures_iter(core).filter_map(|i| {
-            let idx: usize = i[1].parse().unwrap();
-            self.asserts[idx].0.clone()
-        })
-    }
-    // create source to config z3 Solver
-    pub fn config_to_source(&self) -> String {
-        let out = "(set-option :produce-unsat-cores true)
-        (define-fun smooth_div ((x Real) (y Real)) Real(if (not (= y 0.0))(/ x y)0.0))
-        (define-fun floor ((x Real)) Int (to_int x))
-        (define-fun ceil ((x Real)) Int (ite (= (to_int x) x) (to_int x) (to_int (+ x 1)) ))
-        (set-option :smt.core.minimize true)\n"
+    // create source to config the solver, solver specific options are set by the backend.
+    // The logic has to follow the standard options and precede all definitions.
+    pub fn config_to_source(&self, logic: Option<&str>) -> String {
+        let mut out = "(set-option :produce-unsat-cores true)
+        (set-option :produce-unsat-assumptions true)
+        (set-option :produce-models true)\n"
             .to_string();
+        if let Some(logic) = logic {
+            let _ = writeln!(out, "(set-logic {logic})");
+        }
+        out.push_str(
+            "(define-fun smooth_div ((x Real) (y Real)) Real(ite (not (= y 0.0))(/ x y)0.0))
+        (define-fun floor ((x Real)) Int (to_int x))
+        (define-fun ceil ((x Real)) Int (ite (= (to_int x) x) (to_int x) (to_int (+ x 1)) ))\n",
+        );
         out
     }
     // create with all Variable the source for the SMTSolver
@@ -180,13 +137,7 @@ impl SMTModule {
     }
 
     // create the source for an assert, if the assert should be negated the variable neg must be true
-    pub fn assert_to_source(
-        &self,
-        i: usize,
-        info: &Option<AssertInfo>,
-        expr: &Expr,
-        not: bool,
-    ) -> String {
+    pub fn assert_to_source(i: usize, info: &Option<AssertInfo>, expr: &Expr, not: bool) -> String {
         let mut out = "".to_string();
         let _ = write!(out, "(assert");
 
@@ -196,6 +147,21 @@ impl SMTModule {
         if not {
             let _ = write!(out, "( not ");
         }
+        Self::expr_to_source(expr, &mut out);
+        if not {
+            let _ = write!(out, " )");
+        }
+        //name tag
+        if info.is_some() {
+            let _ = write!(out, " :named a{i})");
+        }
+
+        let _ = writeln!(out, ")");
+        out
+    }
+
+    // append the source of an expression
+    pub fn expr_to_source(expr: &Expr, out: &mut String) {
         #[derive(Debug)]
         enum CExpr<'a> {
             Expr(&'a Expr),
@@ -324,26 +290,16 @@ impl SMTModule {
                 }
             }
         }
-        if not {
-            let _ = write!(out, " )");
-        }
-        //name tag
-        if info.is_some() {
-            let _ = write!(out, " :named a{i})");
-        }
-
-        let _ = write!(out, ")\n");
-        out
     }
 
     //tree to source
-    pub fn to_source(&self, module: &Module) -> String {
+    pub fn to_source(&self, module: &Module, logic: Option<&str>) -> String {
         let time = Instant::now();
         //
-        let mut out = self.config_to_source();
+        let mut out = self.config_to_source(logic);
         let _ = writeln!(out, "{}", self.variable_to_source(module));
         for (i, Assert(info, expr)) in self.asserts.iter().enumerate() {
-            let _ = writeln!(out, "{}", self.assert_to_source(i, info, expr, false));
+            let _ = writeln!(out, "{}", Self::assert_to_source(i, info, expr, false));
         }
         info!("model to string  in {:?}", time.elapsed());
         out
@@ -351,12 +307,13 @@ impl SMTModule {
     pub fn var(&self, ms: ModuleSymbol) -> usize {
         self.variables.get_index_of(&ms).unwrap()
     }
-    pub fn pseudo_bool(&self, ms: ModuleSymbol, module: &Module) -> String {
+    pub fn pseudo_bool(&self, ms: ModuleSymbol, module: &Module) -> Expr {
         let ms = module.resolve_value(ms);
+        let var = Expr::Var(self.var(ms));
         match module.type_of(ms) {
-            Type::Bool => format!("v{}", self.var(ms)),
-            Type::Real => format!("(not(= v{} 0.0))", self.var(ms)),
-            Type::String => format!(r#"(not(= v{} ""))"#, self.var(ms)),
+            Type::Bool => var,
+            Type::Real => Expr::Not(Expr::Equal(vec![var, Expr::Real(0.0)]).into()),
+            Type::String => Expr::Not(Expr::Equal(vec![var, Expr::String("".into())]).into()),
             _ => unimplemented!(),
         }
     }
@@ -1,7 +1,7 @@
 use crate::{
     core::*,
     ide,
-    smt::{self, uvl2smt, SmtSolver},
+    smt::{self, new_backend, uvl2smt},
 };
 use axum::{
     extract::{ws::WebSocketUpgrade, Path},
@@ -517,29 +517,18 @@ async fn ui_event_loop(
 
                 let config_module = tx_config.borrow().module.clone();
                 let smt_module = uvl2smt(&config_module.module, &config_module.values);
-                let solver = SmtSolver::new(
-                    smt_module.to_source(&config_module),
+                let solver = new_backend(
+                    &smt_module,
+                    &config_module.module,
                     &CancellationToken::new(),
                 )
                 .await;
 
                 match solver {
                     Ok(mut smt_solver) => match smt_solver.check_sat().await {
-                        Ok(true) => {
-                            let query = smt_module
-                                .variables
-                                .iter()
-                                .enumerate()
-                                .fold(String::new(), |acc, (i, _)| format!("{acc} v{i}"));
-
-                            let values = smt_solver
-                                .values(query.clone())
-                                .await
-                                .unwrap_or(String::from(""));
-
-                            let values_parsed: HashMap<ModuleSymbol, ConfigValue> = smt_module
-                                .parse_values(&values, &config_module.module)
-                                .collect();
+                        Ok(smt::SatResult::Sat) => {
+                            let values_parsed: HashMap<ModuleSymbol, ConfigValue> =
+                                smt_solver.model(&smt_module).await.unwrap_or_default();
 
                             // Store solution in file
                             tokio::task::spawn_blocking(move || {
@@ -724,6 +713,16 @@ async fn ui_event_loop(
                             }
                         }
                     }
+                    smt::SMTModel::UNKNOWN => {
+                        ui_state.with_mut(|state| {
+                            state.sat = SatState::ERR(smt::inconclusive());
+                            state.solver_active = false;
+                        });
+                        for i in entries.values_mut() {
+                            i.update_smt(None);
+                            i.unsat(false);
+                        }
+                    }
                 });
             }
         }